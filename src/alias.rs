@@ -0,0 +1,296 @@
+use std::collections::BTreeMap;
+
+use crate::error::TuduError;
+
+/// Expands the command the args start with when it is an alias, giving the args for each command
+/// to run in turn. The args after an alias fill in its `$1`, `$2`... and `$@` placeholders, and
+/// any not used by a `$1` placeholder or the like are added to the end of its last command.
+/// Aliases can be made of other aliases but not of themselves, and cannot have the name of one of
+/// the commands tudu has.
+pub fn expand_aliases(
+    aliases: &BTreeMap<String, String>,
+    builtins: &[String],
+    args: Vec<String>,
+) -> Result<Vec<Vec<String>>, TuduError> {
+    if let Some(name) = aliases.keys().find(|name| builtins.contains(name)) {
+        return Err(TuduError::InvalidConfig(format!(
+            "the alias `{name}` has the name of a command tudu has, which an alias cannot replace"
+        )));
+    }
+
+    let Some((program, command)) = args.split_first() else {
+        return Ok(vec![args]);
+    };
+
+    let commands = expand(aliases, command.to_vec(), &mut Vec::new())?;
+
+    Ok(commands
+        .into_iter()
+        .map(|command| std::iter::once(program.clone()).chain(command).collect())
+        .collect())
+}
+
+fn expand(
+    aliases: &BTreeMap<String, String>,
+    command: Vec<String>,
+    expanding: &mut Vec<String>,
+) -> Result<Vec<Vec<String>>, TuduError> {
+    let Some((name, args)) = command.split_first() else {
+        return Ok(vec![command]);
+    };
+
+    let Some(expansion) = aliases.get(name) else {
+        return Ok(vec![command]);
+    };
+
+    if expanding.contains(name) {
+        expanding.push(name.clone());
+
+        return Err(TuduError::InvalidConfig(format!(
+            "the alias `{name}` runs itself, {}",
+            expanding.join(" → ")
+        )));
+    }
+
+    expanding.push(name.clone());
+
+    let mut expanded = Vec::new();
+
+    for command in fill_in_args(name, expansion, args)? {
+        expanded.extend(expand(aliases, command, expanding)?);
+    }
+
+    expanding.pop();
+
+    Ok(expanded)
+}
+
+fn fill_in_args(
+    name: &str,
+    expansion: &str,
+    args: &[String],
+) -> Result<Vec<Vec<String>>, TuduError> {
+    let mut uses_all = false;
+    let mut used = 0;
+    let mut commands = Vec::new();
+
+    for command in split_commands(name, expansion)? {
+        let mut filled_in = Vec::new();
+
+        for word in command {
+            if word == "$@" {
+                uses_all = true;
+                filled_in.extend(args.iter().cloned());
+                continue;
+            }
+
+            match word.strip_prefix('$').and_then(|n| n.parse::<usize>().ok()) {
+                Some(position) if position > 0 => {
+                    let arg = args.get(position - 1).ok_or_else(|| {
+                        TuduError::InvalidArguments(format!(
+                            "the alias `{name}` needs {position} arguments"
+                        ))
+                    })?;
+
+                    used = used.max(position);
+                    filled_in.push(arg.clone());
+                }
+                _ => filled_in.push(word),
+            }
+        }
+
+        commands.push(filled_in);
+    }
+
+    // Any args left over, such as a date, go on the end
+    if !uses_all {
+        if let Some(last) = commands.last_mut() {
+            last.extend(args[used..].iter().cloned());
+        }
+    }
+
+    Ok(commands)
+}
+
+/// Splits an alias into its commands at each `;` and each command into words at spaces, keeping
+/// anything in quotes together, e.g. `add "Write docs"; view` into `add`, `Write docs` and `view`
+fn split_commands(name: &str, expansion: &str) -> Result<Vec<Vec<String>>, TuduError> {
+    let mut commands = Vec::new();
+    let mut command = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+
+    for character in expansion.chars() {
+        match (quote, character) {
+            (Some(open), character) if character == open => quote = None,
+            (Some(_), character) => word.get_or_insert_with(String::new).push(character),
+            (None, '"' | '\'') => {
+                quote = Some(character);
+                word.get_or_insert_with(String::new);
+            }
+            (None, ';') => {
+                command.extend(word.take());
+                commands.push(std::mem::take(&mut command));
+            }
+            (None, character) if character.is_whitespace() => command.extend(word.take()),
+            (None, character) => word.get_or_insert_with(String::new).push(character),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(TuduError::InvalidConfig(format!(
+            "the alias `{name}` has a quote that is not closed"
+        )));
+    }
+
+    command.extend(word.take());
+    commands.push(command);
+
+    let commands: Vec<Vec<String>> = commands
+        .into_iter()
+        .filter(|command| !command.is_empty())
+        .collect();
+
+    match commands.is_empty() {
+        true => Err(TuduError::InvalidConfig(format!(
+            "the alias `{name}` has nothing to run"
+        ))),
+        false => Ok(commands),
+    }
+}
+
+/// Lists the aliases with what they run, one per line
+pub fn render_aliases(aliases: &BTreeMap<String, String>) -> String {
+    aliases
+        .iter()
+        .map(|(name, expansion)| format!("{name} = {expansion}\n"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aliases(aliases: &[(&str, &str)]) -> BTreeMap<String, String> {
+        aliases
+            .iter()
+            .map(|(name, expansion)| (name.to_string(), expansion.to_string()))
+            .collect()
+    }
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(String::from).collect()
+    }
+
+    fn builtins() -> Vec<String> {
+        args("add complete set view")
+    }
+
+    #[test]
+    fn expand_aliases_adds_args_to_end_without_placeholders() {
+        let aliases = aliases(&[("d", "complete")]);
+
+        let commands = expand_aliases(&aliases, &builtins(), args("tudu d 3 tomorrow")).unwrap();
+
+        assert_eq!(commands, vec![args("tudu complete 3 tomorrow")]);
+    }
+
+    #[test]
+    fn expand_aliases_fills_in_placeholders() {
+        let aliases = aliases(&[("s", "set $1 S"), ("a", "add \"Review $1\" $@")]);
+
+        let set = expand_aliases(&aliases, &builtins(), args("tudu s 2")).unwrap();
+        let set_on_date = expand_aliases(&aliases, &builtins(), args("tudu s 2 tomorrow")).unwrap();
+        let add = expand_aliases(&aliases, &builtins(), args("tudu a 3 tomorrow")).unwrap();
+        let missing = expand_aliases(&aliases, &builtins(), args("tudu s"));
+
+        assert_eq!(set, vec![args("tudu set 2 S")]);
+        assert_eq!(set_on_date, vec![args("tudu set 2 S tomorrow")]);
+        assert_eq!(
+            add,
+            vec![vec![
+                String::from("tudu"),
+                String::from("add"),
+                String::from("Review $1"),
+                String::from("3"),
+                String::from("tomorrow"),
+            ]]
+        );
+        assert_eq!(
+            missing,
+            Err(TuduError::InvalidArguments(String::from(
+                "the alias `s` needs 1 arguments"
+            )))
+        );
+    }
+
+    #[test]
+    fn expand_aliases_runs_each_command_of_macro_through_aliases() {
+        let aliases = aliases(&[("eod", "carry; v tomorrow"), ("v", "view")]);
+
+        let commands = expand_aliases(&aliases, &builtins(), args("tudu eod")).unwrap();
+
+        assert_eq!(
+            commands,
+            vec![args("tudu carry"), args("tudu view tomorrow")]
+        );
+    }
+
+    #[test]
+    fn expand_aliases_leaves_other_commands() {
+        let aliases = aliases(&[("d", "complete")]);
+
+        assert_eq!(
+            expand_aliases(&aliases, &builtins(), args("tudu view d")).unwrap(),
+            vec![args("tudu view d")]
+        );
+        assert_eq!(
+            expand_aliases(&aliases, &builtins(), args("tudu")).unwrap(),
+            vec![args("tudu")]
+        );
+    }
+
+    #[test]
+    fn expand_aliases_gives_error_for_cycle() {
+        let aliases = aliases(&[("a", "b 1"), ("b", "view; c"), ("c", "a")]);
+
+        assert_eq!(
+            expand_aliases(&aliases, &builtins(), args("tudu a")),
+            Err(TuduError::InvalidConfig(String::from(
+                "the alias `a` runs itself, a → b → c → a"
+            )))
+        );
+    }
+
+    #[test]
+    fn expand_aliases_gives_error_for_alias_named_like_a_command() {
+        let aliases = aliases(&[("d", "complete"), ("view", "view tomorrow")]);
+
+        assert_eq!(
+            expand_aliases(&aliases, &builtins(), args("tudu d 3")),
+            Err(TuduError::InvalidConfig(String::from(
+                "the alias `view` has the name of a command tudu has, which an alias cannot replace"
+            )))
+        );
+    }
+
+    #[test]
+    fn split_commands_keeps_quoted_words_together() {
+        let commands = split_commands("w", "add 'Write; docs' ; view \"\" ;").unwrap();
+        let unclosed = split_commands("w", "add \"Write docs");
+
+        assert_eq!(
+            commands,
+            vec![
+                vec![String::from("add"), String::from("Write; docs")],
+                vec![String::from("view"), String::new()],
+            ]
+        );
+        assert_eq!(
+            unclosed,
+            Err(TuduError::InvalidConfig(String::from(
+                "the alias `w` has a quote that is not closed"
+            )))
+        );
+    }
+}
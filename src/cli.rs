@@ -0,0 +1,360 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+const AFTER_HELP: &str = "Dates:
+  Dates can be written in the form 10-6-2023, 10-6 which uses the current year, or with relative date commands `yesterday/today/tomorrow`.
+  If a date is optional in a command and was not specified the command will use the current date
+
+States:
+  ◯ - [N]ot started
+  ◐ - [S]tarted
+  ● - [C]ompleted
+  ► - Carry [F]orward
+  x - [X] Not doing
+  ⊘ - Blocked, waiting on a task that is not finished";
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "tudu",
+    about = "Manage your tasks with a simple but powerful system",
+    after_help = AFTER_HELP,
+    disable_version_flag = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+    /// Use the tasks of this list rather than the default list
+    #[arg(long, short, global = true, value_name = "LIST")]
+    pub list: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CliCommand {
+    /// Add a task
+    Add {
+        /// The task description, e.g. "Example task", optionally ending with an estimate such as ~30m
+        task: String,
+        /// Add the task as a subtask of the task with this number
+        #[arg(long, short, value_name = "INDEX")]
+        parent: Option<usize>,
+        /// The date the task has to be done by, which can be later than the day it is planned on
+        #[arg(long, value_name = "DATE")]
+        due: Option<String>,
+        /// The priority of the task, a letter from A, the most important, to Z
+        #[arg(long, value_name = "PRIORITY")]
+        priority: Option<String>,
+        /// The time of day to be reminded of the task, such as 14:30
+        #[arg(long, value_name = "TIME")]
+        at: Option<String>,
+        #[command(flatten)]
+        date: DateArgs,
+    },
+    /// Remove a task
+    Remove {
+        /// The number of the task, or of a subtask such as 3.1
+        index: String,
+        #[command(flatten)]
+        date: DateArgs,
+    },
+    /// Set the state of a task
+    Set {
+        /// The number of the task, or of a subtask such as 3.1
+        index: String,
+        /// The new state of the task, one of N, S, C, F or X
+        state: String,
+        #[command(flatten)]
+        date: DateArgs,
+    },
+    /// Mark a task as complete
+    Complete {
+        /// The number of the task, or of a subtask such as 3.1
+        index: String,
+        #[command(flatten)]
+        date: DateArgs,
+    },
+    /// View the tasks for a date
+    View {
+        #[command(flatten)]
+        date: DateArgs,
+        /// Show the journal entries for the date below the tasks
+        #[arg(long, short)]
+        journal: bool,
+        /// Show the tasks of every list, labelled with their list
+        #[arg(long)]
+        all_lists: bool,
+    },
+    /// Edit the description of a task
+    Edit {
+        /// The number of the task, or of a subtask such as 3.1
+        index: String,
+        /// The new task description, optionally ending with an estimate such as ~30m
+        task: String,
+        #[command(flatten)]
+        date: DateArgs,
+    },
+    /// Generate shell completions
+    Completions {
+        /// The shell to generate completions for
+        #[arg(value_parser = ["bash", "zsh", "fish"])]
+        shell: String,
+    },
+    /// Export tasks to another format
+    Export {
+        /// The format to export to
+        #[arg(long, short, value_parser = ["todotxt", "ics", "csv"])]
+        format: String,
+        /// The dates to export, either a single date or a range such as 1-6-2023..7-6-2023,
+        /// defaults to today
+        range: Option<String>,
+    },
+    /// Import tasks from a file, placing each on its dated task list
+    Import {
+        /// The file to import
+        file: PathBuf,
+        /// The format of the file, guessed from its extension by default
+        #[arg(long, short, value_parser = ["todotxt", "ics", "csv"])]
+        format: Option<String>,
+    },
+    /// Render a report of the tasks over a range of dates
+    Report {
+        /// The format of the report
+        #[arg(long, short, value_parser = ["md", "html"])]
+        format: String,
+        /// The dates to report on, either a single date or a range such as 1-6-2023..7-6-2023
+        range: String,
+    },
+    /// Summarise what was done on the previous working day and what is planned for today
+    Standup {
+        /// Report on everything done since this date rather than the previous working day
+        #[arg(long, value_name = "DATE")]
+        since: Option<String>,
+        /// The format of the summary
+        #[arg(long, short, value_parser = ["md", "slack"], default_value = "md")]
+        format: String,
+    },
+    /// Start a timer on a task, marking it as started
+    Start {
+        /// The number of the task
+        index: usize,
+        #[command(flatten)]
+        date: DateArgs,
+    },
+    /// Stop the timer on a task, or every running timer if no task is given
+    Stop {
+        /// The number of the task
+        index: Option<usize>,
+        /// The date of the task list, defaults to today
+        #[arg(long, short, value_name = "DATE")]
+        date: Option<String>,
+    },
+    /// Total the time spent on each task and each day over a range of dates
+    Timesheet {
+        /// The dates to total, either a single date or a range such as 1-6-2023..7-6-2023
+        range: String,
+    },
+    /// Make a task wait on another task being finished first
+    Depend {
+        /// The number of the task that has to wait
+        index: String,
+        /// The number of the task to finish first
+        prerequisite: String,
+        #[command(flatten)]
+        date: DateArgs,
+        /// The date of the task to finish first, defaults to the date of the waiting task
+        #[arg(long, value_name = "DATE")]
+        from: Option<String>,
+        /// Remove the dependency instead of adding it
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Print the dependencies between tasks as a DOT graph
+    Graph {
+        /// The dates to include, either a single date or a range such as 1-6-2023..7-6-2023,
+        /// defaults to today
+        range: Option<String>,
+    },
+    /// Write notes for a task in your editor
+    Note {
+        /// The number of the task, or of a subtask such as 3.1
+        index: String,
+        #[command(flatten)]
+        date: DateArgs,
+    },
+    /// Show a task along with its notes
+    Show {
+        /// The number of the task, or of a subtask such as 3.1
+        index: String,
+        #[command(flatten)]
+        date: DateArgs,
+    },
+    /// Write a timestamped entry in the journal for a date
+    Journal {
+        #[command(flatten)]
+        date: DateArgs,
+        /// The text of the entry, read from standard input if not given
+        #[arg(long, short)]
+        message: Option<String>,
+    },
+    /// Search the tasks, notes and journal entries for some text
+    Search {
+        /// The text to look for, ignoring case
+        query: String,
+        /// The dates to search, either a single date or a range such as 1-6-2023..7-6-2023,
+        /// defaults to every date
+        range: Option<String>,
+    },
+    /// Set the date a task has to be done by
+    Due {
+        /// The number of the task, or of a subtask such as 3.1
+        index: String,
+        /// The date the task has to be done by
+        #[arg(required_unless_present = "clear")]
+        due: Option<String>,
+        #[command(flatten)]
+        date: DateArgs,
+        /// Remove the due date instead of setting it
+        #[arg(long, conflicts_with = "due")]
+        clear: bool,
+    },
+    /// List the unfinished tasks on every day that are past their due date
+    Overdue,
+    /// Set the priority of a task
+    Priority {
+        /// The number of the task, or of a subtask such as 3.1
+        index: String,
+        /// A letter from A, the most important, to Z
+        #[arg(required_unless_present = "clear")]
+        priority: Option<String>,
+        #[command(flatten)]
+        date: DateArgs,
+        /// Remove the priority instead of setting it
+        #[arg(long, conflicts_with = "priority")]
+        clear: bool,
+    },
+    /// Set the time of day to be reminded of a task
+    At {
+        /// The number of the task, or of a subtask such as 3.1
+        index: String,
+        /// The time of day, such as 14:30
+        #[arg(required_unless_present = "clear")]
+        at: Option<String>,
+        #[command(flatten)]
+        date: DateArgs,
+        /// Remove the reminder instead of setting it
+        #[arg(long, conflicts_with = "at")]
+        clear: bool,
+    },
+    /// Watch today's tasks and send a reminder when each one's time comes
+    Remind {
+        /// Where to send reminders, one of stdout, command or desktop
+        #[arg(long, default_value = "stdout", value_name = "NOTIFIER")]
+        notify: String,
+        /// The shell command the command notifier runs, given the reminder in the
+        /// TUDU_REMINDER_TASK, TUDU_REMINDER_INDEX, TUDU_REMINDER_TIME and TUDU_REMINDER_DATE env
+        /// variables
+        #[arg(long)]
+        command: Option<String>,
+        /// Send the reminders that are due now and stop, rather than watching
+        #[arg(long)]
+        once: bool,
+    },
+    /// Put off the reminder for a task
+    Snooze {
+        /// The number of the task, or of a subtask such as 3.1
+        index: String,
+        #[command(flatten)]
+        date: DateArgs,
+        /// How long to put the reminder off for, such as 10m or 1h
+        #[arg(long = "for", default_value = "10m", value_name = "DURATION")]
+        duration: String,
+    },
+    /// Suggest the tasks to do next from today and earlier days
+    Next {
+        /// The number of tasks to suggest
+        #[arg(long, short = 'n', default_value_t = 3)]
+        count: usize,
+        /// Mark the top suggestion as started
+        #[arg(long)]
+        start: bool,
+        /// Explain why each task is ranked where it is
+        #[arg(long)]
+        why: bool,
+    },
+    /// Show the history of the tasks for a date, when history is kept in git
+    Log {
+        #[command(flatten)]
+        date: DateArgs,
+    },
+    /// Pull and push the history of the tasks with the git remote
+    Sync,
+    /// Merge two versions of a day file changed from a common base, as used by git when syncing
+    Merge {
+        /// The day file both versions were changed from
+        base: PathBuf,
+        /// Our version of the day file, which is replaced by the merged tasks
+        ours: PathBuf,
+        /// Their version of the day file
+        theirs: PathBuf,
+        /// Write the merged tasks to this file rather than replacing our version
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Serve the tasks as JSON over HTTP, only to this machine unless another host is given
+    Serve {
+        /// The port to listen on
+        #[arg(long, short, default_value_t = 7070)]
+        port: u16,
+        /// The address to listen on, such as 0.0.0.0 to serve other machines
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        /// Require every request to send this token as `Authorization: Bearer TOKEN`, defaults to
+        /// the TUDU_TOKEN env variable
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Keep the tasks loaded in the background for fast clients, answering over a Unix socket in the
+    /// tasks directory. Tudu reads the tasks through it while it is running.
+    Daemon,
+    /// List the aliases set in the config
+    Alias,
+    /// List the lists of tasks, marking the current one
+    Lists,
+    /// Move a task to another list on the same date, keeping its state
+    Move {
+        /// The number of the task
+        index: usize,
+        /// The list to move the task to
+        #[arg(long, value_name = "LIST")]
+        to: String,
+        #[command(flatten)]
+        date: DateArgs,
+    },
+    /// List the tasks of a day for the completion scripts
+    #[command(name = "__complete", hide = true)]
+    TaskCandidates {
+        #[command(flatten)]
+        date: DateArgs,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct DateArgs {
+    /// The date of the task list, defaults to today
+    #[arg(value_name = "DATE")]
+    date: Option<String>,
+    /// The date of the task list, as an alternative to giving it positionally
+    #[arg(
+        long = "date",
+        short = 'd',
+        value_name = "DATE",
+        conflicts_with = "date"
+    )]
+    date_flag: Option<String>,
+}
+
+impl DateArgs {
+    pub fn value(&self) -> Option<&str> {
+        self.date.as_deref().or(self.date_flag.as_deref())
+    }
+}
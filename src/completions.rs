@@ -0,0 +1,494 @@
+use clap::CommandFactory;
+
+use crate::cli::Cli;
+use crate::error::TuduError;
+use crate::model::Task;
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    pub fn from_name(name: &str) -> Result<Shell, TuduError> {
+        match name {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(TuduError::InvalidArguments(String::from(
+                "`completions` accepts one of bash, zsh or fish",
+            ))),
+        }
+    }
+}
+
+enum Candidates {
+    Tasks,
+    States,
+    Dates,
+    Shells,
+    Files,
+}
+
+const STATES: [(&str, &str); 5] = [
+    ("N", "Not started"),
+    ("S", "Started"),
+    ("C", "Completed"),
+    ("F", "Carry forward"),
+    ("X", "Not doing"),
+];
+
+const DATE_KEYWORDS: [&str; 3] = ["today", "tomorrow", "yesterday"];
+
+const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+/// The argument position, counting from 1 after the subcommand, at which each
+/// command accepts something that can be completed. The `-l/--list` and
+/// `-d/--date` options are not counted, and are given to `tudu __complete` so
+/// the tasks offered come from that list and date.
+const POSITIONS: [(&str, usize, Candidates); 44] = [
+    ("add", 2, Candidates::Dates),
+    ("remove", 1, Candidates::Tasks),
+    ("remove", 2, Candidates::Dates),
+    ("set", 1, Candidates::Tasks),
+    ("set", 2, Candidates::States),
+    ("set", 3, Candidates::Dates),
+    ("complete", 1, Candidates::Tasks),
+    ("complete", 2, Candidates::Dates),
+    ("edit", 1, Candidates::Tasks),
+    ("edit", 3, Candidates::Dates),
+    ("view", 1, Candidates::Dates),
+    ("completions", 1, Candidates::Shells),
+    ("export", 1, Candidates::Dates),
+    ("import", 1, Candidates::Files),
+    ("report", 1, Candidates::Dates),
+    ("start", 1, Candidates::Tasks),
+    ("start", 2, Candidates::Dates),
+    ("stop", 1, Candidates::Tasks),
+    ("timesheet", 1, Candidates::Dates),
+    ("depend", 1, Candidates::Tasks),
+    ("depend", 2, Candidates::Tasks),
+    ("depend", 3, Candidates::Dates),
+    ("graph", 1, Candidates::Dates),
+    ("note", 1, Candidates::Tasks),
+    ("note", 2, Candidates::Dates),
+    ("show", 1, Candidates::Tasks),
+    ("show", 2, Candidates::Dates),
+    ("journal", 1, Candidates::Dates),
+    ("search", 2, Candidates::Dates),
+    ("due", 1, Candidates::Tasks),
+    ("due", 2, Candidates::Dates),
+    ("due", 3, Candidates::Dates),
+    ("priority", 1, Candidates::Tasks),
+    ("priority", 3, Candidates::Dates),
+    ("at", 1, Candidates::Tasks),
+    ("at", 3, Candidates::Dates),
+    ("snooze", 1, Candidates::Tasks),
+    ("snooze", 2, Candidates::Dates),
+    ("move", 1, Candidates::Tasks),
+    ("move", 2, Candidates::Dates),
+    ("log", 1, Candidates::Dates),
+    ("merge", 1, Candidates::Files),
+    ("merge", 2, Candidates::Files),
+    ("merge", 3, Candidates::Files),
+];
+
+/// Lists the visible subcommands from the CLI definition along with their descriptions
+fn get_commands() -> Vec<(String, String)> {
+    let mut cli = Cli::command();
+    cli.build();
+
+    cli.get_subcommands()
+        .filter(|command| !command.is_hide_set())
+        .map(|command| {
+            let name = command.get_name().to_string();
+            let about = command
+                .get_about()
+                .map(|about| about.to_string())
+                .unwrap_or_default();
+
+            (name, about)
+        })
+        .collect()
+}
+
+pub fn generate_script(shell: &Shell) -> String {
+    match shell {
+        Shell::Bash => generate_bash_script(),
+        Shell::Zsh => generate_zsh_script(),
+        Shell::Fish => generate_fish_script(),
+    }
+}
+
+/// Lists the tasks in the task list as tab separated index and task pairs, to
+/// be consumed by the completion scripts through `tudu __complete`
+pub fn get_task_candidates(tasks: &[Task]) -> String {
+    tasks
+        .iter()
+        .enumerate()
+        .flat_map(|(index, task)| {
+            let subtasks = task
+                .subtasks
+                .iter()
+                .enumerate()
+                .map(move |(subtask_index, subtask)| {
+                    format!("{}.{}\t{}\n", index + 1, subtask_index + 1, subtask.task)
+                });
+
+            std::iter::once(format!("{}\t{}\n", index + 1, task.task)).chain(subtasks)
+        })
+        .collect()
+}
+
+fn generate_bash_script() -> String {
+    let commands = get_commands()
+        .into_iter()
+        .map(|(command, _)| command)
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    let mut script = String::from(
+        "_tudu() {
+    local cur=\"${COMP_WORDS[COMP_CWORD]}\"
+    local -a args=() options=()
+    local i
+
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case \"${COMP_WORDS[i]}\" in
+            -l|--list|-d|--date)
+                options+=(\"${COMP_WORDS[i]}\" \"${COMP_WORDS[i + 1]}\")
+                ((i++))
+                ;;
+            --list=*|--date=*) options+=(\"${COMP_WORDS[i]}\") ;;
+            *) args+=(\"${COMP_WORDS[i]}\") ;;
+        esac
+    done
+
+    case \"${COMP_WORDS[COMP_CWORD - 1]}\" in
+",
+    );
+    script.push_str(&format!(
+        "        -d|--date) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;\n",
+        DATE_KEYWORDS.join(" ")
+    ));
+    script.push_str(
+        "        -l|--list) return ;;
+    esac
+
+    if [[ ${#args[@]} -eq 0 ]]; then
+",
+    );
+    script.push_str(&format!(
+        "        COMPREPLY=($(compgen -W \"{commands}\" -- \"$cur\"))\n"
+    ));
+    script.push_str(
+        "        return
+    fi
+
+    case \"${args[0]}:${#args[@]}\" in
+",
+    );
+
+    for (command, position, candidates) in POSITIONS.iter() {
+        let completion = match candidates {
+            Candidates::Tasks => String::from(
+                "local IFS=$'\\n'
+            local matches=($(tudu __complete \"${options[@]}\" 2>/dev/null | awk -F'\\t' -v cur=\"$cur\" 'index($1, cur) == 1 { print $1 \" - \" $2 }'))
+            if [[ ${#matches[@]} -eq 1 ]]; then
+                COMPREPLY=(\"${matches[0]%% *}\")
+            else
+                COMPREPLY=(\"${matches[@]}\")
+            fi",
+            ),
+            Candidates::States => {
+                let states = STATES.map(|(state, _)| state).join(" ");
+                format!("COMPREPLY=($(compgen -W \"{states}\" -- \"$cur\"))")
+            }
+            Candidates::Dates => {
+                let dates = DATE_KEYWORDS.join(" ");
+                format!("COMPREPLY=($(compgen -W \"{dates}\" -- \"$cur\"))")
+            }
+            Candidates::Shells => {
+                let shells = SHELLS.join(" ");
+                format!("COMPREPLY=($(compgen -W \"{shells}\" -- \"$cur\"))")
+            }
+            Candidates::Files => String::from("COMPREPLY=($(compgen -f -- \"$cur\"))"),
+        };
+
+        script.push_str(&format!(
+            "        {command}:{position})\n            {completion}\n            ;;\n"
+        ));
+    }
+
+    script.push_str(
+        "    esac
+}
+
+complete -F _tudu tudu
+",
+    );
+
+    script
+}
+
+fn generate_zsh_script() -> String {
+    let mut script = String::from(
+        "#compdef tudu
+
+_tudu() {
+    local -a commands states dates shells tasks args options
+    local i
+
+",
+    );
+
+    script.push_str("    commands=(\n");
+    for (command, description) in get_commands() {
+        script.push_str(&format!(
+            "        {}\n",
+            describe_zsh(&command, &description)
+        ));
+    }
+    script.push_str("    )\n    states=(\n");
+    for (state, description) in STATES.iter() {
+        script.push_str(&format!("        {}\n", describe_zsh(state, description)));
+    }
+    script.push_str("    )\n");
+    script.push_str(&format!("    dates=({})\n", DATE_KEYWORDS.join(" ")));
+    script.push_str(&format!("    shells=({})\n", SHELLS.join(" ")));
+
+    script.push_str(
+        "
+    for ((i = 2; i < CURRENT; i++)); do
+        case \"${words[i]}\" in
+            -l|--list|-d|--date)
+                options+=(\"${words[i]}\" \"${words[i + 1]}\")
+                ((i++))
+                ;;
+            --list=*|--date=*) options+=(\"${words[i]}\") ;;
+            *) args+=(\"${words[i]}\") ;;
+        esac
+    done
+
+    case \"${words[CURRENT - 1]}\" in
+        -d|--date) compadd -a dates; return ;;
+        -l|--list) return ;;
+    esac
+
+    if (( ${#args} == 0 )); then
+        _describe 'command' commands
+        return
+    fi
+
+    case \"${args[1]}:${#args}\" in
+",
+    );
+
+    for (command, position, candidates) in POSITIONS.iter() {
+        let completion = match candidates {
+            Candidates::Tasks => {
+                "tasks=(${${(f)\"$(tudu __complete \"${options[@]}\" 2>/dev/null)\"}//$'\\t'/:})
+            _describe 'task' tasks"
+            }
+            Candidates::States => "_describe 'state' states",
+            Candidates::Dates => "compadd -a dates",
+            Candidates::Shells => "compadd -a shells",
+            Candidates::Files => "_files",
+        };
+
+        script.push_str(&format!(
+            "        {command}:{position})\n            {completion}\n            ;;\n"
+        ));
+    }
+
+    script.push_str(
+        "    esac
+}
+
+compdef _tudu tudu
+",
+    );
+
+    script
+}
+
+/// An entry for zsh's `_describe`, where a `:` in the name would be taken as the start of its
+/// description
+fn describe_zsh(name: &str, description: &str) -> String {
+    quote(&format!("{}:{description}", name.replace(':', "\\:")))
+}
+
+/// Quotes text for zsh and fish, which both end a single quoted string at the next `'`, so each
+/// one is closed, escaped and reopened
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn generate_fish_script() -> String {
+    let mut script = String::from(
+        "function __tudu_tokens
+    set -l tokens (commandline -opc)
+    set -e tokens[1]
+    set -l is_value 0
+    for token in $tokens
+        if test $is_value -eq 1
+            set is_value 0
+            test $argv[1] = options; and echo $token
+            continue
+        end
+        switch $token
+            case -l --list -d --date
+                set is_value 1
+                test $argv[1] = options; and echo $token
+            case '--list=*' '--date=*'
+                test $argv[1] = options; and echo $token
+            case '*'
+                test $argv[1] = args; and echo $token
+        end
+    end
+end
+
+function __tudu_at_position
+    set -l args (__tudu_tokens args)
+    test (count $args) -eq $argv[1]
+    and test \"$args[1]\" = \"$argv[2]\"
+end
+
+complete -c tudu -f
+complete -c tudu -s l -l list -r
+",
+    );
+    script.push_str(&format!(
+        "complete -c tudu -s d -l date -r -a '{}'\n",
+        DATE_KEYWORDS.join(" ")
+    ));
+
+    for (command, description) in get_commands() {
+        script.push_str(&format!(
+            "complete -c tudu -n 'test (count (__tudu_tokens args)) -eq 0' -a {command} -d {}\n",
+            quote(&description)
+        ));
+    }
+
+    for (command, position, candidates) in POSITIONS.iter() {
+        let condition = format!("-n '__tudu_at_position {position} {command}'");
+
+        match candidates {
+            Candidates::Tasks => script.push_str(&format!(
+                "complete -c tudu {condition} -a '(tudu __complete (__tudu_tokens options) 2>/dev/null)'\n"
+            )),
+            Candidates::States => {
+                for (state, description) in STATES.iter() {
+                    script.push_str(&format!(
+                        "complete -c tudu {condition} -a {state} -d {}\n",
+                        quote(description)
+                    ));
+                }
+            }
+            Candidates::Dates => script.push_str(&format!(
+                "complete -c tudu {condition} -a '{}'\n",
+                DATE_KEYWORDS.join(" ")
+            )),
+            Candidates::Shells => script.push_str(&format!(
+                "complete -c tudu {condition} -a '{}'\n",
+                SHELLS.join(" ")
+            )),
+            Candidates::Files => script.push_str(&format!("complete -c tudu {condition} -F\n")),
+        }
+    }
+
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::TaskState;
+
+    #[test]
+    fn from_name_accepts_supported_shells() {
+        assert_eq!(Shell::from_name("bash").unwrap(), Shell::Bash);
+        assert_eq!(Shell::from_name("zsh").unwrap(), Shell::Zsh);
+        assert_eq!(Shell::from_name("fish").unwrap(), Shell::Fish);
+        assert!(Shell::from_name("powershell").is_err());
+    }
+
+    #[test]
+    fn get_commands_excludes_hidden_commands() {
+        let commands = get_commands();
+
+        assert!(commands.iter().any(|(command, _)| command == "add"));
+        assert!(commands.iter().any(|(command, _)| command == "help"));
+        assert!(!commands.iter().any(|(command, _)| command == "__complete"));
+    }
+
+    #[test]
+    fn generated_scripts_complete_every_command() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = generate_script(&shell);
+
+            for (command, _) in get_commands() {
+                assert!(script.contains(&command), "{shell:?} is missing {command}");
+            }
+            assert!(script.contains("tudu __complete"));
+            assert!(script.contains("tomorrow"));
+        }
+    }
+
+    #[test]
+    fn generated_scripts_offer_states_for_set() {
+        assert!(generate_script(&Shell::Bash)
+            .contains("set:2)\n            COMPREPLY=($(compgen -W \"N S C F X\""));
+        assert!(
+            generate_script(&Shell::Zsh).contains("set:2)\n            _describe 'state' states")
+        );
+        assert!(generate_script(&Shell::Fish)
+            .contains("complete -c tudu -n '__tudu_at_position 2 set' -a F -d 'Carry forward'"));
+    }
+
+    #[test]
+    fn generated_scripts_skip_list_and_date_options() {
+        let bash = generate_script(&Shell::Bash);
+        let zsh = generate_script(&Shell::Zsh);
+        let fish = generate_script(&Shell::Fish);
+
+        assert!(bash.contains("-l|--list|-d|--date)"));
+        assert!(bash.contains("case \"${args[0]}:${#args[@]}\" in"));
+        assert!(bash.contains("tudu __complete \"${options[@]}\""));
+        assert!(zsh.contains("-l|--list|-d|--date)"));
+        assert!(zsh.contains("case \"${args[1]}:${#args}\" in"));
+        assert!(zsh.contains("tudu __complete \"${options[@]}\""));
+        assert!(fish.contains("-a '(tudu __complete (__tudu_tokens options) 2>/dev/null)'"));
+        assert!(fish.contains("case -l --list -d --date"));
+    }
+
+    #[test]
+    fn generated_scripts_escape_quotes_in_descriptions() {
+        let remind =
+            r"'remind:Watch today'\''s tasks and send a reminder when each one'\''s time comes'";
+
+        assert!(generate_script(&Shell::Zsh).contains(&format!("\n        {remind}\n")));
+        assert!(generate_script(&Shell::Fish).contains(
+            r"-a remind -d 'Watch today'\''s tasks and send a reminder when each one'\''s time comes'"
+        ));
+        assert_eq!(describe_zsh("a:b", "It's"), r"'a\:b:It'\''s'");
+    }
+
+    #[test]
+    fn get_task_candidates_lists_index_and_task() {
+        let mut first_task = Task::new(String::from("First task"), TaskState::Complete);
+        first_task.subtasks = vec![Task::new(String::from("Step"), TaskState::Complete)];
+        let tasks = vec![
+            first_task,
+            Task::new(String::from("Second task"), TaskState::NotStarted),
+        ];
+
+        let expected_candidates = "1\tFirst task\n1.1\tStep\n2\tSecond task\n";
+
+        let candidates = get_task_candidates(&tasks);
+
+        assert_eq!(candidates, expected_candidates);
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::error::TuduError;
+use crate::storage::{create_root_filepath, read_file_if_exists};
+
+pub const CONFIG_FILENAME: &str = "config";
+
+/// The user's settings, read from a file of `setting = value` lines where `#` starts a comment:
+///
+/// ```text
+/// # shortcuts for commands
+/// alias d = complete
+/// alias eod = carry; view tomorrow
+/// list = work
+/// ```
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct Config {
+    pub aliases: BTreeMap<String, String>,
+    /// The list used when none is chosen for a command
+    pub list: Option<String>,
+}
+
+impl Config {
+    /// Reads the config from the file set in the TUDU_CONFIG env variable, falling back to `config`
+    /// in the tasks directory. Having no config file is the same as an empty one.
+    pub fn load() -> Result<Config, TuduError> {
+        Config::load_from(&config_path()?)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Config, TuduError> {
+        match read_file_if_exists(path)? {
+            Some(contents) => Config::parse(&contents),
+            None => Ok(Config::default()),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<Config, TuduError> {
+        let mut config = Config::default();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid =
+                |reason: &str| TuduError::InvalidConfig(format!("line {}, {reason}", number + 1));
+
+            let Some((setting, value)) = line.split_once('=') else {
+                return Err(invalid("settings are written as `setting = value`"));
+            };
+            let value = value.trim();
+
+            match setting.split_whitespace().collect::<Vec<&str>>().as_slice() {
+                ["alias", name] => {
+                    if value.is_empty() {
+                        return Err(invalid("the alias has nothing to run"));
+                    }
+
+                    config.aliases.insert(name.to_string(), value.to_string());
+                }
+                ["alias"] => return Err(invalid("the alias needs a name")),
+                ["list"] => config.list = Some(value.to_string()),
+                _ => return Err(invalid(&format!("`{}` is not a setting", setting.trim()))),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+pub fn config_path() -> Result<PathBuf, TuduError> {
+    match env::var("TUDU_CONFIG") {
+        Ok(path) => Ok(PathBuf::from(path)),
+        Err(_) => create_root_filepath(CONFIG_FILENAME),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_settings_and_skips_comments() {
+        let contents =
+            "# shortcuts\nalias d = complete\n\n  alias eod = carry; view tomorrow\nlist = work\n";
+
+        let config = Config::parse(contents).unwrap();
+
+        assert_eq!(
+            config.aliases,
+            BTreeMap::from([
+                (String::from("d"), String::from("complete")),
+                (String::from("eod"), String::from("carry; view tomorrow")),
+            ])
+        );
+        assert_eq!(config.list, Some(String::from("work")));
+    }
+
+    #[test]
+    fn parse_gives_line_of_invalid_setting() {
+        assert_eq!(
+            Config::parse("alias d = complete\ncolour = blue\n"),
+            Err(TuduError::InvalidConfig(String::from(
+                "line 2, `colour` is not a setting"
+            )))
+        );
+        assert_eq!(
+            Config::parse("alias d\n"),
+            Err(TuduError::InvalidConfig(String::from(
+                "line 1, settings are written as `setting = value`"
+            )))
+        );
+        assert_eq!(
+            Config::parse("alias =  complete\n"),
+            Err(TuduError::InvalidConfig(String::from(
+                "line 1, the alias needs a name"
+            )))
+        );
+    }
+}
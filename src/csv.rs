@@ -0,0 +1,390 @@
+use crate::date::TuduDate;
+use crate::error::TuduError;
+use crate::model::{check_single_line, Task, TaskIndex, TaskList, TaskState, MULTILINE_TASK};
+
+const HEADER: [&str; 5] = ["date", "index", "state", "state_name", "task"];
+
+/// Formats every task in the task lists as a CSV row, after a header row. Subtasks follow their
+/// task, with an index such as `3.1`.
+pub fn export_tasks(task_lists: &[TaskList]) -> String {
+    let mut rows = vec![format_row(&HEADER.map(String::from))];
+
+    for task_list in task_lists {
+        let date = task_list.date().to_iso();
+
+        for (index, task) in task_list.tasks().iter().enumerate() {
+            rows.push(format_task_row(&date, (index + 1).into(), task));
+
+            for (subtask_index, subtask) in task.subtasks.iter().enumerate() {
+                let index = TaskIndex {
+                    task: index + 1,
+                    subtask: Some(subtask_index + 1),
+                };
+
+                rows.push(format_task_row(&date, index, subtask));
+            }
+        }
+    }
+
+    rows.concat()
+}
+
+fn format_task_row(date: &str, index: TaskIndex, task: &Task) -> String {
+    format_row(&[
+        String::from(date),
+        index.to_string(),
+        String::from(task.state.code()),
+        String::from(task.state.name()),
+        task.task.clone(),
+    ])
+}
+
+fn format_row(fields: &[String]) -> String {
+    let fields: Vec<String> = fields.iter().map(|field| quote_field(field)).collect();
+
+    format!("{}\r\n", fields.join(","))
+}
+
+/// Quotes a field if it contains anything that would otherwise be read as the end of the field
+fn quote_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) || field.starts_with(' ') || field.ends_with(' ') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        String::from(field)
+    }
+}
+
+/// Reads the tasks from CSV contents along with the date of the task list each belongs on. Every
+/// row is checked before anything is returned, so all problems are reported at once by row number.
+/// Within a date, tasks are ordered by their index column, and a subtask such as `3.1` is added to
+/// the task numbered 3.
+pub fn import_tasks(contents: &str) -> Result<Vec<(TuduDate, Task)>, TuduError> {
+    // Spreadsheets such as Excel start the files they save with a byte order mark
+    let contents = contents.strip_prefix('\u{feff}').unwrap_or(contents);
+
+    let rows = parse_rows(contents)?;
+
+    let mut tasks = Vec::new();
+    let mut errors = Vec::new();
+
+    for (row_index, fields) in rows.iter().enumerate() {
+        let row_number = row_index + 1;
+
+        let is_header = row_number == 1 && fields == &HEADER;
+        let is_blank = fields.len() == 1 && fields[0].trim().is_empty();
+
+        if is_header || is_blank {
+            continue;
+        }
+
+        match parse_task(fields) {
+            Ok((date, index, task)) => tasks.push((row_number, date, index, task)),
+            Err(reason) => errors.push((row_number, reason)),
+        }
+    }
+
+    // A task comes before its subtasks, as it has no subtask index
+    tasks.sort_by(
+        |(_, first_date, first_index, _), (_, second_date, second_index, _)| {
+            (first_date, first_index.task, first_index.subtask).cmp(&(
+                second_date,
+                second_index.task,
+                second_index.subtask,
+            ))
+        },
+    );
+
+    let mut imported: Vec<(TuduDate, usize, Task)> = Vec::new();
+
+    for (row_number, date, index, task) in tasks {
+        if index.subtask.is_none() {
+            imported.push((date, index.task, task));
+            continue;
+        }
+
+        match imported.last_mut() {
+            Some((parent_date, parent_index, parent))
+                if *parent_date == date && *parent_index == index.task =>
+            {
+                parent.subtasks.push(task)
+            }
+            _ => errors.push((
+                row_number,
+                format!("there is no task {} for subtask {index}", index.task),
+            )),
+        }
+    }
+
+    if !errors.is_empty() {
+        errors.sort();
+        return Err(TuduError::InvalidRows(errors));
+    }
+
+    Ok(imported
+        .into_iter()
+        .map(|(date, _, task)| (date, task))
+        .collect())
+}
+
+fn parse_task(fields: &[String]) -> Result<(TuduDate, TaskIndex, Task), String> {
+    let [date, index, code, name, description] = fields else {
+        return Err(format!("expected 5 columns but found {}", fields.len()));
+    };
+
+    let date = TuduDate::from_iso(date)
+        .map_err(|_| format!("`{date}` is not a date in the form 2023-06-07"))?;
+
+    let index = match TaskIndex::parse(index) {
+        Ok(parsed) if parsed.task > 0 && parsed.subtask != Some(0) => parsed,
+        _ => return Err(format!("`{index}` is not a task number such as 3 or 3.1")),
+    };
+
+    let state = match (code.as_str(), name.as_str()) {
+        ("", "") => return Err(String::from("a state code or state name is required")),
+        ("", name) => state_from_name(name)?,
+        (code, name) => {
+            let state =
+                TaskState::from_code(code).ok_or(format!("`{code}` is not a state code"))?;
+
+            if !name.is_empty() && state != state_from_name(name)? {
+                return Err(format!(
+                    "state code `{code}` does not match state name `{name}`"
+                ));
+            }
+
+            state
+        }
+    };
+
+    if description.trim().is_empty() {
+        return Err(String::from("the task is empty"));
+    }
+
+    if check_single_line(description).is_err() {
+        return Err(String::from(MULTILINE_TASK));
+    }
+
+    Ok((date, index, Task::new(description.clone(), state)))
+}
+
+fn state_from_name(name: &str) -> Result<TaskState, String> {
+    TaskState::ALL
+        .into_iter()
+        .find(|state| state.name().eq_ignore_ascii_case(name))
+        .ok_or(format!("`{name}` is not a state name"))
+}
+
+/// Splits CSV contents into rows of fields, following RFC 4180 quoting
+fn parse_rows(contents: &str) -> Result<Vec<Vec<String>>, TuduError> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut characters = contents.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        match (in_quotes, character) {
+            (true, '"') if characters.peek() == Some(&'"') => {
+                field.push('"');
+                characters.next();
+            }
+            (true, '"') => in_quotes = false,
+            (true, character) => field.push(character),
+            (false, '"') if field.is_empty() => in_quotes = true,
+            (false, ',') => row.push(std::mem::take(&mut field)),
+            (false, '\r') if characters.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (false, character) => field.push(character),
+        }
+    }
+
+    if in_quotes {
+        return Err(TuduError::InvalidRows(vec![(
+            rows.len() + 1,
+            String::from("a quoted field is never closed"),
+        )]));
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_tasks_quotes_fields_that_need_it() {
+        let date = TuduDate::new(7, 6, 2023);
+        let task_lists = vec![TaskList::from_tasks(
+            &date,
+            vec![
+                Task::new(String::from("Buy eggs, milk"), TaskState::Complete),
+                Task::new(String::from("Read \"Dune\""), TaskState::Forwarded),
+                Task::new(String::from("Plain"), TaskState::NotStarted),
+            ],
+        )];
+
+        let expected_contents = "date,index,state,state_name,task\r
+2023-06-07,1,C,Completed,\"Buy eggs, milk\"\r
+2023-06-07,2,F,Carried forward,\"Read \"\"Dune\"\"\"\r
+2023-06-07,3,N,Not started,Plain\r
+";
+
+        let exported = export_tasks(&task_lists);
+
+        assert_eq!(exported, expected_contents);
+    }
+
+    #[test]
+    fn export_then_import_gives_back_the_same_tasks() {
+        let first_date = TuduDate::new(7, 6, 2023);
+        let second_date = TuduDate::new(8, 6, 2023);
+        let first_tasks = vec![
+            Task::new(String::from("This task is started"), TaskState::Started),
+            Task::new(String::from("Commas, \"quotes\""), TaskState::Complete),
+            Task::new(String::from("Didn't like this one"), TaskState::Ignored),
+        ];
+        let second_tasks = vec![Task::new(
+            String::from("This one's for later"),
+            TaskState::Forwarded,
+        )];
+        let task_lists = vec![
+            TaskList::from_tasks(&first_date, first_tasks.clone()),
+            TaskList::from_tasks(&second_date, second_tasks.clone()),
+        ];
+
+        let imported = import_tasks(&export_tasks(&task_lists)).unwrap();
+
+        let mut expected_tasks: Vec<(TuduDate, Task)> = first_tasks
+            .into_iter()
+            .map(|task| (first_date.clone(), task))
+            .collect();
+        expected_tasks.extend(
+            second_tasks
+                .into_iter()
+                .map(|task| (second_date.clone(), task)),
+        );
+
+        assert_eq!(imported, expected_tasks);
+    }
+
+    #[test]
+    fn export_then_import_keeps_subtasks() {
+        let date = TuduDate::new(7, 6, 2023);
+        let mut parent = Task::new(String::from("Ship it"), TaskState::Started);
+        parent.subtasks = vec![
+            Task::new(String::from("Write docs"), TaskState::Complete),
+            Task::new(String::from("Tag release"), TaskState::NotStarted),
+        ];
+        let tasks = vec![
+            parent,
+            Task::new(String::from("Rest"), TaskState::NotStarted),
+        ];
+        let task_lists = vec![TaskList::from_tasks(&date, tasks.clone())];
+
+        let exported = export_tasks(&task_lists);
+        let imported = import_tasks(&exported).unwrap();
+
+        assert!(exported.contains("2023-06-07,1.1,C,Completed,Write docs\r\n"));
+        assert_eq!(
+            imported,
+            tasks
+                .into_iter()
+                .map(|task| (date.clone(), task))
+                .collect::<Vec<(TuduDate, Task)>>()
+        );
+    }
+
+    #[test]
+    fn import_tasks_reports_subtask_without_task() {
+        let contents = "2023-06-07,1,N,,First\n2023-06-07,2.1,N,,Orphan\n";
+
+        let result = import_tasks(contents);
+
+        assert_eq!(
+            result,
+            Err(TuduError::InvalidRows(vec![(
+                2,
+                String::from("there is no task 2 for subtask 2.1")
+            )]))
+        );
+    }
+
+    #[test]
+    fn import_tasks_orders_by_index_and_accepts_state_names() {
+        let contents = "2023-06-07,2,,started,Second\n2023-06-07,1,C,,First\n";
+
+        let date = TuduDate::new(7, 6, 2023);
+        let expected_tasks = vec![
+            (
+                date.clone(),
+                Task::new(String::from("First"), TaskState::Complete),
+            ),
+            (date, Task::new(String::from("Second"), TaskState::Started)),
+        ];
+
+        let imported = import_tasks(contents).unwrap();
+
+        assert_eq!(imported, expected_tasks);
+    }
+
+    #[test]
+    fn import_tasks_skips_byte_order_mark() {
+        let with_header = "\u{feff}date,index,state,state_name,task\r\n2023-06-07,1,C,,First\r\n";
+        let without_header = "\u{feff}2023-06-07,1,C,,First\r\n";
+
+        let expected_tasks = vec![(
+            TuduDate::new(7, 6, 2023),
+            Task::new(String::from("First"), TaskState::Complete),
+        )];
+
+        assert_eq!(import_tasks(with_header), Ok(expected_tasks.clone()));
+        assert_eq!(import_tasks(without_header), Ok(expected_tasks));
+    }
+
+    #[test]
+    fn import_tasks_reports_every_invalid_row() {
+        let contents = "date,index,state,state_name,task
+2023-06-07,1,C,Completed,Fine
+7-6-2023,2,C,Completed,Bad date
+2023-06-07,0,C,Completed,Bad index
+2023-06-07,4,Q,,Bad state
+2023-06-07,5,C,Not doing,Mismatched state
+2023-06-07,6,N
+2023-06-07,7,N,,
+";
+
+        let result = import_tasks(contents);
+
+        let Err(TuduError::InvalidRows(errors)) = result else {
+            panic!("expected invalid rows, got {result:?}");
+        };
+        let row_numbers: Vec<usize> = errors.iter().map(|(row, _)| *row).collect();
+
+        assert_eq!(row_numbers, vec![3, 4, 5, 6, 7, 8]);
+        assert_eq!(errors[5].1, "the task is empty");
+    }
+
+    #[test]
+    fn import_tasks_with_unclosed_quote_gives_error() {
+        let contents = "2023-06-07,1,C,Completed,Fine\n2023-06-07,2,C,Completed,\"Oops\n";
+
+        let result = import_tasks(contents);
+
+        assert_eq!(
+            result,
+            Err(TuduError::InvalidRows(vec![(
+                2,
+                String::from("a quoted field is never closed")
+            )]))
+        );
+    }
+}
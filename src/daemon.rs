@@ -0,0 +1,413 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, Metadata};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use serde_json::{json, Value};
+
+use crate::date::TuduDate;
+use crate::error::{user_error_message, TuduError};
+use crate::lists::{current_list, with_list};
+use crate::model::Task;
+use crate::server::{error_status, handle_request, task_from_json, task_to_json, ApiRequest};
+use crate::storage::{create_filepath, fingerprint_contents, parse_tasks, read_file_if_exists};
+
+pub const SOCKET_FILENAME: &str = ".daemon.sock";
+
+/// The request the cli makes for the tasks of a day file, alongside those of the server
+const LOAD_METHOD: &str = "LOAD";
+
+/// How long the cli waits for the daemon before reading the day file itself, unless set with the
+/// TUDU_DAEMON_TIMEOUT env variable
+const DEFAULT_CLIENT_TIMEOUT_MS: u64 = 200;
+
+/// The day files the daemon has loaded by their path, which is only set in the daemon's process
+static LOADED_DAYS: Mutex<Option<BTreeMap<PathBuf, LoadedDay>>> = Mutex::new(None);
+
+/// Set once the daemon has failed to answer, so the cli reads the files itself for the rest of the
+/// command rather than waiting on the daemon for each day
+static DAEMON_UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+
+/// The tasks of a day file along with its fingerprint, or `None` when the file does not exist yet
+type DayTasks = Option<(Vec<Task>, u64)>;
+
+#[derive(Debug, Clone)]
+struct LoadedDay {
+    stamp: FileStamp,
+    tasks: Vec<Task>,
+    fingerprint: u64,
+}
+
+/// What changes whenever a day file is written, as tudu and most editors replace the file. Rather
+/// than watching the directory, the daemon compares the stamp of a day file with the one it was
+/// loaded with each time the day is asked for, which is a single `stat` and notices every change.
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+    inode: u64,
+}
+
+impl From<&Metadata> for FileStamp {
+    fn from(metadata: &Metadata) -> FileStamp {
+        FileStamp {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            inode: metadata.ino(),
+        }
+    }
+}
+
+/// Loads the tasks of a day file along with its fingerprint, giving `None` if it does not exist
+/// yet. The daemon keeps the tasks it has loaded, while the cli asks the daemon for them already
+/// parsed when it is running and reads the file itself otherwise.
+pub fn load_tasks(filename: &str) -> Result<DayTasks, TuduError> {
+    let filepath = create_filepath(filename)?;
+
+    let mut loaded_days = LOADED_DAYS.lock().unwrap_or_else(PoisonError::into_inner);
+
+    if let Some(loaded_days) = loaded_days.as_mut() {
+        let day = load_day(loaded_days, &filepath)?;
+        return Ok(day.map(|day| (day.tasks, day.fingerprint)));
+    }
+
+    drop(loaded_days);
+
+    if let Some(day) = ask_for_tasks(filename)? {
+        return Ok(day);
+    }
+
+    read_file_if_exists(&filepath)?
+        .map(|contents| Ok((parse_tasks(&contents)?, fingerprint_contents(&contents))))
+        .transpose()
+}
+
+/// Keeps the tasks loaded and answers requests on the socket in the tasks directory until the
+/// process is stopped, one line of JSON for each request
+pub fn run() -> Result<(), TuduError> {
+    let socket_path = create_filepath(SOCKET_FILENAME)?;
+
+    if socket_path.exists() {
+        if UnixStream::connect(&socket_path).is_ok() {
+            return Err(TuduError::InvalidArguments(String::from(
+                "the daemon is already running for these tasks",
+            )));
+        }
+
+        // Left behind by a daemon that was stopped
+        let _ = fs::remove_file(&socket_path);
+    }
+
+    let listener = UnixListener::bind(&socket_path).map_err(|err| {
+        TuduError::InvalidArguments(format!(
+            "could not listen on {}, {err}",
+            socket_path.display()
+        ))
+    })?;
+
+    *LOADED_DAYS.lock().unwrap_or_else(PoisonError::into_inner) = Some(BTreeMap::new());
+
+    println!("Answering on {}", socket_path.display());
+
+    // Each client is answered on a thread of its own, which is given the daemon's list
+    let list = current_list()?;
+
+    for stream in listener.incoming().flatten() {
+        let list = list.clone();
+
+        thread::spawn(move || {
+            with_list(&list, || {
+                answer_client(stream);
+                Ok(())
+            })
+        });
+    }
+
+    Ok(())
+}
+
+/// Gives back the day from those loaded unless the file's stamp has changed since, reading it again
+/// when it has been edited elsewhere
+fn load_day(
+    loaded_days: &mut BTreeMap<PathBuf, LoadedDay>,
+    filepath: &Path,
+) -> Result<Option<LoadedDay>, TuduError> {
+    // The stamp is taken before reading, so a change made while reading is noticed next time
+    let stamp = match fs::metadata(filepath) {
+        Ok(metadata) => FileStamp::from(&metadata),
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            loaded_days.remove(filepath);
+            return Ok(None);
+        }
+        Err(_) => return Err(TuduError::FailedToReadFile),
+    };
+
+    if let Some(day) = loaded_days.get(filepath) {
+        if day.stamp == stamp {
+            return Ok(Some(day.clone()));
+        }
+    }
+
+    let Some(contents) = read_file_if_exists(filepath)? else {
+        loaded_days.remove(filepath);
+        return Ok(None);
+    };
+
+    let day = LoadedDay {
+        stamp,
+        tasks: parse_tasks(&contents)?,
+        fingerprint: fingerprint_contents(&contents),
+    };
+
+    loaded_days.insert(filepath.to_path_buf(), day.clone());
+
+    Ok(Some(day))
+}
+
+fn answer_client(stream: UnixStream) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if writeln!(writer, "{}", answer(&line)).is_err() {
+            return;
+        }
+    }
+}
+
+/// Answers a request such as `{"method": "GET", "path": "/days/today"}` with the same endpoints as
+/// the server, e.g. `{"status": 200, "body": {"date": "2023-06-07", "tasks": [...]}}`
+fn answer(line: &str) -> Value {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(err) => {
+            return json!({
+                "status": 400,
+                "body": { "error": format!("the request is not valid JSON, {err}") },
+            })
+        }
+    };
+
+    let method = request["method"].as_str().unwrap_or_default();
+    let path = request["path"].as_str().unwrap_or_default();
+
+    if method == LOAD_METHOD {
+        return match load_loaded_day(path) {
+            Ok(Some(day)) => {
+                let tasks: Vec<Value> = day
+                    .tasks
+                    .iter()
+                    .enumerate()
+                    .map(|(position, task)| task_to_json((position + 1).into(), task))
+                    .collect();
+
+                json!({
+                    "status": 200,
+                    "body": { "tasks": tasks, "fingerprint": day.fingerprint },
+                })
+            }
+            Ok(None) => json!({ "status": 200, "body": { "tasks": null } }),
+            Err(err) => json!({
+                "status": error_status(&err),
+                "body": { "error": user_error_message(err) },
+            }),
+        };
+    }
+
+    let body = match &request["body"] {
+        Value::Null => String::new(),
+        body => body.to_string(),
+    };
+
+    let response = handle_request(
+        &ApiRequest {
+            method: String::from(method),
+            url: String::from(path),
+            authorization: None,
+            body,
+        },
+        None,
+    );
+
+    json!({ "status": response.status, "body": response.body })
+}
+
+/// The loaded tasks of a day file in the tasks directory, as the cli asks for them
+fn load_loaded_day(filename: &str) -> Result<Option<LoadedDay>, TuduError> {
+    let is_day_file = filename
+        .strip_suffix(".txt")
+        .is_some_and(|date| TuduDate::from_iso(date).is_ok());
+
+    if !is_day_file {
+        return Err(TuduError::InvalidArguments(format!(
+            "`{filename}` is not a day file"
+        )));
+    }
+
+    let filepath = create_filepath(filename)?;
+
+    let mut loaded_days = LOADED_DAYS.lock().unwrap_or_else(PoisonError::into_inner);
+
+    // Only the daemon keeps days loaded, anywhere else the file is read where it is needed
+    let Some(loaded_days) = loaded_days.as_mut() else {
+        return Err(TuduError::InvalidArguments(String::from(
+            "days are only kept loaded by the daemon",
+        )));
+    };
+
+    load_day(loaded_days, &filepath)
+}
+
+/// Asks the daemon for the tasks of a day file and their fingerprint, giving `None` when it is not
+/// running or does not answer in time. A daemon that is not running is found out without waiting,
+/// as there is either no socket or nothing listening on it. Once the daemon has not answered in
+/// time it is not asked again for the rest of the command.
+fn ask_for_tasks(filename: &str) -> Result<Option<DayTasks>, TuduError> {
+    if DAEMON_UNAVAILABLE.load(Ordering::Relaxed) {
+        return Ok(None);
+    }
+
+    let socket_path = create_filepath(SOCKET_FILENAME)?;
+
+    if !socket_path.exists() {
+        return Ok(None);
+    }
+
+    let answer = request_tasks(&socket_path, filename, client_timeout()?);
+
+    if answer.is_none() {
+        DAEMON_UNAVAILABLE.store(true, Ordering::Relaxed);
+    }
+
+    Ok(answer)
+}
+
+/// How long to wait for the daemon, set in milliseconds with the TUDU_DAEMON_TIMEOUT env variable
+fn client_timeout() -> Result<Duration, TuduError> {
+    match env::var("TUDU_DAEMON_TIMEOUT") {
+        Ok(timeout) => timeout
+            .parse()
+            .ok()
+            .filter(|timeout| *timeout > 0)
+            .map(Duration::from_millis)
+            .ok_or_else(|| {
+                TuduError::InvalidArguments(format!(
+                    "TUDU_DAEMON_TIMEOUT of `{timeout}` is not a number of milliseconds such as 500"
+                ))
+            }),
+        Err(_) => Ok(Duration::from_millis(DEFAULT_CLIENT_TIMEOUT_MS)),
+    }
+}
+
+fn request_tasks(socket_path: &Path, filename: &str, timeout: Duration) -> Option<DayTasks> {
+    let mut stream = UnixStream::connect(socket_path).ok()?;
+    stream.set_read_timeout(Some(timeout)).ok()?;
+    stream.set_write_timeout(Some(timeout)).ok()?;
+
+    writeln!(
+        stream,
+        "{}",
+        json!({ "method": LOAD_METHOD, "path": filename })
+    )
+    .ok()?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).ok()?;
+
+    let response: Value = serde_json::from_str(&line).ok()?;
+
+    if response["status"] != 200 {
+        return None;
+    }
+
+    match &response["body"]["tasks"] {
+        Value::Null => Some(None),
+        Value::Array(tasks) => {
+            let tasks = tasks
+                .iter()
+                .map(task_from_json)
+                .collect::<Result<Vec<Task>, TuduError>>()
+                .ok()?;
+            let fingerprint = response["body"]["fingerprint"].as_u64()?;
+
+            Some(Some((tasks, fingerprint)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::TaskState;
+    use crate::storage::{test_directory, write_file};
+
+    #[test]
+    fn load_day_keeps_tasks_until_the_file_changes() {
+        let directory = test_directory("daemon");
+        let filepath = directory.join("2023-06-07.txt");
+        let mut loaded_days = BTreeMap::new();
+
+        let before = load_day(&mut loaded_days, &filepath).unwrap();
+        write_file(&filepath, "N,Write docs\n").unwrap();
+        let loaded = load_day(&mut loaded_days, &filepath).unwrap().unwrap();
+        let is_kept = loaded_days.contains_key(&filepath);
+        write_file(&filepath, "C,Write docs\n").unwrap();
+        let changed = load_day(&mut loaded_days, &filepath).unwrap().unwrap();
+
+        // cleanup
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert!(before.is_none());
+        assert!(is_kept);
+        assert_eq!(
+            loaded.tasks,
+            vec![Task::new(String::from("Write docs"), TaskState::NotStarted)]
+        );
+        assert_eq!(
+            changed.tasks,
+            vec![Task::new(String::from("Write docs"), TaskState::Complete)]
+        );
+        assert_ne!(loaded.fingerprint, changed.fingerprint);
+    }
+
+    #[test]
+    fn answer_gives_errors_for_bad_requests() {
+        assert_eq!(answer("{method")["status"], 400);
+        assert_eq!(
+            answer(r#"{"method": "LOAD", "path": "../secrets.txt"}"#)["status"],
+            400
+        );
+        assert_eq!(
+            answer(r#"{"method": "PUT", "path": "/days/today"}"#)["status"],
+            404
+        );
+    }
+
+    #[test]
+    fn answer_load_outside_the_daemon_gives_error() {
+        let response = answer(r#"{"method": "LOAD", "path": "2023-06-07.txt"}"#);
+
+        assert_eq!(response["status"], 400);
+        assert!(LOADED_DAYS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_none());
+    }
+}
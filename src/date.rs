@@ -1,8 +1,21 @@
-use chrono::{DateTime, Datelike, Duration, Local};
+use std::cmp::Ordering;
+use std::env;
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, Weekday};
 
 use crate::error::TuduError;
 
-#[derive(Eq, PartialEq, Debug)]
+/// The current moment, which can be set with the TUDU_NOW env variable to a time such as
+/// `2023-06-07T09:30:00+01:00` to see what tudu would do then, such as which reminders it sends
+pub fn now() -> DateTime<FixedOffset> {
+    match env::var("TUDU_NOW") {
+        Ok(now) => DateTime::parse_from_rfc3339(&now)
+            .expect("TUDU_NOW is not a time such as 2023-06-07T09:30:00+01:00"),
+        Err(_) => DateTime::from(Local::now()),
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
 pub struct TuduDate {
     day: u32,
     month: u32,
@@ -27,14 +40,12 @@ impl TuduDate {
         match sections.len() {
             2 => match (sections[0].parse::<u32>(), sections[1].parse::<u32>()) {
                 (Ok(day), Ok(month)) => {
-                    if let Err(err) = is_valid_date(day, month) {
-                        return Err(err);
-                    }
+                    is_valid_date(day, month)?;
 
                     // TODO: this 2023 should be the actual year
-                    return Ok(TuduDate::new(day, month, 2023));
+                    Ok(TuduDate::new(day, month, 2023))
                 }
-                _ => return Err(TuduError::InvalidDate),
+                _ => Err(TuduError::InvalidDate),
             },
             3 => match (
                 sections[0].parse::<u32>(),
@@ -42,41 +53,78 @@ impl TuduDate {
                 sections[2].parse::<u32>(),
             ) {
                 (Ok(day), Ok(month), Ok(year)) => {
-                    if let Err(err) = is_valid_date(day, month) {
-                        return Err(err);
-                    }
+                    is_valid_date(day, month)?;
 
-                    return Ok(TuduDate::new(day, month, year));
+                    Ok(TuduDate::new(day, month, year))
                 }
                 _ => Err(TuduError::InvalidDate),
             },
-            _ => return Err(TuduError::InvalidDate),
+            _ => Err(TuduError::InvalidDate),
         }
     }
 
     pub fn today() -> TuduDate {
-        let today = Local::now();
+        let today = now();
 
         TuduDate::from_date_time(today)
     }
 
     pub fn to_filename(&self) -> String {
-        format!("{}-{:02}-{:02}.txt", self.year, self.month, self.day)
+        format!("{}.txt", self.to_iso())
+    }
+
+    /// Formats the date as yyyy-mm-dd, the form used by day files and most other tools
+    pub fn to_iso(&self) -> String {
+        format!("{}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+
+    pub fn from_iso(date: &str) -> Result<TuduDate, TuduError> {
+        match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+            Ok(naive_date) => Ok(TuduDate::from_naive_date(naive_date)),
+            Err(_) => Err(TuduError::InvalidDate),
+        }
+    }
+
+    pub fn from_naive_date(naive_date: NaiveDate) -> TuduDate {
+        let day = naive_date.day();
+        let month = naive_date.month();
+        let year = naive_date.year().try_into().unwrap_or(0);
+
+        TuduDate { day, month, year }
+    }
+
+    /// Gives the date a number of days after this one, or before it when negative
+    pub fn add_days(&self, days: i64) -> Result<TuduDate, TuduError> {
+        let naive_date = self.to_naive_date()? + Duration::days(days);
+
+        Ok(TuduDate::from_naive_date(naive_date))
+    }
+
+    pub fn is_weekend(&self) -> Result<bool, TuduError> {
+        let weekday = self.to_naive_date()?.weekday();
+
+        Ok(weekday == Weekday::Sat || weekday == Weekday::Sun)
+    }
+
+    pub fn to_naive_date(&self) -> Result<NaiveDate, TuduError> {
+        let year = self.year.try_into().map_err(|_| TuduError::InvalidDate)?;
+
+        NaiveDate::from_ymd_opt(year, self.month, self.day).ok_or(TuduError::InvalidDate)
     }
 
     fn tomorrow() -> TuduDate {
-        let tomorrow = Local::now() + Duration::days(1);
+        let tomorrow = now() + Duration::days(1);
 
         TuduDate::from_date_time(tomorrow)
     }
 
     fn yesterday() -> TuduDate {
-        let yesterday = Local::now() - Duration::days(1);
+        let yesterday = now() - Duration::days(1);
 
         TuduDate::from_date_time(yesterday)
     }
 
-    fn from_date_time(date_time: DateTime<Local>) -> TuduDate {
+    pub fn from_date_time(date_time: DateTime<FixedOffset>) -> TuduDate {
         let day = date_time.day();
         let month = date_time.month();
         let year = date_time.year().try_into().unwrap();
@@ -85,13 +133,78 @@ impl TuduDate {
     }
 }
 
+impl Ord for TuduDate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.year, self.month, self.day).cmp(&(other.year, other.month, other.day))
+    }
+}
+
+impl PartialOrd for TuduDate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// An inclusive span of dates, written as `start..end` or as a single date
+#[derive(Eq, PartialEq, Debug)]
+pub struct DateRange {
+    start: TuduDate,
+    end: TuduDate,
+}
+
+impl DateRange {
+    pub fn new(start: TuduDate, end: TuduDate) -> Result<DateRange, TuduError> {
+        if end < start {
+            return Err(TuduError::InvalidArguments(String::from(
+                "the end of a date range must not be before its start",
+            )));
+        }
+
+        Ok(DateRange { start, end })
+    }
+
+    pub fn single(date: TuduDate) -> DateRange {
+        DateRange {
+            start: date.clone(),
+            end: date,
+        }
+    }
+
+    pub fn from_range(range: &str) -> Result<DateRange, TuduError> {
+        match range.split_once("..") {
+            Some((start, end)) => {
+                DateRange::new(TuduDate::from_date(start)?, TuduDate::from_date(end)?)
+            }
+            None => Ok(DateRange::single(TuduDate::from_date(range)?)),
+        }
+    }
+
+    pub fn dates(&self) -> Result<Vec<TuduDate>, TuduError> {
+        let end = self.end.to_naive_date()?;
+
+        let dates = self
+            .start
+            .to_naive_date()?
+            .iter_days()
+            .take_while(|date| *date <= end)
+            .map(TuduDate::from_naive_date)
+            .collect();
+
+        Ok(dates)
+    }
+}
+
 fn is_valid_date(day: u32, month: u32) -> Result<(), TuduError> {
-    return match month {
-        1 | 3 | 5 | 7 | 8 | 10 | 12 => (day <= 31).then(|| ()).ok_or(TuduError::InvalidDate),
-        4 | 6 | 9 | 11 => (day <= 30).then(|| ()).ok_or(TuduError::InvalidDate),
-        2 => (day <= 29).then(|| ()).ok_or(TuduError::InvalidDate),
-        _ => todo!(),
-    };
+    if day == 0 {
+        return Err(TuduError::InvalidDate);
+    }
+
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => (day <= 31).then_some(()).ok_or(TuduError::InvalidDate),
+        4 | 6 | 9 | 11 => (day <= 30).then_some(()).ok_or(TuduError::InvalidDate),
+        2 => (day <= 29).then_some(()).ok_or(TuduError::InvalidDate),
+        _ => Err(TuduError::InvalidDate),
+    }
 }
 
 #[cfg(test)]
@@ -122,6 +235,16 @@ mod tests {
         assert_eq!(date, expected_date);
     }
 
+    #[test]
+    fn from_date_when_given_out_of_range_date_gives_error() {
+        assert_eq!(
+            TuduDate::from_date("10-13-2023"),
+            Err(TuduError::InvalidDate)
+        );
+        assert_eq!(TuduDate::from_date("0-6"), Err(TuduError::InvalidDate));
+        assert_eq!(TuduDate::from_date("31-4"), Err(TuduError::InvalidDate));
+    }
+
     #[test]
     fn from_date_when_given_relative_dates_creates_correct_dates() {
         let today = Local::now();
@@ -134,7 +257,7 @@ mod tests {
             yesterday.month(),
             yesterday.year()
         );
-        let expected_yesterday = TuduDate::from_date(&yesterday_input);
+        let expected_yesterday = TuduDate::from_date(&yesterday_input).unwrap();
 
         let tomorrow = today + Duration::days(1);
         let tomorrow_input = format!(
@@ -143,15 +266,15 @@ mod tests {
             tomorrow.month(),
             tomorrow.year()
         );
-        let expected_tomorrow = TuduDate::from_date(&tomorrow_input);
+        let expected_tomorrow = TuduDate::from_date(&tomorrow_input).unwrap();
 
         let today_date = TuduDate::from_date("today").unwrap();
         let yeterday_date = TuduDate::from_date("yesterday").unwrap();
         let tomorrow_date = TuduDate::from_date("tomorrow").unwrap();
 
         assert_eq!(today_date, expected_today);
-        assert_eq!(today_date, expected_today);
-        assert_eq!(today_date, expected_today);
+        assert_eq!(yeterday_date, expected_yesterday);
+        assert_eq!(tomorrow_date, expected_tomorrow);
     }
 
     #[test]
@@ -168,6 +291,64 @@ mod tests {
         assert_eq!(year, date.year);
     }
 
+    #[test]
+    fn from_iso_creates_correct_date() {
+        assert_eq!(
+            TuduDate::from_iso("2023-06-07").unwrap(),
+            TuduDate::new(7, 6, 2023)
+        );
+        assert_eq!(TuduDate::from_iso("7-6-2023"), Err(TuduError::InvalidDate));
+    }
+
+    #[test]
+    fn add_days_moves_across_months_in_both_directions() {
+        let date = TuduDate::new(30, 6, 2023);
+
+        assert_eq!(date.add_days(2).unwrap(), TuduDate::new(2, 7, 2023));
+        assert_eq!(date.add_days(-30).unwrap(), TuduDate::new(31, 5, 2023));
+    }
+
+    #[test]
+    fn is_weekend_is_true_only_on_saturday_and_sunday() {
+        assert!(!TuduDate::new(9, 6, 2023).is_weekend().unwrap());
+        assert!(TuduDate::new(10, 6, 2023).is_weekend().unwrap());
+        assert!(TuduDate::new(11, 6, 2023).is_weekend().unwrap());
+        assert!(!TuduDate::new(12, 6, 2023).is_weekend().unwrap());
+    }
+
+    #[test]
+    fn dates_are_ordered_by_year_then_month_then_day() {
+        assert!(TuduDate::new(31, 12, 2022) < TuduDate::new(1, 1, 2023));
+        assert!(TuduDate::new(1, 2, 2023) > TuduDate::new(28, 1, 2023));
+    }
+
+    #[test]
+    fn from_range_when_given_range_lists_every_date() {
+        let range = DateRange::from_range("30-6-2023..2-7-2023").unwrap();
+
+        let expected_dates = vec![
+            TuduDate::new(30, 6, 2023),
+            TuduDate::new(1, 7, 2023),
+            TuduDate::new(2, 7, 2023),
+        ];
+
+        assert_eq!(range.dates().unwrap(), expected_dates);
+    }
+
+    #[test]
+    fn from_range_when_given_single_date_lists_that_date() {
+        let range = DateRange::from_range("7-6-2023").unwrap();
+
+        assert_eq!(range.dates().unwrap(), vec![TuduDate::new(7, 6, 2023)]);
+    }
+
+    #[test]
+    fn from_range_when_end_before_start_gives_error() {
+        let range = DateRange::from_range("7-6-2023..1-6-2023");
+
+        assert!(matches!(range, Err(TuduError::InvalidArguments(_))));
+    }
+
     #[test]
     fn to_filename_generates_correctly_formatted_name() {
         let date = TuduDate::new(7, 6, 2023);
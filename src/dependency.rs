@@ -0,0 +1,461 @@
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::date::TuduDate;
+use crate::error::TuduError;
+use crate::model::{Task, TaskIndex, TaskList, TaskState};
+
+pub const BLOCKED_ICON: &str = "⊘";
+
+/// A stable reference to a task that must be finished first, by the date of its task list and its
+/// id, so it survives the task being moved within its list
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Dependency {
+    pub date: TuduDate,
+    pub id: String,
+}
+
+impl Dependency {
+    /// Reads a dependency in the form used in task files, e.g. `2023-06-05/lq8x2k`
+    pub fn parse(dependency: &str) -> Result<Dependency, TuduError> {
+        let (date, id) = dependency.split_once('/').ok_or(TuduError::BadTaskFormat)?;
+
+        if id.is_empty() {
+            return Err(TuduError::BadTaskFormat);
+        }
+
+        let date = TuduDate::from_iso(date).map_err(|_| TuduError::BadTaskFormat)?;
+
+        Ok(Dependency {
+            date,
+            id: String::from(id),
+        })
+    }
+
+    pub fn format(&self) -> String {
+        format!("{}/{}", self.date.to_iso(), self.id)
+    }
+}
+
+/// Ids made so far by this process, so that ids made within the same clock tick still differ
+static TASK_IDS_MADE: AtomicU64 = AtomicU64::new(0);
+
+/// Makes an id for a task that will not be shared with any other task. The clock time and a count
+/// of the ids made so far are hashed with a key that is random for each process, so ids made at the
+/// same time by this and other processes still differ.
+pub fn new_task_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default();
+
+    let mut hasher = RandomState::new().build_hasher();
+    nanos.hash(&mut hasher);
+    process::id().hash(&mut hasher);
+    TASK_IDS_MADE
+        .fetch_add(1, Ordering::Relaxed)
+        .hash(&mut hasher);
+    let mut value = hasher.finish();
+
+    let mut id = Vec::new();
+    loop {
+        id.push(char::from_digit((value % 36) as u32, 36).unwrap_or('0'));
+        value /= 36;
+
+        if value == 0 {
+            break;
+        }
+    }
+
+    id.into_iter().rev().collect()
+}
+
+/// Every task and subtask in the list along with its user facing index
+pub fn flatten_tasks(tasks: &[Task]) -> Vec<(TaskIndex, &Task)> {
+    tasks
+        .iter()
+        .enumerate()
+        .flat_map(|(index, task)| {
+            let task_index = TaskIndex::from(index + 1);
+
+            let subtasks = task
+                .subtasks
+                .iter()
+                .enumerate()
+                .map(move |(subtask_index, subtask)| {
+                    let index = TaskIndex {
+                        subtask: Some(subtask_index + 1),
+                        ..task_index
+                    };
+
+                    (index, subtask)
+                });
+
+            std::iter::once((task_index, task)).chain(subtasks)
+        })
+        .collect()
+}
+
+/// Whether the prerequisite already waits on the task, directly or through the tasks it waits on,
+/// in which case making the task wait on it would leave them both waiting forever. The tasks of
+/// each day are given by `tasks_on`.
+pub fn waits_on(
+    prerequisite: &Task,
+    task: &Dependency,
+    mut tasks_on: impl FnMut(&TuduDate) -> Result<Vec<Task>, TuduError>,
+) -> Result<bool, TuduError> {
+    let mut tasks_by_date: BTreeMap<TuduDate, Vec<Task>> = BTreeMap::new();
+    let mut waiting_on = prerequisite.after.clone();
+    let mut seen = Vec::new();
+
+    while let Some(dependency) = waiting_on.pop() {
+        if dependency == *task {
+            return Ok(true);
+        }
+
+        if seen.contains(&dependency) {
+            continue;
+        }
+
+        if !tasks_by_date.contains_key(&dependency.date) {
+            tasks_by_date.insert(dependency.date.clone(), tasks_on(&dependency.date)?);
+        }
+
+        let found = tasks_by_date.get(&dependency.date).and_then(|tasks| {
+            flatten_tasks(tasks)
+                .into_iter()
+                .find(|(_, task)| task.id.as_deref() == Some(dependency.id.as_str()))
+                .map(|(_, task)| task.after.clone())
+        });
+
+        waiting_on.extend(found.unwrap_or_default());
+        seen.push(dependency);
+    }
+
+    Ok(false)
+}
+
+/// The tasks on the days that a set of tasks depend on, so their prerequisites can be looked up
+pub struct Prerequisites {
+    tasks_by_date: BTreeMap<TuduDate, Vec<Task>>,
+}
+
+impl Prerequisites {
+    /// Loads the task lists of every date that the tasks, or their subtasks, depend on
+    pub fn for_tasks<'a>(
+        tasks: impl IntoIterator<Item = &'a Task>,
+    ) -> Result<Prerequisites, TuduError> {
+        let mut tasks_by_date = BTreeMap::new();
+
+        for task in tasks {
+            let dependencies = task
+                .after
+                .iter()
+                .chain(task.subtasks.iter().flat_map(|subtask| &subtask.after));
+
+            for dependency in dependencies {
+                if !tasks_by_date.contains_key(&dependency.date) {
+                    let task_list = TaskList::for_date(&dependency.date)?;
+                    let tasks = task_list.tasks().to_vec();
+
+                    tasks_by_date.insert(dependency.date.clone(), tasks);
+                }
+            }
+        }
+
+        Ok(Prerequisites { tasks_by_date })
+    }
+
+    #[cfg(test)]
+    pub fn from_tasks_by_date(tasks_by_date: BTreeMap<TuduDate, Vec<Task>>) -> Prerequisites {
+        Prerequisites { tasks_by_date }
+    }
+
+    /// Finds the task a dependency refers to, along with its user facing index on its date
+    pub fn find(&self, dependency: &Dependency) -> Option<(TaskIndex, &Task)> {
+        let tasks = self.tasks_by_date.get(&dependency.date)?;
+
+        flatten_tasks(tasks)
+            .into_iter()
+            .find(|(_, task)| task.id.as_deref() == Some(dependency.id.as_str()))
+    }
+
+    /// The prerequisites of the task that are not completed, carried forward or not being done.
+    /// Prerequisites that have since been removed are treated as finished.
+    pub fn unfinished<'a>(&'a self, task: &'a Task) -> Vec<(&'a Dependency, TaskIndex, &'a Task)> {
+        task.after
+            .iter()
+            .filter_map(|dependency| {
+                let (index, prerequisite) = self.find(dependency)?;
+
+                (!is_finished(prerequisite)).then_some((dependency, index, prerequisite))
+            })
+            .collect()
+    }
+
+    /// A task is blocked while it has not been started and any prerequisite is unfinished
+    pub fn is_blocked(&self, task: &Task) -> bool {
+        task.state == TaskState::NotStarted && !self.unfinished(task).is_empty()
+    }
+}
+
+/// A task carried forward is done with on its day, and its copy on a later day is a task of its
+/// own that nothing depends on, so it no longer holds up the tasks waiting on it
+fn is_finished(task: &Task) -> bool {
+    matches!(
+        task.state,
+        TaskState::Complete | TaskState::Forwarded | TaskState::Ignored
+    )
+}
+
+/// Renders the dependencies between the tasks in the task lists as a DOT graph, with an edge from
+/// each prerequisite to the task waiting on it. Prerequisites on dates outside the task lists are
+/// included so that every edge has both ends.
+pub fn render_graph(task_lists: &[TaskList], prerequisites: &Prerequisites) -> String {
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+
+    for task_list in task_lists {
+        for (index, task) in flatten_tasks(task_list.tasks()) {
+            let state = if prerequisites.is_blocked(task) {
+                "Blocked"
+            } else {
+                task.state.name()
+            };
+
+            nodes.push((
+                node_id(task_list.date(), index, task),
+                format_label(task_list.date(), index, task, state),
+            ));
+        }
+    }
+
+    // Prerequisites come after the tasks in the task lists, so any that are in the task lists keep
+    // the label showing whether they are blocked themselves
+    for task_list in task_lists {
+        for (index, task) in flatten_tasks(task_list.tasks()) {
+            let node = node_id(task_list.date(), index, task);
+
+            for dependency in task.after.iter() {
+                let prerequisite = match prerequisites.find(dependency) {
+                    Some((index, prerequisite)) => {
+                        let label = format_label(
+                            &dependency.date,
+                            index,
+                            prerequisite,
+                            prerequisite.state.name(),
+                        );
+                        (node_id(&dependency.date, index, prerequisite), label)
+                    }
+                    None => (
+                        dependency.id.clone(),
+                        format!("{} (removed task)", dependency.date.to_iso()),
+                    ),
+                };
+
+                edges.push((prerequisite.0.clone(), node.clone()));
+                nodes.push(prerequisite);
+            }
+        }
+    }
+
+    let mut graph = String::from("digraph tudu {\n");
+
+    let mut seen = Vec::new();
+    for (node, label) in nodes {
+        if seen.contains(&node) {
+            continue;
+        }
+
+        graph.push_str(&format!(
+            "    \"{}\" [label=\"{}\"];\n",
+            escape_dot(&node),
+            escape_dot(&label)
+        ));
+        seen.push(node);
+    }
+
+    for (from, to) in edges {
+        graph.push_str(&format!(
+            "    \"{}\" -> \"{}\";\n",
+            escape_dot(&from),
+            escape_dot(&to)
+        ));
+    }
+
+    graph.push_str("}\n");
+
+    graph
+}
+
+fn node_id(date: &TuduDate, index: TaskIndex, task: &Task) -> String {
+    match &task.id {
+        Some(id) => id.clone(),
+        None => format!("{}#{index}", date.to_iso()),
+    }
+}
+
+fn format_label(date: &TuduDate, index: TaskIndex, task: &Task, state: &str) -> String {
+    format!("{} {index}: {} ({state})", date.to_iso(), task.task)
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn task_with_id(task: &str, state: TaskState, id: &str) -> Task {
+        let mut task = Task::new(String::from(task), state);
+        task.id = Some(String::from(id));
+        task
+    }
+
+    fn dependency(date: &TuduDate, id: &str) -> Dependency {
+        Dependency {
+            date: date.clone(),
+            id: String::from(id),
+        }
+    }
+
+    #[test]
+    fn format_then_parse_dependency_gives_back_the_same_dependency() {
+        let dependency = dependency(&TuduDate::new(5, 6, 2023), "lq8x2k");
+
+        let formatted = dependency.format();
+
+        assert_eq!(formatted, "2023-06-05/lq8x2k");
+        assert_eq!(Dependency::parse(&formatted).unwrap(), dependency);
+    }
+
+    #[test]
+    fn parse_dependency_without_id_gives_error() {
+        assert_eq!(
+            Dependency::parse("2023-06-05/"),
+            Err(TuduError::BadTaskFormat)
+        );
+        assert_eq!(Dependency::parse("lq8x2k"), Err(TuduError::BadTaskFormat));
+    }
+
+    #[test]
+    fn new_task_id_gives_different_ids() {
+        let ids: Vec<String> = (0..1000).map(|_| new_task_id()).collect();
+        let distinct: HashSet<&String> = ids.iter().collect();
+
+        assert!(ids.iter().all(|id| !id.is_empty()));
+        assert_eq!(distinct.len(), ids.len());
+    }
+
+    #[test]
+    fn unfinished_finds_prerequisites_across_days() {
+        let earlier = TuduDate::new(5, 6, 2023);
+        let today = TuduDate::new(7, 6, 2023);
+        let prerequisites = Prerequisites::from_tasks_by_date(BTreeMap::from([
+            (
+                earlier.clone(),
+                vec![task_with_id("Write spec", TaskState::Started, "spec")],
+            ),
+            (
+                today.clone(),
+                vec![
+                    task_with_id("Review", TaskState::Complete, "review"),
+                    task_with_id("Plan", TaskState::Forwarded, "plan"),
+                ],
+            ),
+        ]));
+
+        let mut task = Task::new(String::from("Implement"), TaskState::NotStarted);
+        task.after = vec![
+            dependency(&earlier, "spec"),
+            dependency(&today, "review"),
+            dependency(&today, "plan"),
+            dependency(&today, "removed"),
+        ];
+
+        let unfinished = prerequisites.unfinished(&task);
+
+        assert_eq!(unfinished.len(), 1);
+        assert_eq!(unfinished[0].1, TaskIndex::from(1));
+        assert_eq!(unfinished[0].2.task, "Write spec");
+        assert!(prerequisites.is_blocked(&task));
+
+        task.state = TaskState::Complete;
+        assert!(!prerequisites.is_blocked(&task));
+    }
+
+    #[test]
+    fn waits_on_follows_prerequisites_across_days() {
+        let earlier = TuduDate::new(5, 6, 2023);
+        let today = TuduDate::new(7, 6, 2023);
+
+        let mut spec = task_with_id("Write spec", TaskState::Started, "spec");
+        spec.after = vec![dependency(&today, "implement")];
+        let mut review = task_with_id("Review", TaskState::NotStarted, "review");
+        review.after = vec![dependency(&earlier, "spec")];
+        let tasks_by_date = BTreeMap::from([
+            (earlier.clone(), vec![spec]),
+            (
+                today.clone(),
+                vec![
+                    task_with_id("Implement", TaskState::NotStarted, "implement"),
+                    review.clone(),
+                ],
+            ),
+        ]);
+        let tasks_on = |date: &TuduDate| Ok(tasks_by_date.get(date).cloned().unwrap_or_default());
+
+        let unrelated = task_with_id("Unrelated", TaskState::NotStarted, "unrelated");
+
+        assert_eq!(
+            waits_on(&review, &dependency(&today, "implement"), tasks_on),
+            Ok(true)
+        );
+        assert_eq!(
+            waits_on(&review, &dependency(&today, "other"), tasks_on),
+            Ok(false)
+        );
+        assert_eq!(
+            waits_on(&unrelated, &dependency(&today, "implement"), tasks_on),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn render_graph_links_prerequisites_to_their_tasks() {
+        let earlier = TuduDate::new(5, 6, 2023);
+        let today = TuduDate::new(7, 6, 2023);
+        let prerequisites = Prerequisites::from_tasks_by_date(BTreeMap::from([(
+            earlier.clone(),
+            vec![task_with_id("Write \"spec\"", TaskState::Started, "spec")],
+        )]));
+
+        let mut blocked = Task::new(String::from("Implement"), TaskState::NotStarted);
+        blocked.after = vec![dependency(&earlier, "spec")];
+        let task_lists = vec![TaskList::from_tasks(
+            &today,
+            vec![
+                Task::new(String::from("Unrelated"), TaskState::Complete),
+                blocked,
+            ],
+        )];
+
+        let expected_graph = "digraph tudu {
+    \"2023-06-07#1\" [label=\"2023-06-07 1: Unrelated (Completed)\"];
+    \"2023-06-07#2\" [label=\"2023-06-07 2: Implement (Blocked)\"];
+    \"spec\" [label=\"2023-06-05 1: Write \\\"spec\\\" (Started)\"];
+    \"spec\" -> \"2023-06-07#2\";
+}
+";
+
+        let graph = render_graph(&task_lists, &prerequisites);
+
+        assert_eq!(graph, expected_graph);
+    }
+}
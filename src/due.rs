@@ -0,0 +1,212 @@
+use std::env;
+
+use crate::date::TuduDate;
+use crate::dependency::flatten_tasks;
+use crate::error::TuduError;
+use crate::model::{Task, TaskIndex, TaskList, TaskState};
+
+pub const OVERDUE_ICON: &str = "⚠";
+
+const DEFAULT_OVERDUE_DAYS: i64 = 30;
+
+/// A task is overdue once its due date has passed while it is still to do or in progress. Tasks
+/// carried forward are left out, as the task on the later day is the one to finish.
+pub fn is_overdue(task: &Task, today: &TuduDate) -> bool {
+    matches!(task.state, TaskState::NotStarted | TaskState::Started)
+        && task.due.as_ref().is_some_and(|due| due < today)
+}
+
+/// Describes the due date of a task for display next to it, flagging it when overdue
+pub fn format_due(task: &Task, today: &TuduDate) -> Option<String> {
+    let due = task.due.as_ref()?;
+
+    if is_overdue(task, today) {
+        return Some(format!("{OVERDUE_ICON} due {}", due.to_iso()));
+    }
+
+    Some(format!("due {}", due.to_iso()))
+}
+
+/// Every overdue task and subtask in the task lists, along with the date it is planned on and its
+/// user facing index
+pub fn find_overdue<'a>(
+    task_lists: &'a [TaskList],
+    today: &TuduDate,
+) -> Vec<(&'a TuduDate, TaskIndex, &'a Task)> {
+    task_lists
+        .iter()
+        .flat_map(|task_list| {
+            flatten_tasks(task_list.tasks())
+                .into_iter()
+                .filter(|(_, task)| is_overdue(task, today))
+                .map(|(index, task)| (task_list.date(), index, task))
+        })
+        .collect()
+}
+
+/// Lists the overdue tasks in the task lists with the day each is planned on and its due date
+pub fn render_overdue(task_lists: &[TaskList], today: &TuduDate) -> String {
+    let overdue = find_overdue(task_lists, today);
+
+    if overdue.is_empty() {
+        return String::from("There are no overdue tasks\n");
+    }
+
+    overdue
+        .into_iter()
+        .map(|(date, index, task)| {
+            format!(
+                "{}    {index}    {} - {}    {}\n",
+                date.to_iso(),
+                task.state.icon(),
+                task.task,
+                format_due(task, today).unwrap_or_default()
+            )
+        })
+        .collect()
+}
+
+/// How many days back viewing tasks looks for overdue tasks, set with the TUDU_OVERDUE_DAYS env
+/// variable and defaulting to 30, so viewing a day does not read every day file
+pub fn overdue_days() -> Result<i64, TuduError> {
+    match env::var("TUDU_OVERDUE_DAYS") {
+        Ok(days) => days.parse().ok().filter(|days| *days >= 0).ok_or_else(|| {
+            TuduError::InvalidArguments(format!(
+                "TUDU_OVERDUE_DAYS of `{days}` is not a number of days such as 14"
+            ))
+        }),
+        Err(_) => Ok(DEFAULT_OVERDUE_DAYS),
+    }
+}
+
+/// The dates from the given number of days before today up to and including today. Later days are
+/// left out, as their tasks are not expected to be done yet.
+pub fn recent_dates(dates: Vec<TuduDate>, today: &TuduDate, days: i64) -> Vec<TuduDate> {
+    let earliest = today.add_days(-days).ok();
+
+    dates
+        .into_iter()
+        .filter(|date| date <= today)
+        .filter(|date| earliest.as_ref().is_none_or(|earliest| date >= earliest))
+        .collect()
+}
+
+/// The banner shown above the tasks when any are overdue
+pub fn render_overdue_warning(overdue_count: usize) -> Option<String> {
+    match overdue_count {
+        0 => None,
+        1 => Some(format!(
+            "{OVERDUE_ICON} 1 task is overdue, run `tudu overdue` to see it"
+        )),
+        count => Some(format!(
+            "{OVERDUE_ICON} {count} tasks are overdue, run `tudu overdue` to see them"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task_due(task: &str, state: TaskState, due: TuduDate) -> Task {
+        Task {
+            due: Some(due),
+            ..Task::new(String::from(task), state)
+        }
+    }
+
+    #[test]
+    fn is_overdue_only_for_unfinished_tasks_past_their_due_date() {
+        let today = TuduDate::new(7, 6, 2023);
+        let yesterday = TuduDate::new(6, 6, 2023);
+
+        assert!(is_overdue(
+            &task_due("Late", TaskState::Started, yesterday.clone()),
+            &today
+        ));
+        assert!(!is_overdue(
+            &task_due("Due today", TaskState::NotStarted, today.clone()),
+            &today
+        ));
+        assert!(!is_overdue(
+            &task_due("Done late", TaskState::Complete, yesterday.clone()),
+            &today
+        ));
+        assert!(!is_overdue(
+            &task_due("Moved on", TaskState::Forwarded, yesterday),
+            &today
+        ));
+        assert!(!is_overdue(
+            &Task::new(String::from("No due date"), TaskState::NotStarted),
+            &today
+        ));
+    }
+
+    #[test]
+    fn render_overdue_lists_tasks_and_subtasks_across_days() {
+        let today = TuduDate::new(7, 6, 2023);
+        let earlier = TuduDate::new(5, 6, 2023);
+        let mut release = Task::new(String::from("Release 1.2"), TaskState::Started);
+        release.subtasks = vec![task_due(
+            "Write changelog",
+            TaskState::NotStarted,
+            TuduDate::new(6, 6, 2023),
+        )];
+        let task_lists = vec![
+            TaskList::from_tasks(
+                &earlier,
+                vec![task_due(
+                    "Send invoice",
+                    TaskState::NotStarted,
+                    TuduDate::new(5, 6, 2023),
+                )],
+            ),
+            TaskList::from_tasks(
+                &today,
+                vec![
+                    release,
+                    task_due("Plan sprint", TaskState::NotStarted, today.clone()),
+                ],
+            ),
+        ];
+
+        let expected_overdue = "2023-06-05    1    ◯ - Send invoice    ⚠ due 2023-06-05
+2023-06-07    1.1    ◯ - Write changelog    ⚠ due 2023-06-06
+";
+
+        assert_eq!(render_overdue(&task_lists, &today), expected_overdue);
+    }
+
+    #[test]
+    fn recent_dates_keeps_days_within_the_window_up_to_today() {
+        let today = TuduDate::new(7, 6, 2023);
+        let dates = vec![
+            TuduDate::new(7, 5, 2023),
+            TuduDate::new(8, 5, 2023),
+            TuduDate::new(6, 6, 2023),
+            today.clone(),
+            TuduDate::new(8, 6, 2023),
+        ];
+
+        assert_eq!(
+            recent_dates(dates.clone(), &today, 30),
+            vec![
+                TuduDate::new(8, 5, 2023),
+                TuduDate::new(6, 6, 2023),
+                today.clone()
+            ]
+        );
+        assert_eq!(recent_dates(dates, &today, 0), vec![today.clone()]);
+    }
+
+    #[test]
+    fn render_overdue_warning_only_when_tasks_are_overdue() {
+        assert_eq!(render_overdue_warning(0), None);
+        assert_eq!(
+            render_overdue_warning(2),
+            Some(String::from(
+                "⚠ 2 tasks are overdue, run `tudu overdue` to see them"
+            ))
+        );
+    }
+}
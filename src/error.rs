@@ -11,6 +11,14 @@ pub enum TuduError {
     BadTaskFormat,
     InvalidTaskDirectory,
     FailedToMakeDirectory,
+    InvalidRows(Vec<(usize, String)>),
+    FailedToOpenEditor(String),
+    ChangedElsewhere(String),
+    GitFailed(String),
+    FailedToNotify(String),
+    HookFailed(String),
+    ScriptFailed(String),
+    InvalidConfig(String),
 }
 
 pub fn fail_with_error(error: TuduError) {
@@ -20,33 +28,60 @@ pub fn fail_with_error(error: TuduError) {
 }
 
 fn print_user_error(error: TuduError) {
+    println!("{}", user_error_message(error));
+}
+
+/// Describes the error to the user, as printed by the cli and returned by the server
+pub fn user_error_message(error: TuduError) -> String {
     match error {
-        TuduError::InvalidDate => println!(
+        TuduError::InvalidDate => String::from(
             "The date entered is not a valid date, enter a date in the form of 10-6-2023, or 13-2"
         ),
-        TuduError::InvalidArguments(err) => println!("Invalid argument: {err}"),
-        TuduError::InvalidIndex => println!("The index entered does not exist for this date"),
+        TuduError::InvalidArguments(err) => format!("Invalid argument: {err}"),
+        TuduError::InvalidIndex => String::from("The index entered does not exist for this date"),
         TuduError::InvalidState => {
-            println!("The state entered is not valid, see tudu help for more info")
+            String::from("The state entered is not valid, see tudu help for more info")
         }
         TuduError::InvalidCommand => {
-            println!("The command enetered is not valid, see tudu help for more info")
+            String::from("The command enetered is not valid, see tudu help for more info")
         }
-        TuduError::NoTaskFile => println!(
+        TuduError::NoTaskFile => String::from(
             "No task file found, if using TUDU_TASKS env variable make sure the folder exists"
         ),
         TuduError::FailedToReadFile => {
-            println!("Failed to read file, make sure it has the correct permissions")
+            String::from("Failed to read file, make sure it has the correct permissions")
         }
-        TuduError::FailedToWriteFile => println!(
+        TuduError::FailedToWriteFile => String::from(
             "Failed to write tasks to file, make sure the folder has the correct permissions"
         ),
-        TuduError::BadTaskFormat => println!("The tasks file was in a bad format"),
+        TuduError::BadTaskFormat => String::from("The tasks file was in a bad format"),
         TuduError::InvalidTaskDirectory => {
-            println!("The directory specified in TUDU_TASKS is not valid or does not exist")
+            String::from("The directory specified in TUDU_TASKS is not valid or does not exist")
         }
         TuduError::FailedToMakeDirectory => {
-            println!("Failed to create directory, check the permissions for HOME directory")
+            String::from("Failed to create directory, check the permissions for HOME directory")
+        }
+        TuduError::InvalidRows(errors) => {
+            let rows: Vec<String> = errors
+                .iter()
+                .map(|(row, reason)| format!("Row {row}: {reason}"))
+                .collect();
+
+            format!(
+                "Nothing was imported as some rows are invalid:\n{}",
+                rows.join("\n")
+            )
         }
+        TuduError::FailedToOpenEditor(editor) => format!(
+            "Failed to edit the note with `{editor}`, set the EDITOR env variable to your editor"
+        ),
+        TuduError::GitFailed(err) => format!("Git failed while keeping the history of the tasks: {err}"),
+        TuduError::FailedToNotify(err) => format!("Failed to send a reminder: {err}"),
+        TuduError::HookFailed(err) => format!("A hook failed: {err}"),
+        TuduError::ScriptFailed(err) => format!("The script failed: {err}"),
+        TuduError::InvalidConfig(err) => format!("The config is not valid: {err}"),
+        TuduError::ChangedElsewhere(date) => format!(
+            "The tasks for {date} were changed by something else while tudu was using them, nothing was saved so try again"
+        ),
     }
 }
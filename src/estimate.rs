@@ -0,0 +1,239 @@
+use std::env;
+
+use chrono::Duration;
+
+use crate::error::TuduError;
+use crate::model::{Task, TaskList, TaskState};
+use crate::timelog::format_duration;
+
+const ESTIMATE_PREFIX: char = '~';
+const DEFAULT_CAPACITY_HOURS: i64 = 8;
+
+/// Reads an estimate such as `~30m`, `~2h` or `~1h30m`, the leading `~` being optional
+pub fn parse_estimate(estimate: &str) -> Result<Duration, TuduError> {
+    let invalid = || {
+        TuduError::InvalidArguments(format!(
+            "`{estimate}` is not an estimate, write it like ~30m, ~2h or ~1h30m"
+        ))
+    };
+
+    let text = estimate.strip_prefix(ESTIMATE_PREFIX).unwrap_or(estimate);
+
+    let (hours, minutes) = match text.split_once('h') {
+        Some((hours, minutes)) => (hours.parse::<u32>().map_err(|_| invalid())?, minutes),
+        None => (0, text),
+    };
+
+    let minutes = match minutes {
+        "" => 0,
+        minutes => match minutes.strip_suffix('m') {
+            Some(minutes) => minutes.parse::<u32>().map_err(|_| invalid())?,
+            None => return Err(invalid()),
+        },
+    };
+
+    let estimate = Duration::hours(i64::from(hours)) + Duration::minutes(i64::from(minutes));
+
+    if estimate <= Duration::zero() {
+        return Err(invalid());
+    }
+
+    Ok(estimate)
+}
+
+/// Splits an estimate written as the last word of a task description, e.g. `Write docs ~30m`. A
+/// last word that is not an estimate, such as `~/.bashrc`, is left as part of the description.
+pub fn split_estimate(task: &str) -> Result<(String, Option<Duration>), TuduError> {
+    let task = task.trim_end();
+
+    let (description, word) = match task.rsplit_once(' ') {
+        Some((description, word)) => (description, word),
+        None => ("", task),
+    };
+
+    let estimate = match word.starts_with(ESTIMATE_PREFIX) {
+        true => parse_estimate(word).ok(),
+        false => None,
+    };
+
+    let Some(estimate) = estimate else {
+        return Ok((String::from(task), None));
+    };
+
+    if description.trim().is_empty() {
+        return Err(TuduError::InvalidArguments(String::from(
+            "the task needs a description as well as an estimate",
+        )));
+    }
+
+    Ok((String::from(description.trim_end()), Some(estimate)))
+}
+
+pub fn format_estimate(estimate: Duration) -> String {
+    let hours = estimate.num_hours();
+    let minutes = estimate.num_minutes() % 60;
+
+    match (hours, minutes) {
+        (0, minutes) => format!("{ESTIMATE_PREFIX}{minutes}m"),
+        (hours, 0) => format!("{ESTIMATE_PREFIX}{hours}h"),
+        (hours, minutes) => format!("{ESTIMATE_PREFIX}{hours}h{minutes}m"),
+    }
+}
+
+/// The effort that fits into a day, set with the TUDU_CAPACITY env variable and defaulting to 8h
+pub fn daily_capacity() -> Result<Duration, TuduError> {
+    match env::var("TUDU_CAPACITY") {
+        Ok(capacity) => parse_estimate(&capacity).map_err(|_| {
+            TuduError::InvalidArguments(format!(
+                "TUDU_CAPACITY of `{capacity}` is not a duration such as 6h or 7h30m"
+            ))
+        }),
+        Err(_) => Ok(Duration::hours(DEFAULT_CAPACITY_HOURS)),
+    }
+}
+
+/// Totals the estimates of the tasks still planned for the day, leaving out tasks that were carried
+/// forward or are not being done. A task without its own estimate counts those of its subtasks.
+pub fn planned_effort(tasks: &[Task]) -> Option<Duration> {
+    let estimates: Vec<Duration> = tasks
+        .iter()
+        .filter(|task| !matches!(task.state, TaskState::Forwarded | TaskState::Ignored))
+        .filter_map(|task| task.estimate.or_else(|| planned_effort(&task.subtasks)))
+        .collect();
+
+    if estimates.is_empty() {
+        return None;
+    }
+
+    Some(
+        estimates
+            .into_iter()
+            .fold(Duration::zero(), |total, estimate| total + estimate),
+    )
+}
+
+/// Summarises the planned effort for the day against the capacity, if any tasks have estimates
+pub fn render_plan(task_list: &TaskList, capacity: Duration) -> Option<String> {
+    let planned = planned_effort(task_list.tasks())?;
+
+    let summary = format!(
+        "Planned {} of {}",
+        format_duration(planned),
+        format_duration(capacity)
+    );
+
+    if planned > capacity {
+        return Some(format!(
+            "{summary} - overbooked by {}",
+            format_duration(planned - capacity)
+        ));
+    }
+
+    Some(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date::TuduDate;
+
+    #[test]
+    fn parse_estimate_reads_hours_and_minutes() {
+        assert_eq!(parse_estimate("~30m").unwrap(), Duration::minutes(30));
+        assert_eq!(parse_estimate("~2h").unwrap(), Duration::hours(2));
+        assert_eq!(parse_estimate("1h30m").unwrap(), Duration::minutes(90));
+    }
+
+    #[test]
+    fn parse_estimate_rejects_other_text() {
+        for estimate in ["~", "~soon", "~30", "~2d", "~0m", "~h30m", "~1h-5m"] {
+            assert!(
+                matches!(
+                    parse_estimate(estimate),
+                    Err(TuduError::InvalidArguments(_))
+                ),
+                "{estimate} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn split_estimate_takes_estimate_from_end_of_task() {
+        let (task, estimate) = split_estimate("Write the docs ~1h30m").unwrap();
+
+        assert_eq!(task, "Write the docs");
+        assert_eq!(estimate, Some(Duration::minutes(90)));
+    }
+
+    #[test]
+    fn split_estimate_without_estimate_leaves_task_alone() {
+        let (task, estimate) = split_estimate("Approx. 3 ~ 4 pages").unwrap();
+
+        assert_eq!(task, "Approx. 3 ~ 4 pages");
+        assert_eq!(estimate, None);
+    }
+
+    #[test]
+    fn split_estimate_keeps_last_word_that_is_not_an_estimate() {
+        let (path, path_estimate) = split_estimate("Edit ~/.bashrc").unwrap();
+        let (tilde, tilde_estimate) = split_estimate("Fix ~ handling").unwrap();
+        let (typo, typo_estimate) = split_estimate("Tidy up ~30x").unwrap();
+
+        assert_eq!(path, "Edit ~/.bashrc");
+        assert_eq!(path_estimate, None);
+        assert_eq!(tilde, "Fix ~ handling");
+        assert_eq!(tilde_estimate, None);
+        assert_eq!(typo, "Tidy up ~30x");
+        assert_eq!(typo_estimate, None);
+    }
+
+    #[test]
+    fn split_estimate_without_description_gives_error() {
+        let result = split_estimate("~30m");
+
+        assert!(matches!(result, Err(TuduError::InvalidArguments(_))));
+    }
+
+    #[test]
+    fn format_estimate_gives_shortest_form() {
+        assert_eq!(format_estimate(Duration::minutes(30)), "~30m");
+        assert_eq!(format_estimate(Duration::hours(2)), "~2h");
+        assert_eq!(format_estimate(Duration::minutes(90)), "~1h30m");
+    }
+
+    #[test]
+    fn render_plan_warns_when_overbooked() {
+        let date = TuduDate::new(7, 6, 2023);
+        let mut tasks = vec![
+            Task::new(String::from("Big job"), TaskState::Started),
+            Task::new(String::from("Small job"), TaskState::NotStarted),
+            Task::new(String::from("Moved on"), TaskState::Forwarded),
+        ];
+        tasks[0].estimate = Some(Duration::hours(6));
+        tasks[1].estimate = Some(Duration::minutes(150));
+        tasks[2].estimate = Some(Duration::hours(4));
+        tasks[1].subtasks = vec![Task::new(String::from("Step"), TaskState::NotStarted)];
+        tasks[1].subtasks[0].estimate = Some(Duration::hours(1));
+        let task_list = TaskList::from_tasks(&date, tasks);
+
+        let plan = render_plan(&task_list, Duration::hours(8));
+
+        assert_eq!(
+            plan,
+            Some(String::from(
+                "Planned 8h 30m of 8h 00m - overbooked by 0h 30m"
+            ))
+        );
+    }
+
+    #[test]
+    fn render_plan_without_estimates_gives_nothing() {
+        let date = TuduDate::new(7, 6, 2023);
+        let task_list = TaskList::from_tasks(
+            &date,
+            vec![Task::new(String::from("Unplanned"), TaskState::NotStarted)],
+        );
+
+        assert_eq!(render_plan(&task_list, Duration::hours(8)), None);
+    }
+}
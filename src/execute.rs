@@ -1,18 +1,108 @@
-use crate::date::TuduDate;
+use std::collections::BTreeMap;
+use std::io::{self, IsTerminal, Read};
+use std::{env, fs};
+
+use chrono::Duration;
+
+use crate::alias::render_aliases;
+use crate::completions::{generate_script, get_task_candidates};
+use crate::config::{config_path, Config};
+use crate::date::{now, TuduDate};
+use crate::dependency::{new_task_id, render_graph, waits_on, Dependency, Prerequisites};
+use crate::due::{
+    find_overdue, format_due, overdue_days, recent_dates, render_overdue, render_overdue_warning,
+};
+use crate::estimate::{daily_capacity, format_estimate, render_plan};
+use crate::history::{self, record_change};
+use crate::hooks::{check_day_rollover, Hook, HookEvent};
+use crate::journal::{Journal, JOURNAL_DIRECTORY};
+use crate::lists::{current_list, list_names, render_lists, with_list};
+use crate::merge::merge_tasks;
 use crate::model::{
-    AddCommand, Command, EditCommand, RemoveCommand, SetCommand, Task, TaskList, TaskState,
-    ViewCommand,
+    AddCommand, AtCommand, Command, CompletionsCommand, DependCommand, DueCommand, EditCommand,
+    ExportCommand, Format, GraphCommand, ImportCommand, JournalCommand, LogCommand, MergeCommand,
+    MoveCommand, NextCommand, NoteCommand, PriorityCommand, RemoveCommand, ReportCommand,
+    SearchCommand, ServeCommand, SetCommand, ShowCommand, SnoozeCommand, StandupCommand,
+    StartCommand, StopCommand, Task, TaskIndex, TaskList, TaskState, TimesheetCommand, ViewCommand,
 };
+use crate::next::{rank_tasks, render_candidates};
+use crate::notes::{edit_in_editor, Notes, NOTE_MARKER};
+use crate::priority::format_priority;
+use crate::reminders::{format_reminder, format_time, reminder_key, Reminders};
+use crate::report::render_report;
+use crate::search::search_day;
+use crate::standup::{previous_dates, render_standup};
+use crate::storage::{list_file_dates, parse_task_file, write_tasks_to_file, TasksLock};
+use crate::timelog::{format_duration, render_timesheet, TimeLog};
 use crate::TuduError;
+use crate::{csv, daemon, ical, reminders, scripts, server, todotxt};
 
 pub fn execute_command(command: Command) -> Result<(), TuduError> {
+    // Completing, merging and help are run by the shell, git and the user in passing rather than
+    // as using tudu for the day
+    let is_using_tasks = !matches!(
+        command,
+        Command::Completions(_)
+            | Command::TaskCandidates(_)
+            | Command::Merge(_)
+            | Command::Help(_)
+            | Command::Macro(_)
+            | Command::InList(_, _)
+    );
+
+    if is_using_tasks {
+        check_day_rollover(&TuduDate::today())?;
+    }
+
     match command {
         Command::Add(config) => execute_add(config),
         Command::Remove(config) => execute_remove(config),
         Command::Set(config) => execute_set(config),
         Command::Edit(config) => execute_edit(config),
         Command::View(config) => execute_view(config),
-        Command::Help => Ok(execute_help()),
+        Command::Completions(config) => {
+            execute_completions(config);
+            Ok(())
+        }
+        Command::Export(config) => execute_export(config),
+        Command::Import(config) => execute_import(config),
+        Command::Report(config) => execute_report(config),
+        Command::Standup(config) => execute_standup(config),
+        Command::Start(config) => execute_start(config),
+        Command::Stop(config) => execute_stop(config),
+        Command::Timesheet(config) => execute_timesheet(config),
+        Command::Depend(config) => execute_depend(config),
+        Command::Graph(config) => execute_graph(config),
+        Command::Note(config) => execute_note(config),
+        Command::Show(config) => execute_show(config),
+        Command::Journal(config) => execute_journal(config),
+        Command::Search(config) => execute_search(config),
+        Command::Due(config) => execute_due(config),
+        Command::Overdue => execute_overdue(),
+        Command::Priority(config) => execute_priority(config),
+        Command::Next(config) => execute_next(config),
+        Command::Log(config) => execute_log(config),
+        Command::Sync => execute_sync(),
+        Command::Merge(config) => execute_merge(config),
+        Command::Serve(config) => execute_serve(config),
+        Command::Daemon => daemon::run(),
+        Command::At(config) => execute_at(config),
+        Command::Remind(config) => reminders::watch(&config.notifier, config.once),
+        Command::Snooze(config) => execute_snooze(config),
+        Command::Script(config) => scripts::run_script(&config.name, &config.script, &config.args),
+        Command::Alias => execute_alias(),
+        Command::Macro(commands) => commands.into_iter().try_for_each(execute_command),
+        Command::InList(list, command) => with_list(&list, || execute_command(*command)),
+        Command::Lists => execute_lists(),
+        Command::Move(config) => execute_move(config),
+        Command::TaskCandidates(date) => {
+            execute_task_candidates(date);
+            Ok(())
+        }
+        Command::Help(usage) => {
+            execute_help(usage);
+            Ok(())
+        }
     }
 }
 
@@ -22,17 +112,152 @@ fn execute_add(config: AddCommand) -> Result<(), TuduError> {
         None => TuduDate::today(),
     };
 
-    let new_task = Task::new(config.task, TaskState::NotStarted);
+    let message = format!("add {} {}", date.to_iso(), config.task);
+
+    let mut new_task = Task::new(config.task, TaskState::NotStarted);
+    new_task.estimate = config.estimate;
+    new_task.due = config.due;
+    new_task.priority = config.priority;
+    new_task.at = config.at;
+
+    // Reminders are kept by task id
+    if new_task.at.is_some() {
+        new_task.id = Some(new_task_id());
+    }
+
+    let lock = TasksLock::acquire()?;
 
     let mut task_list = TaskList::for_date(&date)?;
 
-    task_list.add_task(new_task);
+    let post_add = add_task(&mut task_list, new_task, config.parent)?;
+
+    derive_parent_states_if_enabled(&mut task_list);
 
     task_list.write_to_file()?;
 
-    print_tasks(&task_list);
+    record_change(&message)?;
 
-    Ok(())
+    // Hooks run once the tasks are released, so they can run tudu themselves
+    drop(lock);
+    post_add.run_after_change();
+
+    print_tasks(&task_list)
+}
+
+/// Adds the task, as a subtask of the parent when given one, once the pre-add hook allows it. Gives
+/// the post-add hook to run once the change is saved.
+pub fn add_task(
+    task_list: &mut TaskList,
+    new_task: Task,
+    parent: Option<usize>,
+) -> Result<Hook, TuduError> {
+    let date = task_list.date().clone();
+
+    let index = new_task_index(task_list, parent)?;
+
+    run_pre_hook(
+        task_list,
+        None,
+        &Hook::for_task(HookEvent::PreAdd, &date, index, &new_task),
+    )?;
+
+    // The hook may have added tasks itself, which the new task goes after
+    let index = new_task_index(task_list, parent)?;
+
+    let post_add = Hook::for_task(HookEvent::PostAdd, &date, index, &new_task);
+
+    match parent {
+        Some(parent) => task_list.add_subtask(parent, new_task)?,
+        None => task_list.add_task(new_task)?,
+    }
+
+    Ok(post_add)
+}
+
+/// The index a task added to the end of the list, or of the parent's subtasks, is given
+fn new_task_index(task_list: &TaskList, parent: Option<usize>) -> Result<TaskIndex, TuduError> {
+    match parent {
+        Some(parent) => match task_list.get_task(parent.into()) {
+            Some(parent_task) => Ok(TaskIndex {
+                task: parent,
+                subtask: Some(parent_task.subtasks.len() + 1),
+            }),
+            None => Err(TuduError::InvalidIndex),
+        },
+        None => Ok((task_list.tasks().len() + 1).into()),
+    }
+}
+
+/// Runs a pre hook, then reads the day again if tudu run by the hook changed it so the change is
+/// made on top. The task at the index, if given, must still be the one the hook was given.
+fn run_pre_hook(
+    task_list: &mut TaskList,
+    index: Option<TaskIndex>,
+    hook: &Hook,
+) -> Result<(), TuduError> {
+    let text = index
+        .and_then(|index| task_list.get_task(index))
+        .map(|task| task.task.clone());
+
+    hook.run()?;
+
+    if !task_list.reload_if_changed()? {
+        return Ok(());
+    }
+
+    match index {
+        Some(index) if task_list.get_task(index).map(|task| &task.task) != text.as_ref() => {
+            Err(TuduError::InvalidIndex)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Sets the state of the task once the pre-state-change hook allows it. Gives the hooks to run once
+/// the change is saved, which are none when the task already has the state.
+pub fn set_task_state(
+    task_list: &mut TaskList,
+    index: TaskIndex,
+    state: TaskState,
+) -> Result<Vec<Hook>, TuduError> {
+    let date = task_list.date().clone();
+
+    let pre_state_change = match task_list.get_task(index) {
+        Some(task) if task.state == state => return Ok(Vec::new()),
+        Some(task) => Hook::for_state_change(
+            HookEvent::PreStateChange,
+            &date,
+            index,
+            task,
+            &task.state,
+            &state,
+        ),
+        None => return Err(TuduError::InvalidIndex),
+    };
+
+    run_pre_hook(task_list, Some(index), &pre_state_change)?;
+
+    // The hook may have changed the state itself
+    let old_state = match task_list.get_task(index) {
+        Some(task) if task.state == state => return Ok(Vec::new()),
+        Some(task) => task.state.clone(),
+        None => return Err(TuduError::InvalidIndex),
+    };
+
+    task_list.set_task_state(index, state.clone())?;
+
+    let task = task_list.get_task(index).ok_or(TuduError::InvalidIndex)?;
+
+    let mut events = vec![HookEvent::OnStateChange];
+
+    if state == TaskState::Complete {
+        events.push(HookEvent::OnComplete);
+    }
+
+    Ok(events
+        .into_iter()
+        .map(|event| Hook::for_state_change(event, &date, index, task, &old_state, &state))
+        .collect())
 }
 
 fn execute_remove(config: RemoveCommand) -> Result<(), TuduError> {
@@ -41,15 +266,83 @@ fn execute_remove(config: RemoveCommand) -> Result<(), TuduError> {
         None => TuduDate::today(),
     };
 
+    let lock = TasksLock::acquire()?;
+
     let mut task_list = TaskList::for_date(&date)?;
 
-    task_list.remove_task(config.index)?;
+    let (message, on_remove) = remove_task(&mut task_list, config.index)?;
+
+    record_change(&message)?;
+
+    drop(lock);
+    on_remove.run_after_change();
+
+    print_tasks(&task_list)
+}
+
+/// Removes the task at the index and saves the day once the pre-remove hook allows it, along with
+/// the notes and time kept for the task so they do not end up with other tasks. Gives the message
+/// to record the change with and the on-remove hook to run once it is recorded.
+pub fn remove_task(
+    task_list: &mut TaskList,
+    index: TaskIndex,
+) -> Result<(String, Hook), TuduError> {
+    let date = task_list.date().clone();
+
+    let pre_remove = match task_list.get_task(index) {
+        Some(task) => Hook::for_task(HookEvent::PreRemove, &date, index, task),
+        None => return Err(TuduError::InvalidIndex),
+    };
+
+    run_pre_hook(task_list, Some(index), &pre_remove)?;
+
+    let (message, on_remove) = match task_list.get_task(index) {
+        Some(task) => (
+            format!("remove {} #{} {}", date.to_iso(), index, task.task),
+            Hook::for_task(HookEvent::OnRemove, &date, index, task),
+        ),
+        None => return Err(TuduError::InvalidIndex),
+    };
+
+    // Notes and time are kept by task id, so the ids are gathered before the task and its subtasks go
+    let removed_ids: Vec<String> = match task_list.get_task(index) {
+        Some(task) => std::iter::once(task)
+            .chain(task.subtasks.iter())
+            .filter_map(|task| task.id.clone())
+            .collect(),
+        None => Vec::new(),
+    };
+
+    task_list.remove_task(index)?;
+
+    derive_parent_states_if_enabled(task_list);
 
     task_list.write_to_file()?;
 
-    print_tasks(&task_list);
+    if !removed_ids.is_empty() {
+        let mut notes = Notes::for_date(&date)?;
+        removed_ids.iter().for_each(|id| notes.remove(id));
+        notes.write_to_file()?;
 
-    Ok(())
+        let mut time_log = TimeLog::for_date(&date)?;
+        removed_ids.iter().for_each(|id| time_log.remove_task(id));
+        time_log.write_to_file()?;
+    }
+
+    Ok((message, on_remove))
+}
+
+/// Moves the task at the index to another and saves the day. Gives the message to record the
+/// change with.
+pub fn move_task(task_list: &mut TaskList, from: usize, to: usize) -> Result<String, TuduError> {
+    task_list.move_task(from, to)?;
+
+    task_list.write_to_file()?;
+
+    Ok(format!(
+        "move {} #{from} → #{to}",
+        task_list.date().to_iso()
+    ))
 }
 
 fn execute_set(config: SetCommand) -> Result<(), TuduError> {
@@ -58,15 +351,33 @@ fn execute_set(config: SetCommand) -> Result<(), TuduError> {
         None => TuduDate::today(),
     };
 
+    let lock = TasksLock::acquire()?;
+
     let mut task_list = TaskList::for_date(&date)?;
 
-    task_list.set_task_state(config.index, config.state)?;
+    if config.state == TaskState::Started {
+        warn_if_blocked(&task_list, config.index)?;
+    }
+
+    let message = format!(
+        "set {} #{} → {}",
+        date.to_iso(),
+        config.index,
+        config.state.name()
+    );
+
+    let hooks = set_task_state(&mut task_list, config.index, config.state)?;
+
+    derive_parent_states_if_enabled(&mut task_list);
 
     task_list.write_to_file()?;
 
-    print_tasks(&task_list);
+    record_change(&message)?;
 
-    Ok(())
+    drop(lock);
+    hooks.iter().for_each(Hook::run_after_change);
+
+    print_tasks(&task_list)
 }
 
 fn execute_edit(config: EditCommand) -> Result<(), TuduError> {
@@ -75,49 +386,1005 @@ fn execute_edit(config: EditCommand) -> Result<(), TuduError> {
         None => TuduDate::today(),
     };
 
+    let _lock = TasksLock::acquire()?;
+
     let mut task_list = TaskList::for_date(&date)?;
 
     task_list.edit_task(config.index, config.task)?;
 
+    // An edit without an estimate keeps the one the task already had
+    if config.estimate.is_some() {
+        task_list.set_task_estimate(config.index, config.estimate)?;
+    }
+
     task_list.write_to_file()?;
 
-    print_tasks(&task_list);
+    record_change(&format!("edit {} #{}", date.to_iso(), config.index))?;
 
-    Ok(())
+    print_tasks(&task_list)
 }
 
 fn execute_view(config: ViewCommand) -> Result<(), TuduError> {
     let task_list = TaskList::for_date(&config.date)?;
 
-    print_tasks(&task_list);
+    // Only recent days are checked, and a day that cannot be read is left to `tudu overdue` to
+    // report rather than stopping the view
+    let today = TuduDate::today();
+    let recent = recent_dates(list_file_dates("")?, &today, overdue_days()?);
+    let earlier_task_lists: Vec<TaskList> = recent
+        .iter()
+        .filter_map(|date| TaskList::for_date(date).ok())
+        .collect();
+
+    if let Some(warning) = render_overdue_warning(find_overdue(&earlier_task_lists, &today).len()) {
+        println!("{warning}\n");
+    }
+
+    match config.all_lists {
+        true => print_all_lists(&config.date)?,
+        false => print_tasks(&task_list)?,
+    }
+
+    if config.journal {
+        let journal = Journal::for_date(&config.date)?;
+
+        println!("Journal\n");
+        print!("{}", journal.get_formatted_entries());
+    }
+
+    Ok(())
+}
+
+fn execute_export(config: ExportCommand) -> Result<(), TuduError> {
+    let dates = config.range.dates()?;
+
+    let task_lists = dates
+        .iter()
+        .map(TaskList::for_date)
+        .collect::<Result<Vec<TaskList>, TuduError>>()?;
+
+    let exported = match config.format {
+        Format::TodoTxt => todotxt::export_tasks(&task_lists),
+        Format::Ics => {
+            let journals = dates
+                .iter()
+                .map(Journal::for_date)
+                .collect::<Result<Vec<Journal>, TuduError>>()?;
+
+            ical::export_tasks(&task_lists, &journals)
+        }
+        Format::Csv => csv::export_tasks(&task_lists),
+    };
+
+    print!("{exported}");
+
+    Ok(())
+}
+
+fn execute_import(config: ImportCommand) -> Result<(), TuduError> {
+    let contents = match fs::read_to_string(&config.path) {
+        Ok(contents) => contents,
+        Err(_) => return Err(TuduError::FailedToReadFile),
+    };
+
+    let imported = match config.format {
+        Format::TodoTxt => todotxt::import_tasks(&contents)?,
+        Format::Ics => ical::import_tasks(&contents)?,
+        Format::Csv => csv::import_tasks(&contents)?,
+    };
+
+    let imported_count = imported.len();
+
+    let mut tasks_by_date: BTreeMap<TuduDate, Vec<Task>> = BTreeMap::new();
+    for (date, task) in imported {
+        tasks_by_date.entry(date).or_default().push(task);
+    }
+
+    let _lock = TasksLock::acquire()?;
+
+    for (date, tasks) in tasks_by_date.iter() {
+        let mut task_list = TaskList::for_date(date)?;
+
+        tasks
+            .iter()
+            .try_for_each(|task| task_list.add_task(task.clone()))?;
+
+        task_list.write_to_file()?;
+    }
+
+    record_change(&format!(
+        "import {imported_count} tasks from {}",
+        config.path.display()
+    ))?;
+
+    println!(
+        "Imported {imported_count} tasks across {} days",
+        tasks_by_date.len()
+    );
+
+    Ok(())
+}
+
+fn execute_report(config: ReportCommand) -> Result<(), TuduError> {
+    let dates = config.range.dates()?;
+
+    let task_lists = dates
+        .iter()
+        .map(TaskList::for_date)
+        .collect::<Result<Vec<TaskList>, TuduError>>()?;
+
+    print!("{}", render_report(&task_lists, &config.format));
 
     Ok(())
 }
 
-fn execute_help() {
-    println!("Commands:");
-    println!("`tudu` - see the tasks for today");
-    println!("`tudu view [date]` - see tasks on given date");
-    println!("`tudu add [task] *[date]` - add specified task on optional date");
-    println!("`tudu set [index] [state] *[date]` - set specified task to provided state on optional date");
-    println!("`tudu complete [index] *[date]` - mark specified task as complete on optional date");
-    println!("`tudu edit [index] [task] *[date]` - edit specified task to new task description on optional date");
-    println!("`tudu remove [index] *[date]` - remove specified task on optional date");
-    println!("");
-    println!("Dates:");
-    println!("Dates can be written in the form 10-6-2023, 10-6 which uses the current year, or with relative date commands `yesterday/today/tomorrow`.");
-    println!("If a date is optional in a command and was not specified the command will use the current date");
-    println!("");
-    println!("States:");
-    println!("◯ - [N]ot started");
-    println!("◐ - [S]tarted");
-    println!("● - [C]ompleted");
-    println!("► - Carry [F]orward");
-    println!("x - [X] Not doing");
+fn execute_standup(config: StandupCommand) -> Result<(), TuduError> {
+    let today = TuduDate::today();
+
+    let previous_dates = previous_dates(&today, config.since.as_ref())?;
+
+    let previous_task_lists = previous_dates
+        .iter()
+        .map(TaskList::for_date)
+        .collect::<Result<Vec<TaskList>, TuduError>>()?;
+
+    let today_task_list = TaskList::for_date(&today)?;
+    let prerequisites = Prerequisites::for_tasks(today_task_list.tasks())?;
+
+    print!(
+        "{}",
+        render_standup(
+            &previous_task_lists,
+            &today_task_list,
+            &prerequisites,
+            &config.format
+        )
+    );
+
+    Ok(())
+}
+
+fn execute_start(config: StartCommand) -> Result<(), TuduError> {
+    let date = match config.date {
+        Some(date) => date,
+        None => TuduDate::today(),
+    };
+
+    let lock = TasksLock::acquire()?;
+
+    let mut task_list = TaskList::for_date(&date)?;
+
+    warn_if_blocked(&task_list, config.index.into())?;
+
+    let hooks = set_task_state(&mut task_list, config.index.into(), TaskState::Started)?;
+
+    // Time is kept by task id, so it stays with the task wherever it is moved
+    let id = task_list.ensure_task_id(config.index.into())?;
+
+    let mut time_log = TimeLog::for_date(&date)?;
+
+    time_log.start_timer(&id, now())?;
+
+    task_list.write_to_file()?;
+    time_log.write_to_file()?;
+
+    record_change(&format!("start {} #{}", date.to_iso(), config.index))?;
+
+    drop(lock);
+    hooks.iter().for_each(Hook::run_after_change);
+
+    print_tasks(&task_list)
+}
+
+fn execute_stop(config: StopCommand) -> Result<(), TuduError> {
+    let date = match config.date {
+        Some(date) => date,
+        None => TuduDate::today(),
+    };
+
+    let _lock = TasksLock::acquire()?;
+
+    let task_list = TaskList::for_date(&date)?;
+
+    let id = match config.index {
+        Some(index) => Some(
+            task_list
+                .get_task(index.into())
+                .ok_or(TuduError::InvalidIndex)?
+                .id
+                .clone()
+                .unwrap_or_default(),
+        ),
+        None => None,
+    };
+
+    let mut time_log = TimeLog::for_date(&date)?;
+
+    time_log.stop_timers(id.as_deref(), now())?;
+
+    time_log.write_to_file()?;
+
+    let message = match config.index {
+        Some(index) => format!("stop {} #{index}", date.to_iso()),
+        None => format!("stop {} timers", date.to_iso()),
+    };
+
+    record_change(&message)?;
+
+    print_tasks(&task_list)
+}
+
+fn execute_timesheet(config: TimesheetCommand) -> Result<(), TuduError> {
+    let dates = config.range.dates()?;
+
+    let days = dates
+        .iter()
+        .map(|date| Ok((TaskList::for_date(date)?, TimeLog::for_date(date)?)))
+        .collect::<Result<Vec<(TaskList, TimeLog)>, TuduError>>()?;
+
+    print!("{}", render_timesheet(&days, now()));
+
+    Ok(())
+}
+
+fn execute_depend(config: DependCommand) -> Result<(), TuduError> {
+    let date = match config.date {
+        Some(date) => date,
+        None => TuduDate::today(),
+    };
+    let prerequisite_date = config.prerequisite_date.unwrap_or_else(|| date.clone());
+    let is_same_day = prerequisite_date == date;
+
+    if is_same_day && config.prerequisite == config.index {
+        return Err(TuduError::InvalidArguments(String::from(
+            "a task cannot depend on itself",
+        )));
+    }
+
+    let _lock = TasksLock::acquire()?;
+
+    let mut task_list = TaskList::for_date(&date)?;
+
+    if task_list.get_task(config.index).is_none() {
+        return Err(TuduError::InvalidIndex);
+    }
+
+    if config.remove {
+        let prerequisite_list = TaskList::for_date(&prerequisite_date)?;
+
+        let prerequisite_id = prerequisite_list
+            .get_task(config.prerequisite)
+            .ok_or(TuduError::InvalidIndex)?
+            .id
+            .clone();
+
+        let is_removed = match prerequisite_id {
+            Some(id) => {
+                let dependency = Dependency {
+                    date: prerequisite_date.clone(),
+                    id,
+                };
+                task_list.remove_dependency(config.index, &dependency)?
+            }
+            None => false,
+        };
+
+        if !is_removed {
+            return Err(TuduError::InvalidArguments(format!(
+                "task {} does not depend on task {}",
+                config.index, config.prerequisite
+            )));
+        }
+    } else {
+        let prerequisite = match is_same_day {
+            true => task_list.get_task(config.prerequisite).cloned(),
+            false => TaskList::for_date(&prerequisite_date)?
+                .get_task(config.prerequisite)
+                .cloned(),
+        }
+        .ok_or(TuduError::InvalidIndex)?;
+
+        // Nothing can be waiting on a task that has no id yet
+        if let Some(id) = task_list
+            .get_task(config.index)
+            .and_then(|task| task.id.clone())
+        {
+            let task = Dependency {
+                date: date.clone(),
+                id,
+            };
+
+            let is_cycle = waits_on(&prerequisite, &task, |tasks_date| {
+                match *tasks_date == date {
+                    true => Ok(task_list.tasks().to_vec()),
+                    false => Ok(TaskList::for_date(tasks_date)?.tasks().to_vec()),
+                }
+            })?;
+
+            if is_cycle {
+                return Err(TuduError::InvalidArguments(format!(
+                    "task {} already waits on task {}, so they would both wait forever",
+                    config.prerequisite, config.index
+                )));
+            }
+        }
+
+        // The prerequisite is given an id the first time something depends on it
+        let prerequisite_id = if is_same_day {
+            task_list.ensure_task_id(config.prerequisite)?
+        } else {
+            let mut prerequisite_list = TaskList::for_date(&prerequisite_date)?;
+            let id = prerequisite_list.ensure_task_id(config.prerequisite)?;
+            prerequisite_list.write_to_file()?;
+            id
+        };
+
+        let dependency = Dependency {
+            date: prerequisite_date.clone(),
+            id: prerequisite_id,
+        };
+
+        task_list.add_dependency(config.index, dependency)?;
+    }
+
+    task_list.write_to_file()?;
+
+    let change = match config.remove {
+        true => "no longer after",
+        false => "after",
+    };
+
+    record_change(&format!(
+        "depend {} #{} {change} {} #{}",
+        date.to_iso(),
+        config.index,
+        prerequisite_date.to_iso(),
+        config.prerequisite
+    ))?;
+
+    print_tasks(&task_list)
+}
+
+fn execute_graph(config: GraphCommand) -> Result<(), TuduError> {
+    let dates = config.range.dates()?;
+
+    let task_lists = dates
+        .iter()
+        .map(TaskList::for_date)
+        .collect::<Result<Vec<TaskList>, TuduError>>()?;
+
+    let prerequisites =
+        Prerequisites::for_tasks(task_lists.iter().flat_map(|task_list| task_list.tasks()))?;
+
+    print!("{}", render_graph(&task_lists, &prerequisites));
+
+    Ok(())
+}
+
+fn execute_note(config: NoteCommand) -> Result<(), TuduError> {
+    let date = match config.date {
+        Some(date) => date,
+        None => TuduDate::today(),
+    };
+
+    // The editor can stay open for a while, so the lock is only held while files are read and
+    // written rather than while the note is being edited
+    let (id, note) = {
+        let _lock = TasksLock::acquire()?;
+
+        let mut task_list = TaskList::for_date(&date)?;
+        let notes = Notes::for_date(&date)?;
+
+        // The task is given an id the first time it has notes so the notes can be found again
+        let id = task_list.ensure_task_id(config.index)?;
+
+        task_list.write_to_file()?;
+
+        let note = String::from(notes.get(&id).unwrap_or_default());
+
+        (id, note)
+    };
+
+    let note = edit_in_editor(&id, &note)?;
+
+    let _lock = TasksLock::acquire()?;
+
+    let task_list = TaskList::for_date(&date)?;
+    let mut notes = Notes::for_date(&date)?;
+
+    notes.set(&id, &note);
+
+    notes.write_to_file()?;
+
+    record_change(&format!("note {} #{}", date.to_iso(), config.index))?;
+
+    print_tasks(&task_list)
+}
+
+fn execute_show(config: ShowCommand) -> Result<(), TuduError> {
+    let date = match config.date {
+        Some(date) => date,
+        None => TuduDate::today(),
+    };
+
+    let task_list = TaskList::for_date(&date)?;
+    let notes = Notes::for_date(&date)?;
+
+    let task = task_list
+        .get_task(config.index)
+        .ok_or(TuduError::InvalidIndex)?;
+
+    println!(
+        "{}    {} - {}\n",
+        config.index,
+        task.state.icon(),
+        task.task
+    );
+
+    match task.id.as_deref().and_then(|id| notes.get(id)) {
+        Some(note) => println!("{note}"),
+        None => println!("This task has no notes"),
+    }
+
+    Ok(())
 }
 
-fn print_tasks(task_list: &TaskList) {
-    let formatted_tasks = task_list.get_formatted_tasks();
+fn execute_journal(config: JournalCommand) -> Result<(), TuduError> {
+    let date = match config.date {
+        Some(date) => date,
+        None => TuduDate::today(),
+    };
+
+    let text = match config.message {
+        Some(message) => message,
+        None => read_journal_entry()?,
+    };
+
+    let _lock = TasksLock::acquire()?;
+
+    let mut journal = Journal::for_date(&date)?;
+
+    journal.add_entry(&text, now())?;
+
+    journal.write_to_file()?;
+
+    record_change(&format!("journal {}", date.to_iso()))?;
+
+    print!("{}", journal.get_formatted_entries());
+
+    Ok(())
+}
+
+/// Reads a journal entry from standard input, prompting for it when typed at a terminal
+fn read_journal_entry() -> Result<String, TuduError> {
+    let mut stdin = io::stdin();
+
+    if stdin.is_terminal() {
+        println!("Write the journal entry, then press Ctrl-D to save it");
+    }
+
+    let mut text = String::new();
+
+    match stdin.read_to_string(&mut text) {
+        Ok(_) => Ok(text),
+        Err(_) => Err(TuduError::FailedToReadFile),
+    }
+}
+
+fn execute_search(config: SearchCommand) -> Result<(), TuduError> {
+    // Without a range every date with tasks or journal entries is searched
+    let dates = match config.range {
+        Some(range) => range.dates()?,
+        None => {
+            let mut dates = list_file_dates("")?;
+            dates.extend(list_file_dates(JOURNAL_DIRECTORY)?);
+            dates.sort();
+            dates.dedup();
+            dates
+        }
+    };
+
+    let mut results = String::new();
+
+    for date in dates.iter() {
+        let task_list = TaskList::for_date(date)?;
+        let notes = Notes::for_date(date)?;
+        let journal = Journal::for_date(date)?;
+
+        results.push_str(&search_day(&task_list, &notes, &journal, &config.query));
+    }
+
+    if results.is_empty() {
+        println!(
+            "No tasks, notes or journal entries match `{}`",
+            config.query
+        );
+    } else {
+        print!("{results}");
+    }
+
+    Ok(())
+}
+
+fn execute_due(config: DueCommand) -> Result<(), TuduError> {
+    let date = match config.date {
+        Some(date) => date,
+        None => TuduDate::today(),
+    };
+
+    let _lock = TasksLock::acquire()?;
+
+    let mut task_list = TaskList::for_date(&date)?;
+
+    let message = match &config.due {
+        Some(due) => format!("due {} #{} → {}", date.to_iso(), config.index, due.to_iso()),
+        None => format!("due {} #{} cleared", date.to_iso(), config.index),
+    };
+
+    task_list.set_task_due(config.index, config.due)?;
+
+    task_list.write_to_file()?;
+
+    record_change(&message)?;
+
+    print_tasks(&task_list)
+}
+
+fn execute_at(config: AtCommand) -> Result<(), TuduError> {
+    let date = match config.date {
+        Some(date) => date,
+        None => TuduDate::today(),
+    };
+
+    let _lock = TasksLock::acquire()?;
+
+    let mut task_list = TaskList::for_date(&date)?;
+
+    let message = match config.at {
+        Some(at) => format!(
+            "at {} #{} → {}",
+            date.to_iso(),
+            config.index,
+            format_time(at)
+        ),
+        None => format!("at {} #{} cleared", date.to_iso(), config.index),
+    };
+
+    // Reminders are kept by task id
+    if config.at.is_some() {
+        task_list.ensure_task_id(config.index)?;
+    }
+
+    task_list.set_task_at(config.index, config.at)?;
+
+    task_list.write_to_file()?;
+
+    record_change(&message)?;
+
+    print_tasks(&task_list)
+}
+
+fn execute_snooze(config: SnoozeCommand) -> Result<(), TuduError> {
+    let date = match config.date {
+        Some(date) => date,
+        None => TuduDate::today(),
+    };
+
+    let _lock = TasksLock::acquire()?;
+
+    let task_list = TaskList::for_date(&date)?;
+
+    let task = match task_list.get_task(config.index) {
+        Some(task) if task.at.is_some() => task,
+        Some(_) => {
+            return Err(TuduError::InvalidArguments(format!(
+                "task {} has no reminder to snooze, give it a time with tudu at",
+                config.index
+            )))
+        }
+        None => return Err(TuduError::InvalidIndex),
+    };
+
+    let until = now() + config.duration;
+    let key = reminder_key(task);
+
+    let mut reminders = Reminders::for_date(&date)?;
+    reminders.snooze(&key, until);
+    reminders.write_to_file()?;
+
+    // The watcher only looks at the day it is, so a snooze ending on a later day is kept there
+    let until_date = TuduDate::from_date_time(until);
+
+    if until_date > date {
+        let mut later_reminders = Reminders::for_date(&until_date)?;
+        later_reminders.snooze_from(&key, &date, until);
+        later_reminders.write_to_file()?;
+    }
+
+    let until = format_time(until.time());
+
+    record_change(&format!(
+        "snooze {} #{} until {until}",
+        date.to_iso(),
+        config.index
+    ))?;
+
+    println!("Snoozed {} - {} until {until}", config.index, task.task);
+
+    Ok(())
+}
+
+fn execute_overdue() -> Result<(), TuduError> {
+    let dates = list_file_dates("")?;
+    let task_lists = load_task_lists(&dates)?;
+
+    print!("{}", render_overdue(&task_lists, &TuduDate::today()));
+
+    Ok(())
+}
+
+fn execute_priority(config: PriorityCommand) -> Result<(), TuduError> {
+    let date = match config.date {
+        Some(date) => date,
+        None => TuduDate::today(),
+    };
+
+    let _lock = TasksLock::acquire()?;
+
+    let mut task_list = TaskList::for_date(&date)?;
+
+    task_list.set_task_priority(config.index, config.priority)?;
+
+    task_list.write_to_file()?;
+
+    let message = match config.priority {
+        Some(priority) => format!("priority {} #{} → {priority}", date.to_iso(), config.index),
+        None => format!("priority {} #{} cleared", date.to_iso(), config.index),
+    };
+
+    record_change(&message)?;
+
+    print_tasks(&task_list)
+}
+
+fn execute_next(config: NextCommand) -> Result<(), TuduError> {
+    // Tasks planned for later days are left for then
+    let today = TuduDate::today();
+    let dates: Vec<TuduDate> = list_file_dates("")?
+        .into_iter()
+        .filter(|date| *date <= today)
+        .collect();
+    let task_lists = load_task_lists(&dates)?;
+    let prerequisites =
+        Prerequisites::for_tasks(task_lists.iter().flat_map(|task_list| task_list.tasks()))?;
+
+    let mut candidates = rank_tasks(&task_lists, &prerequisites);
+    candidates.truncate(config.count);
+
+    print!("{}", render_candidates(&candidates, &today, config.why));
+
+    if config.start {
+        if let Some(top) = candidates.first() {
+            let lock = TasksLock::acquire()?;
+            let mut task_list = TaskList::for_date(top.date)?;
+
+            let hooks = set_task_state(&mut task_list, top.index, TaskState::Started)?;
+
+            derive_parent_states_if_enabled(&mut task_list);
+
+            task_list.write_to_file()?;
+
+            record_change(&format!(
+                "set {} #{} → {}",
+                top.date.to_iso(),
+                top.index,
+                TaskState::Started.name()
+            ))?;
+
+            drop(lock);
+            hooks.iter().for_each(Hook::run_after_change);
+
+            println!("\nStarted task {} on {}\n", top.index, top.date.to_iso());
+
+            print_tasks(&task_list)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn load_task_lists(dates: &[TuduDate]) -> Result<Vec<TaskList<'_>>, TuduError> {
+    dates.iter().map(TaskList::for_date).collect()
+}
+
+fn execute_log(config: LogCommand) -> Result<(), TuduError> {
+    let date = match config.date {
+        Some(date) => date,
+        None => TuduDate::today(),
+    };
+
+    print!("{}", history::render_log(&date)?);
+
+    Ok(())
+}
+
+fn execute_sync() -> Result<(), TuduError> {
+    let _lock = TasksLock::acquire()?;
+
+    print!("{}", history::sync()?);
+
+    Ok(())
+}
+
+/// Merges the day files without taking the lock, as git runs it while `sync` holds the lock
+fn execute_merge(config: MergeCommand) -> Result<(), TuduError> {
+    // Git gives an empty base when the day was added on both sides
+    let base = match parse_task_file(&config.base) {
+        Err(TuduError::NoTaskFile) => Vec::new(),
+        base => base?,
+    };
+    let ours = parse_task_file(&config.ours)?;
+    let theirs = parse_task_file(&config.theirs)?;
+
+    let merged = merge_tasks(&base, &ours, &theirs);
+
+    let output = config.output.unwrap_or(config.ours);
+
+    write_tasks_to_file(&output, &merged.tasks)?;
+
+    for conflict in merged.conflicts {
+        println!("{conflict}");
+    }
+
+    Ok(())
+}
+
+fn execute_serve(config: ServeCommand) -> Result<(), TuduError> {
+    let token = config.token.or_else(|| env::var("TUDU_TOKEN").ok());
+
+    server::serve(&config.host, config.port, token.as_deref())
+}
+
+fn execute_completions(config: CompletionsCommand) {
+    let script = generate_script(&config.shell);
+
+    print!("{script}");
+}
+
+fn execute_task_candidates(date: Option<TuduDate>) {
+    let Some(date) = date else {
+        return;
+    };
+
+    // Errors are swallowed so that the shell is never shown an error message as a completion
+    if let Ok(task_list) = TaskList::for_date(&date) {
+        print!("{}", get_task_candidates(task_list.tasks()));
+    }
+}
+
+fn execute_lists() -> Result<(), TuduError> {
+    print!("{}", render_lists(&list_names()?, &current_list()?));
+
+    Ok(())
+}
+
+/// Moves a task to the same date in another list, along with its notes and the time tracked on it
+fn execute_move(config: MoveCommand) -> Result<(), TuduError> {
+    let date = match config.date {
+        Some(date) => date,
+        None => TuduDate::today(),
+    };
+
+    let from = current_list()?;
+
+    if config.to == from {
+        return Err(TuduError::InvalidArguments(format!(
+            "the task is already in the `{from}` list"
+        )));
+    }
+
+    if !list_names()?.contains(&config.to) {
+        return Err(TuduError::InvalidArguments(format!(
+            "there is no `{}` list, add a task to it with `tudu -l {} add` to start it",
+            config.to, config.to
+        )));
+    }
+
+    let lock = TasksLock::acquire()?;
+
+    let mut task_list = TaskList::for_date(&date)?;
+
+    let index = TaskIndex::from(config.index);
+    let task = task_list
+        .get_task(index)
+        .cloned()
+        .ok_or(TuduError::InvalidIndex)?;
+    let note = match &task.id {
+        Some(id) => Notes::for_date(&date)?.get(id).map(String::from),
+        None => None,
+    };
+    let intervals = match &task.id {
+        Some(id) => TimeLog::for_date(&date)?.intervals_for(id),
+        None => Vec::new(),
+    };
+
+    // The task is added before it is removed, so it is never lost if either goes wrong
+    let post_add = with_list(&config.to, || {
+        let mut moved_to = TaskList::for_date(&date)?;
+        let post_add = add_task(&mut moved_to, task.clone(), None)?;
+
+        derive_parent_states_if_enabled(&mut moved_to);
+
+        moved_to.write_to_file()?;
+
+        if let (Some(id), Some(note)) = (&task.id, &note) {
+            let mut notes = Notes::for_date(&date)?;
+            notes.set(id, note);
+            notes.write_to_file()?;
+        }
+
+        if !intervals.is_empty() {
+            let mut time_log = TimeLog::for_date(&date)?;
+            time_log.add_intervals(intervals);
+            time_log.write_to_file()?;
+        }
+
+        Ok(post_add)
+    })?;
+
+    let (_, on_remove) = remove_task(&mut task_list, index)?;
+
+    record_change(&format!(
+        "move {} #{} {} → {}",
+        date.to_iso(),
+        config.index,
+        task.task,
+        config.to
+    ))?;
+
+    drop(lock);
+    with_list(&config.to, || {
+        post_add.run_after_change();
+        Ok(())
+    })?;
+    on_remove.run_after_change();
+
+    println!("Moved {} - {} to {}\n", config.index, task.task, config.to);
+
+    print_tasks(&task_list)
+}
+
+/// Prints the tasks of every list for the date, each labelled with its list
+fn print_all_lists(date: &TuduDate) -> Result<(), TuduError> {
+    let mut formatted_lists = Vec::new();
+
+    for list in list_names()? {
+        let formatted_tasks = with_list(&list, || {
+            let task_list = TaskList::for_date(date)?;
+
+            match task_list.tasks().is_empty() {
+                true => Ok(None),
+                false => format_tasks(&task_list, Some(&list)).map(Some),
+            }
+        })?;
+
+        formatted_lists.extend(formatted_tasks);
+    }
+
+    match formatted_lists.is_empty() {
+        true => println!("There are no tasks for this date in any list"),
+        false => println!("{}", formatted_lists.join("\n")),
+    }
+
+    Ok(())
+}
+
+fn execute_alias() -> Result<(), TuduError> {
+    let config = Config::load()?;
+
+    if config.aliases.is_empty() {
+        println!(
+            "There are no aliases, add them to {} like `alias d = complete`",
+            config_path()?.display()
+        );
+        return Ok(());
+    }
+
+    print!("{}", render_aliases(&config.aliases));
+
+    Ok(())
+}
+
+fn execute_help(usage: String) {
+    print!("{usage}");
+}
+
+/// Warns about any unfinished tasks that the task is waiting on, as it is about to be started
+fn warn_if_blocked(task_list: &TaskList, index: TaskIndex) -> Result<(), TuduError> {
+    let Some(task) = task_list.get_task(index) else {
+        return Ok(());
+    };
+
+    let prerequisites = Prerequisites::for_tasks([task])?;
+
+    for (dependency, prerequisite_index, prerequisite) in prerequisites.unfinished(task) {
+        println!(
+            "Warning: task {index} is blocked by task {prerequisite_index} on {}, {} - {}",
+            dependency.date.to_iso(),
+            prerequisite.state.name().to_lowercase(),
+            prerequisite.task
+        );
+    }
+
+    Ok(())
+}
+
+/// Parent tasks take their state from their subtasks when the TUDU_DERIVE_STATE env variable is set
+/// to `true`
+pub fn derive_parent_states_if_enabled(task_list: &mut TaskList) {
+    if env::var("TUDU_DERIVE_STATE").is_ok_and(|derive| derive == "true") {
+        task_list.derive_parent_states();
+    }
+}
+
+/// Prints the tasks along with their priorities, markers for those with notes, their estimates, due
+/// dates and the time spent on any that have been timed, followed by the planned effort for the day
+pub fn print_tasks(task_list: &TaskList) -> Result<(), TuduError> {
+    let formatted_tasks = format_tasks(task_list, None)?;
+
+    match render_plan(task_list, daily_capacity()?) {
+        Some(plan) => println!("{formatted_tasks}\n{plan}\n"),
+        None => println!("{formatted_tasks}"),
+    }
+
+    Ok(())
+}
+
+/// Formats the tasks with everything known about them, labelled with a list when given one
+fn format_tasks(task_list: &TaskList, list: Option<&str>) -> Result<String, TuduError> {
+    let time_log = TimeLog::for_date(task_list.date())?;
+    let notes = Notes::for_date(task_list.date())?;
+    let prerequisites = Prerequisites::for_tasks(task_list.tasks())?;
+    let now = now();
+    let today = TuduDate::today();
+
+    let is_blocked = |task: &Task| prerequisites.is_blocked(task);
+
+    let formatted_tasks = task_list.get_formatted_tasks(is_blocked, |index, task| {
+        let time_spent = match &task.id {
+            Some(id) => time_log.time_spent(id, now),
+            None => Duration::zero(),
+        };
+        let is_running = task.id.as_deref().is_some_and(|id| time_log.is_running(id));
+
+        let timer = match (index.subtask, is_running, time_spent.is_zero()) {
+            (Some(_), _, _) => None,
+            (None, true, _) => Some(format!("⏱ {}", format_duration(time_spent))),
+            (None, false, false) => Some(format_duration(time_spent)),
+            (None, false, true) => None,
+        };
+
+        let note = task
+            .id
+            .as_deref()
+            .and_then(|id| notes.get(id))
+            .map(|_| String::from(NOTE_MARKER));
+
+        let annotations: Vec<String> = [
+            list.map(|list| format!("[{list}]")),
+            task.priority.map(format_priority),
+            note,
+            task.estimate.map(format_estimate),
+            format_due(task, &today),
+            format_reminder(task),
+            timer,
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        (!annotations.is_empty()).then(|| annotations.join("    "))
+    });
 
-    println!("{formatted_tasks}");
+    Ok(formatted_tasks)
 }
@@ -0,0 +1,368 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Mutex;
+
+use crate::date::TuduDate;
+use crate::error::TuduError;
+use crate::journal::JOURNAL_DIRECTORY;
+use crate::lists::{current_list, list_directory};
+use crate::storage::{tasks_root, write_file};
+
+const REMOTE: &str = "origin";
+const IGNORED_FILES: &str = ".lock\n.*.tmp\n.daemon.sock\n.last-day\n";
+const MERGE_ATTRIBUTES: &str = "/*.txt merge=tudu\n/lists/*/*.txt merge=tudu\n";
+
+/// Whether git has an author set up for each directory, so it is only asked once per command
+static HAS_AUTHOR: Mutex<BTreeMap<PathBuf, bool>> = Mutex::new(BTreeMap::new());
+
+/// Keeping the history of the tasks directory in git is turned on by setting the TUDU_GIT env
+/// variable to `true`
+pub fn is_enabled() -> bool {
+    env::var("TUDU_GIT").is_ok_and(|enabled| enabled == "true")
+}
+
+/// Commits every change in the tasks directory with the message, if keeping history is turned on
+pub fn record_change(message: &str) -> Result<(), TuduError> {
+    if !is_enabled() {
+        return Ok(());
+    }
+
+    commit_changes(&tasks_directory()?, message)
+}
+
+/// The commits that changed the tasks, notes, timers or journal of a date, newest first
+pub fn render_log(date: &TuduDate) -> Result<String, TuduError> {
+    require_enabled()?;
+
+    let directory = tasks_directory()?;
+
+    // Lists other than the main list keep their files in a folder of the repository
+    let list_directory = list_directory(&current_list()?)?;
+    let list_path = list_directory
+        .strip_prefix(&directory)
+        .unwrap_or(Path::new(""));
+
+    log_for_date(&directory, list_path, date)
+}
+
+/// Pulls the history from the remote and pushes the local history to it. The remote is set from
+/// the TUDU_GIT_REMOTE env variable when given, otherwise the `origin` remote of the repository is
+/// used.
+pub fn sync() -> Result<String, TuduError> {
+    require_enabled()?;
+
+    let remote_url = env::var("TUDU_GIT_REMOTE").ok();
+
+    sync_repository(&tasks_directory()?, remote_url.as_deref())
+}
+
+fn require_enabled() -> Result<(), TuduError> {
+    match is_enabled() {
+        true => Ok(()),
+        false => Err(TuduError::InvalidArguments(String::from(
+            "history is not kept, set the TUDU_GIT env variable to `true` to keep it in git",
+        ))),
+    }
+}
+
+fn tasks_directory() -> Result<PathBuf, TuduError> {
+    tasks_root()
+}
+
+fn commit_changes(directory: &Path, message: &str) -> Result<(), TuduError> {
+    ensure_repository(directory)?;
+
+    git(directory, &["add", "--all"])?;
+
+    // Commands that end up changing nothing do not leave empty commits behind
+    if git(directory, &["status", "--porcelain"])?
+        .trim()
+        .is_empty()
+    {
+        return Ok(());
+    }
+
+    git(directory, &["commit", "--quiet", "--message", message])?;
+
+    Ok(())
+}
+
+fn log_for_date(directory: &Path, list_path: &Path, date: &TuduDate) -> Result<String, TuduError> {
+    ensure_repository(directory)?;
+
+    let iso_date = date.to_iso();
+    let journal_path = format!("{JOURNAL_DIRECTORY}/{}", date.to_filename());
+    let paths: Vec<String> = [
+        date.to_filename(),
+        format!("{iso_date}.notes"),
+        format!("{iso_date}.time"),
+        format!("{iso_date}.reminders"),
+        journal_path,
+    ]
+    .iter()
+    .map(|path| list_path.join(path).to_string_lossy().to_string())
+    .collect();
+
+    // A repository without any commits yet has no history to show
+    if git(directory, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_err() {
+        return Ok(no_history(&iso_date));
+    }
+
+    let mut args = vec![
+        "log",
+        "--date=format:%Y-%m-%d %H:%M",
+        "--format=%h    %ad    %s",
+        "--",
+    ];
+    args.extend(paths.iter().map(String::as_str));
+
+    let log = git(directory, &args)?;
+
+    match log.is_empty() {
+        true => Ok(no_history(&iso_date)),
+        false => Ok(log),
+    }
+}
+
+fn no_history(iso_date: &str) -> String {
+    format!("There is no history for {iso_date}\n")
+}
+
+fn sync_repository(directory: &Path, remote_url: Option<&str>) -> Result<String, TuduError> {
+    ensure_repository(directory)?;
+    ensure_merge_driver(directory)?;
+
+    // Anything changed while history was turned off is committed before syncing
+    commit_changes(directory, "record changes before syncing")?;
+
+    let has_remote = git(directory, &["remote", "get-url", REMOTE]).is_ok();
+
+    match (remote_url, has_remote) {
+        (Some(url), true) => git(directory, &["remote", "set-url", REMOTE, url])?,
+        (Some(url), false) => git(directory, &["remote", "add", REMOTE, url])?,
+        (None, true) => String::new(),
+        (None, false) => {
+            return Err(TuduError::InvalidArguments(String::from(
+                "there is no remote to sync with, set the TUDU_GIT_REMOTE env variable to its url",
+            )))
+        }
+    };
+
+    let branch = git(directory, &["symbolic-ref", "--short", "HEAD"])?;
+    let branch = branch.trim();
+
+    let remote_has_branch = !git(directory, &["ls-remote", "--heads", REMOTE, branch])?
+        .trim()
+        .is_empty();
+
+    if remote_has_branch {
+        git(directory, &["fetch", "--quiet", REMOTE, branch])?;
+
+        let merged = git(
+            directory,
+            &[
+                "merge",
+                "--quiet",
+                "--no-edit",
+                "--allow-unrelated-histories",
+                "FETCH_HEAD",
+            ],
+        );
+
+        // A failed merge is undone so that the tasks are left as they were before syncing
+        if let Err(err) = merged {
+            let _ = git(directory, &["merge", "--abort"]);
+            return Err(err);
+        }
+    }
+
+    let has_commits = git(directory, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok();
+
+    if has_commits {
+        git(
+            directory,
+            &[
+                "push",
+                "--quiet",
+                REMOTE,
+                &format!("HEAD:refs/heads/{branch}"),
+            ],
+        )?;
+    }
+
+    Ok(format!("Synced the tasks with {REMOTE} on {branch}\n"))
+}
+
+/// Makes the tasks directory a git repository if it is not one already, ignoring the files tudu
+/// only uses while it is running
+fn ensure_repository(directory: &Path) -> Result<(), TuduError> {
+    if directory.join(".git").exists() {
+        return Ok(());
+    }
+
+    git(directory, &["init", "--quiet"])?;
+
+    write_file(&directory.join(".gitignore"), IGNORED_FILES)
+}
+
+/// Has git merge day files task by task with `tudu merge` rather than line by line, so that the
+/// changes made to a day on two devices are both kept
+fn ensure_merge_driver(directory: &Path) -> Result<(), TuduError> {
+    git(
+        directory,
+        &["config", "merge.tudu.name", "tudu day file merge"],
+    )?;
+    git(
+        directory,
+        &["config", "merge.tudu.driver", "tudu merge %O %A %B"],
+    )?;
+
+    let attributes = directory.join(".gitattributes");
+
+    if !attributes.exists() {
+        write_file(&attributes, MERGE_ATTRIBUTES)?;
+    }
+
+    Ok(())
+}
+
+/// Runs git in the directory, giving back what it printed. The commits are made as tudu when no
+/// author is set up in git.
+fn git(directory: &Path, args: &[&str]) -> Result<String, TuduError> {
+    let mut command = process::Command::new("git");
+    command.arg("-C").arg(directory);
+
+    if !has_author(directory) {
+        command.args(["-c", "user.name=tudu", "-c", "user.email=tudu@localhost"]);
+    }
+
+    let output = command
+        .args(args)
+        .output()
+        .map_err(|err| TuduError::GitFailed(err.to_string()))?;
+
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(TuduError::GitFailed(error));
+    }
+
+    // A new repository no longer reads the config of any repository it sits inside
+    if args.first() == Some(&"init") {
+        forget_author(directory);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Whether commits in the directory would have an author without tudu giving one
+fn has_author(directory: &Path) -> bool {
+    let mut has_author = HAS_AUTHOR
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    *has_author
+        .entry(directory.to_path_buf())
+        .or_insert_with(|| {
+            process::Command::new("git")
+                .arg("-C")
+                .arg(directory)
+                .args(["config", "user.email"])
+                .output()
+                .is_ok_and(|output| output.status.success())
+        })
+}
+
+fn forget_author(directory: &Path) {
+    HAS_AUTHOR
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .remove(directory);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::storage::test_directory;
+
+    #[test]
+    fn commit_changes_then_log_for_date_shows_history() {
+        let directory = test_directory("history");
+        let date = TuduDate::new(7, 6, 2023);
+
+        write_file(&directory.join("2023-06-07.txt"), "N,Write the docs\n").unwrap();
+        commit_changes(&directory, "add 2023-06-07 #1 Write the docs").unwrap();
+        write_file(&directory.join("2023-06-08.txt"), "N,Another day\n").unwrap();
+        commit_changes(&directory, "add 2023-06-08 #1 Another day").unwrap();
+        write_file(&directory.join("2023-06-07.txt"), "C,Write the docs\n").unwrap();
+        commit_changes(&directory, "set 2023-06-07 #1 → Completed").unwrap();
+        commit_changes(&directory, "nothing changed").unwrap();
+
+        let log = log_for_date(&directory, Path::new(""), &date).unwrap();
+        let subjects: Vec<&str> = log
+            .lines()
+            .map(|line| line.rsplit("    ").next().unwrap())
+            .collect();
+
+        let ignored = fs::read_to_string(directory.join(".gitignore")).unwrap();
+
+        // cleanup
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            subjects,
+            vec![
+                "set 2023-06-07 #1 → Completed",
+                "add 2023-06-07 #1 Write the docs"
+            ]
+        );
+        assert_eq!(ignored, IGNORED_FILES);
+    }
+
+    #[test]
+    fn sync_repository_shares_changes_through_remote() {
+        let remote = test_directory("sync-remote.git");
+        let laptop = test_directory("sync-laptop");
+        let desktop = test_directory("sync-desktop");
+        fs::create_dir_all(&remote).unwrap();
+        git(&remote, &["init", "--quiet", "--bare"]).unwrap();
+        let remote_url = fs::canonicalize(&remote).unwrap();
+        let remote_url = remote_url.to_str().unwrap();
+
+        write_file(&laptop.join("2023-06-07.txt"), "N,From the laptop\n").unwrap();
+        commit_changes(&laptop, "add 2023-06-07 #1 From the laptop").unwrap();
+        sync_repository(&laptop, Some(remote_url)).unwrap();
+
+        write_file(&desktop.join("2023-06-08.txt"), "N,From the desktop\n").unwrap();
+        commit_changes(&desktop, "add 2023-06-08 #1 From the desktop").unwrap();
+        sync_repository(&desktop, Some(remote_url)).unwrap();
+        sync_repository(&laptop, None).unwrap();
+
+        let laptop_has_desktop_task = laptop.join("2023-06-08.txt").exists();
+        let desktop_has_laptop_task = desktop.join("2023-06-07.txt").exists();
+
+        // cleanup
+        for directory in [&remote, &laptop, &desktop] {
+            fs::remove_dir_all(directory).unwrap();
+        }
+
+        assert!(laptop_has_desktop_task);
+        assert!(desktop_has_laptop_task);
+    }
+
+    #[test]
+    fn sync_repository_without_remote_gives_error() {
+        let directory = test_directory("sync-no-remote");
+        fs::create_dir_all(&directory).unwrap();
+
+        let result = sync_repository(&directory, None);
+
+        // cleanup
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert!(matches!(result, Err(TuduError::InvalidArguments(_))));
+    }
+}
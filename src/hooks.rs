@@ -0,0 +1,329 @@
+use std::env;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde_json::{json, Value};
+
+use crate::date::TuduDate;
+use crate::error::{user_error_message, TuduError};
+use crate::lists::current_list;
+use crate::model::{Task, TaskIndex, TaskList, TaskState};
+use crate::server::{task_list_to_json, task_to_json};
+use crate::storage::{
+    create_root_filepath, read_file_if_exists, write_file, TasksLock, LOCK_HELD_VARIABLE,
+};
+
+pub const HOOKS_DIRECTORY: &str = "hooks";
+
+/// Keeps the last day tudu was used on, so the day rollover hook runs once for each new day
+const LAST_DAY_FILENAME: &str = ".last-day";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HookEvent {
+    PreAdd,
+    PostAdd,
+    PreStateChange,
+    OnStateChange,
+    OnComplete,
+    PreRemove,
+    OnRemove,
+    OnDayRollover,
+}
+
+impl HookEvent {
+    /// The name of the executable in the hooks directory that is run for the event
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::PreAdd => "pre-add",
+            HookEvent::PostAdd => "post-add",
+            HookEvent::PreStateChange => "pre-state-change",
+            HookEvent::OnStateChange => "on-state-change",
+            HookEvent::OnComplete => "on-complete",
+            HookEvent::PreRemove => "pre-remove",
+            HookEvent::OnRemove => "on-remove",
+            HookEvent::OnDayRollover => "on-day-rollover",
+        }
+    }
+
+    /// Pre hooks run before the change is made and can stop it
+    fn is_pre(&self) -> bool {
+        matches!(
+            self,
+            HookEvent::PreAdd | HookEvent::PreStateChange | HookEvent::PreRemove
+        )
+    }
+}
+
+/// An event to run the user's hook for, along with the JSON the hook is given on stdin
+#[derive(Debug, PartialEq, Clone)]
+pub struct Hook {
+    event: HookEvent,
+    payload: Value,
+}
+
+impl Hook {
+    /// A hook given the task, e.g. `{"event": "post-add", "list": "main", "date": "2023-06-07",
+    /// "index": "2", "task": {...}}`
+    pub fn for_task(event: HookEvent, date: &TuduDate, index: TaskIndex, task: &Task) -> Hook {
+        Hook {
+            event,
+            payload: json!({
+                "event": event.name(),
+                "list": current_list().ok(),
+                "date": date.to_iso(),
+                "index": index.to_string(),
+                "task": task_to_json(index, task),
+            }),
+        }
+    }
+
+    /// A hook given the task along with the state it is changing from and to
+    pub fn for_state_change(
+        event: HookEvent,
+        date: &TuduDate,
+        index: TaskIndex,
+        task: &Task,
+        old_state: &TaskState,
+        new_state: &TaskState,
+    ) -> Hook {
+        let mut hook = Hook::for_task(event, date, index, task);
+        hook.payload["old_state"] = json!(old_state.code());
+        hook.payload["new_state"] = json!(new_state.code());
+        hook
+    }
+
+    /// Runs the user's executable for the event if there is one, giving an error when it exits
+    /// unsuccessfully
+    pub fn run(&self) -> Result<(), TuduError> {
+        let executable = hooks_directory()?.join(self.event.name());
+
+        match executable.is_file() {
+            true => self.run_executable(&executable),
+            false => Ok(()),
+        }
+    }
+
+    fn run_executable(&self, executable: &Path) -> Result<(), TuduError> {
+        let name = self.event.name();
+
+        let mut hook = Command::new(executable);
+
+        // Tudu run by the hook uses the same list
+        if let Ok(list) = current_list() {
+            hook.env("TUDU_LIST", list);
+        }
+
+        // Pre hooks run while the tasks are locked, so tudu run by them must not wait for the lock
+        if self.event.is_pre() {
+            hook.env(LOCK_HELD_VARIABLE, "1");
+        }
+
+        let mut child = hook
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| TuduError::HookFailed(format!("`{name}` could not be run, {err}")))?;
+
+        // A hook that exits without reading what it was given has not failed
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(self.payload.to_string().as_bytes());
+        }
+
+        let output = child
+            .wait_with_output()
+            .map_err(|err| TuduError::HookFailed(format!("`{name}` could not be run, {err}")))?;
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        if output.status.success() {
+            eprint!("{stderr}");
+            return Ok(());
+        }
+
+        let mut reason = format!("`{name}` exited with {}", output.status);
+
+        if self.event.is_pre() {
+            reason.push_str(" so nothing was changed");
+        }
+
+        if !stderr.trim().is_empty() {
+            reason.push_str(&format!(", {}", stderr.trim()));
+        }
+
+        Err(TuduError::HookFailed(reason))
+    }
+
+    /// Runs a hook for a change that has already been saved, so a failure is reported without
+    /// failing the command
+    pub fn run_after_change(&self) {
+        if let Err(err) = self.run() {
+            eprintln!("{}", user_error_message(err));
+        }
+    }
+}
+
+/// Hooks are the executables in the directory set in the TUDU_HOOKS env variable, falling back to
+/// the `hooks` folder of the tasks directory
+fn hooks_directory() -> Result<PathBuf, TuduError> {
+    match env::var("TUDU_HOOKS") {
+        Ok(directory) => Ok(PathBuf::from(directory)),
+        Err(_) => create_root_filepath(HOOKS_DIRECTORY),
+    }
+}
+
+/// Runs the day rollover hook the first time tudu is used on a new day, given the tasks of the
+/// last day it was used on. Nothing is kept track of until there is a hook to run.
+pub fn check_day_rollover(today: &TuduDate) -> Result<(), TuduError> {
+    if !hooks_directory()?
+        .join(HookEvent::OnDayRollover.name())
+        .is_file()
+    {
+        return Ok(());
+    }
+
+    let hook = {
+        let _lock = TasksLock::acquire()?;
+
+        let filepath = create_root_filepath(LAST_DAY_FILENAME)?;
+
+        let last_day = read_file_if_exists(&filepath)?
+            .and_then(|contents| TuduDate::from_iso(contents.trim()).ok());
+
+        if last_day.as_ref().is_some_and(|last_day| last_day >= today) {
+            return Ok(());
+        }
+
+        write_file(&filepath, &format!("{}\n", today.to_iso()))?;
+
+        // The first day tudu is used with the hook has no day before it to roll over from
+        match last_day {
+            Some(last_day) => Some(rollover_hook(today, &TaskList::for_date(&last_day)?)),
+            None => None,
+        }
+    };
+
+    if let Some(hook) = hook {
+        hook.run_after_change();
+    }
+
+    Ok(())
+}
+
+/// The day rollover hook, e.g. `{"event": "on-day-rollover", "date": "2023-06-08",
+/// "previous_date": "2023-06-07", "tasks": [...]}`
+fn rollover_hook(today: &TuduDate, previous: &TaskList) -> Hook {
+    let event = HookEvent::OnDayRollover;
+    let previous = task_list_to_json(previous);
+
+    Hook {
+        event,
+        payload: json!({
+            "event": event.name(),
+            "list": current_list().ok(),
+            "date": today.to_iso(),
+            "previous_date": previous["date"],
+            "tasks": previous["tasks"],
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+    use crate::storage::test_directory;
+
+    fn install_hook(directory: &Path, event: HookEvent, script: &str) -> PathBuf {
+        let executable = directory.join(event.name());
+
+        fs::create_dir_all(directory).unwrap();
+        fs::write(&executable, format!("#!/bin/sh\n{script}\n")).unwrap();
+        fs::set_permissions(&executable, fs::Permissions::from_mode(0o755)).unwrap();
+
+        executable
+    }
+
+    #[test]
+    fn for_state_change_gives_task_and_states() {
+        let date = TuduDate::from_iso("2023-06-07").unwrap();
+        let mut task = Task::new(String::from("Write docs"), TaskState::Complete);
+        task.subtasks = vec![Task::new(String::from("Intro"), TaskState::Complete)];
+
+        let hook = Hook::for_state_change(
+            HookEvent::OnComplete,
+            &date,
+            2.into(),
+            &task,
+            &TaskState::Started,
+            &TaskState::Complete,
+        );
+
+        assert_eq!(hook.payload["event"], "on-complete");
+        assert_eq!(hook.payload["date"], "2023-06-07");
+        assert_eq!(hook.payload["index"], "2");
+        assert_eq!(hook.payload["task"]["task"], "Write docs");
+        assert_eq!(hook.payload["task"]["subtasks"][0]["index"], "2.1");
+        assert_eq!(hook.payload["old_state"], "S");
+        assert_eq!(hook.payload["new_state"], "C");
+    }
+
+    #[test]
+    fn rollover_hook_gives_previous_tasks() {
+        let today = TuduDate::from_iso("2023-06-08").unwrap();
+        let previous = TuduDate::from_iso("2023-06-07").unwrap();
+        let task_list = TaskList::from_tasks(
+            &previous,
+            vec![Task::new(String::from("Write docs"), TaskState::Started)],
+        );
+
+        let hook = rollover_hook(&today, &task_list);
+
+        assert_eq!(hook.payload["event"], "on-day-rollover");
+        assert_eq!(hook.payload["date"], "2023-06-08");
+        assert_eq!(hook.payload["previous_date"], "2023-06-07");
+        assert_eq!(hook.payload["tasks"][0]["state"], "S");
+    }
+
+    #[test]
+    fn run_executable_gives_payload_and_fails_on_non_zero_exit() {
+        let directory = test_directory("hooks");
+        let received = directory.join("received.json");
+        let executable = install_hook(
+            &directory,
+            HookEvent::PreAdd,
+            &format!("cat > {}\necho 'too busy' >&2\nexit 3", received.display()),
+        );
+        let hook = Hook {
+            event: HookEvent::PreAdd,
+            payload: json!({ "task": "Write docs" }),
+        };
+        let post_hook = Hook {
+            event: HookEvent::PostAdd,
+            ..hook.clone()
+        };
+
+        let result = hook.run_executable(&executable);
+        let payload = fs::read_to_string(&received).unwrap();
+        let post_result = post_hook.run_executable(&executable);
+
+        // cleanup
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            result,
+            Err(TuduError::HookFailed(String::from(
+                "`pre-add` exited with exit status: 3 so nothing was changed, too busy"
+            )))
+        );
+        assert_eq!(payload, r#"{"task":"Write docs"}"#);
+        assert_eq!(
+            post_result,
+            Err(TuduError::HookFailed(String::from(
+                "`post-add` exited with exit status: 3, too busy"
+            )))
+        );
+    }
+}
@@ -0,0 +1,554 @@
+use chrono::Utc;
+
+use crate::date::TuduDate;
+use crate::error::TuduError;
+use crate::journal::{Journal, JournalEntry};
+use crate::model::{Task, TaskIndex, TaskList, TaskState};
+
+const LINE_LIMIT: usize = 75;
+
+/// Formats every task in the task lists as a VTODO within a single calendar, followed by every
+/// journal entry as a VJOURNAL. Forwarded tasks have no equivalent status so they are kept in an
+/// `X-TUDU-STATE` property to be read back on import. Subtasks are VTODOs of their own, related to
+/// their task by its UID.
+pub fn export_tasks(task_lists: &[TaskList], journals: &[Journal]) -> String {
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        String::from("PRODID:-//tudu//tudu//EN"),
+    ];
+
+    for task_list in task_lists {
+        let date = task_list.date();
+
+        for (index, task) in task_list.tasks().iter().enumerate() {
+            let uid = task_uid(date, (index + 1).into(), task);
+
+            lines.extend(format_task(date, &uid, None, task));
+
+            for (subtask_index, subtask) in task.subtasks.iter().enumerate() {
+                let index = TaskIndex {
+                    task: index + 1,
+                    subtask: Some(subtask_index + 1),
+                };
+
+                lines.extend(format_task(
+                    date,
+                    &task_uid(date, index, subtask),
+                    Some(&uid),
+                    subtask,
+                ));
+            }
+        }
+    }
+
+    for journal in journals {
+        for (index, entry) in journal.entries().iter().enumerate() {
+            lines.extend(format_journal_entry(journal.date(), index + 1, entry));
+        }
+    }
+
+    lines.push(String::from("END:VCALENDAR"));
+
+    lines.iter().map(|line| fold_line(line)).collect()
+}
+
+/// Calendar clients match tasks by UID, so a task keeps its id wherever it moves to. Tasks without
+/// one fall back to their place in the day.
+fn task_uid(date: &TuduDate, index: TaskIndex, task: &Task) -> String {
+    match &task.id {
+        Some(id) => format!("{id}@tudu"),
+        None => format!("{}-{index}@tudu", date.to_iso()),
+    }
+}
+
+fn format_task(date: &TuduDate, uid: &str, parent_uid: Option<&str>, task: &Task) -> Vec<String> {
+    let compact_date = date.to_iso().replace('-', "");
+
+    let status = match task.state {
+        TaskState::NotStarted | TaskState::Forwarded => "NEEDS-ACTION",
+        TaskState::Started => "IN-PROCESS",
+        TaskState::Complete => "COMPLETED",
+        TaskState::Ignored => "CANCELLED",
+    };
+
+    let mut lines = vec![
+        String::from("BEGIN:VTODO"),
+        format!("UID:{uid}"),
+        format!("DTSTAMP:{compact_date}T000000Z"),
+        format!("SUMMARY:{}", escape_text(&task.task)),
+        format!("DTSTART;VALUE=DATE:{compact_date}"),
+    ];
+
+    if let Some(due) = &task.due {
+        lines.push(format!("DUE;VALUE=DATE:{}", due.to_iso().replace('-', "")));
+    }
+
+    lines.push(format!("STATUS:{status}"));
+
+    if let Some(parent_uid) = parent_uid {
+        lines.push(format!("RELATED-TO:{parent_uid}"));
+    }
+
+    if task.state == TaskState::Forwarded {
+        lines.push(String::from("X-TUDU-STATE:F"));
+    }
+
+    lines.push(String::from("END:VTODO"));
+
+    lines
+}
+
+fn format_journal_entry(date: &TuduDate, index: usize, entry: &JournalEntry) -> Vec<String> {
+    let time = entry.time.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ");
+
+    vec![
+        String::from("BEGIN:VJOURNAL"),
+        format!("UID:{}-journal-{index}@tudu", date.to_iso()),
+        format!("DTSTAMP:{time}"),
+        format!("DTSTART:{time}"),
+        format!("DESCRIPTION:{}", escape_text(&entry.text)),
+        String::from("END:VJOURNAL"),
+    ]
+}
+
+/// Reads the VTODOs from a calendar along with the date of the task list each belongs on. Tasks
+/// are placed on their start date, falling back to their due date, then today, and keep their due
+/// date. A VTODO related to
+/// a parent among them is added to it as a subtask.
+pub fn import_tasks(contents: &str) -> Result<Vec<(TuduDate, Task)>, TuduError> {
+    let mut todos = Vec::new();
+    let mut todo: Option<TodoBuilder> = None;
+
+    for line in unfold_lines(contents) {
+        let (name, parameters, value) = parse_property(&line)?;
+
+        match (name.as_str(), todo.as_mut()) {
+            ("BEGIN", None) if value == "VTODO" => todo = Some(TodoBuilder::default()),
+            ("END", Some(_)) if value == "VTODO" => {
+                if let Some(builder) = todo.take() {
+                    todos.push(builder.build()?);
+                }
+            }
+            ("UID", Some(builder)) => builder.uid = Some(String::from(value)),
+            ("RELATED-TO", Some(builder)) if is_parent_relation(parameters) => {
+                builder.parent_uid = Some(String::from(value))
+            }
+            ("SUMMARY", Some(builder)) => builder.summary = Some(unescape_text(value)),
+            ("DUE", Some(builder)) => builder.due = Some(parse_date(value)?),
+            ("DTSTART", Some(builder)) => builder.start = Some(parse_date(value)?),
+            ("STATUS", Some(builder)) => builder.status = Some(String::from(value)),
+            ("X-TUDU-STATE", Some(builder)) => builder.tudu_state = Some(String::from(value)),
+            _ => {}
+        }
+    }
+
+    Ok(add_subtasks_to_parents(todos))
+}
+
+/// A relation is to the parent unless it says otherwise
+fn is_parent_relation(parameters: &str) -> bool {
+    parameters
+        .split(';')
+        .filter_map(|parameter| parameter.split_once('='))
+        .find(|(name, _)| name.eq_ignore_ascii_case("RELTYPE"))
+        .is_none_or(|(_, relation)| relation.eq_ignore_ascii_case("PARENT"))
+}
+
+struct ImportedTodo {
+    uid: Option<String>,
+    parent_uid: Option<String>,
+    date: TuduDate,
+    task: Task,
+}
+
+/// Adds each VTODO to its parent as a subtask. Subtasks cannot have subtasks of their own, so one
+/// whose parent is missing or is itself a subtask is kept as a task.
+fn add_subtasks_to_parents(todos: Vec<ImportedTodo>) -> Vec<(TuduDate, Task)> {
+    let is_subtask: Vec<bool> = todos
+        .iter()
+        .map(|todo| {
+            todo.parent_uid.as_ref().is_some_and(|parent_uid| {
+                todos.iter().any(|other| {
+                    other.parent_uid.is_none() && other.uid.as_ref() == Some(parent_uid)
+                })
+            })
+        })
+        .collect();
+
+    let mut tasks: Vec<ImportedTodo> = Vec::new();
+    let mut subtasks = Vec::new();
+
+    for (todo, is_subtask) in todos.into_iter().zip(is_subtask) {
+        match is_subtask {
+            true => subtasks.push(todo),
+            false => tasks.push(todo),
+        }
+    }
+
+    for subtask in subtasks {
+        if let Some(parent) = tasks
+            .iter_mut()
+            .find(|task| task.parent_uid.is_none() && task.uid == subtask.parent_uid)
+        {
+            parent.task.subtasks.push(subtask.task);
+        }
+    }
+
+    tasks
+        .into_iter()
+        .map(|todo| (todo.date, todo.task))
+        .collect()
+}
+
+#[derive(Default)]
+struct TodoBuilder {
+    uid: Option<String>,
+    parent_uid: Option<String>,
+    summary: Option<String>,
+    due: Option<TuduDate>,
+    start: Option<TuduDate>,
+    status: Option<String>,
+    tudu_state: Option<String>,
+}
+
+impl TodoBuilder {
+    fn build(self) -> Result<ImportedTodo, TuduError> {
+        let summary = self.summary.ok_or(TuduError::BadTaskFormat)?;
+
+        // Day files hold one task per line, so a summary over several lines is joined into one
+        let summary = summary
+            .split(['\r', '\n'])
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<&str>>()
+            .join(" ");
+
+        let state = match (self.status.as_deref(), self.tudu_state.as_deref()) {
+            (_, Some("F")) => TaskState::Forwarded,
+            (Some("IN-PROCESS"), _) => TaskState::Started,
+            (Some("COMPLETED"), _) => TaskState::Complete,
+            (Some("CANCELLED"), _) => TaskState::Ignored,
+            _ => TaskState::NotStarted,
+        };
+
+        let date = self
+            .start
+            .or_else(|| self.due.clone())
+            .unwrap_or_else(TuduDate::today);
+
+        Ok(ImportedTodo {
+            uid: self.uid,
+            parent_uid: self.parent_uid,
+            date,
+            task: Task {
+                due: self.due,
+                ..Task::new(summary, state)
+            },
+        })
+    }
+}
+
+/// Splits a content line into its name, parameters and value, e.g. `DUE;VALUE=DATE:20230607`
+fn parse_property(line: &str) -> Result<(String, &str, &str), TuduError> {
+    let (key, value) = line.split_once(':').ok_or(TuduError::BadTaskFormat)?;
+
+    let (name, parameters) = key.split_once(';').unwrap_or((key, ""));
+
+    Ok((name.to_ascii_uppercase(), parameters, value))
+}
+
+/// Reads a DATE or DATE-TIME value, keeping only the date
+fn parse_date(value: &str) -> Result<TuduDate, TuduError> {
+    let date = match value.get(0..8) {
+        Some(date) if date.bytes().all(|byte| byte.is_ascii_digit()) => date,
+        _ => return Err(TuduError::BadTaskFormat),
+    };
+
+    let iso_date = format!("{}-{}-{}", &date[0..4], &date[4..6], &date[6..8]);
+
+    TuduDate::from_iso(&iso_date).map_err(|_| TuduError::BadTaskFormat)
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut characters = text.chars();
+
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            unescaped.push(character);
+            continue;
+        }
+
+        match characters.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+/// Folds a content line so no line is longer than 75 octets, continuing lines with a space
+fn fold_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut line_length = 0;
+
+    for character in line.chars() {
+        let character_length = character.len_utf8();
+
+        if line_length + character_length > LINE_LIMIT {
+            folded.push_str("\r\n ");
+            line_length = 1;
+        }
+
+        folded.push(character);
+        line_length += character_length;
+    }
+
+    folded.push_str("\r\n");
+
+    folded
+}
+
+fn unfold_lines(contents: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+
+    for line in contents.lines() {
+        let line = line.trim_end_matches('\r');
+
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(previous)) => previous.push_str(continuation),
+            _ if line.is_empty() => {}
+            _ => lines.push(String::from(line)),
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chrono::DateTime;
+
+    use super::*;
+
+    fn golden_tasks() -> Vec<Task> {
+        vec![
+            Task::new(String::from("This task is started"), TaskState::Started),
+            Task::new(String::from("This one is completed"), TaskState::Complete),
+            Task::new(String::from("Didn't like this one"), TaskState::Ignored),
+            Task::new(String::from("This one's for later"), TaskState::Forwarded),
+            Task::new(String::from("Patience is a virtue"), TaskState::NotStarted),
+        ]
+    }
+
+    #[test]
+    fn export_tasks_matches_golden_file() {
+        let date = TuduDate::new(7, 6, 2023);
+        let task_lists = vec![TaskList::from_tasks(&date, golden_tasks())];
+
+        let expected_contents = fs::read_to_string("./src/tests/2023-06-07.ics").unwrap();
+
+        let exported = export_tasks(&task_lists, &[]);
+
+        assert_eq!(exported, expected_contents);
+    }
+
+    #[test]
+    fn export_tasks_includes_journal_entries() {
+        let date = TuduDate::new(7, 6, 2023);
+        let mut journal = Journal::from_entries(&date, vec![]);
+        journal
+            .add_entry(
+                "Shipped it, finally\nTime to rest",
+                DateTime::parse_from_rfc3339("2023-06-07T17:30:00+01:00").unwrap(),
+            )
+            .unwrap();
+
+        let exported = export_tasks(&[], &[journal]);
+
+        assert!(exported.contains(
+            "BEGIN:VJOURNAL\r\nUID:2023-06-07-journal-1@tudu\r\nDTSTAMP:20230607T163000Z\r\nDTSTART:20230607T163000Z\r\nDESCRIPTION:Shipped it\\, finally\\nTime to rest\r\nEND:VJOURNAL\r\n"
+        ));
+        assert_eq!(import_tasks(&exported), Ok(vec![]));
+    }
+
+    #[test]
+    fn import_tasks_reads_golden_file() {
+        let contents = fs::read_to_string("./src/tests/2023-06-07.ics").unwrap();
+
+        let date = TuduDate::new(7, 6, 2023);
+        let expected_tasks: Vec<(TuduDate, Task)> = golden_tasks()
+            .into_iter()
+            .map(|task| (date.clone(), task))
+            .collect();
+
+        let imported = import_tasks(&contents).unwrap();
+
+        assert_eq!(imported, expected_tasks);
+    }
+
+    #[test]
+    fn import_tasks_reads_calendars_from_other_clients() {
+        let contents = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nSUMMARY:Not a task\r\nEND:VEVENT\r\nBEGIN:VTODO\r\nSUMMARY:Book flights\\, hotel and\r\n  car\r\nDTSTART;TZID=Europe/London:20230610T090000\r\nSTATUS:IN-PROCESS\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+
+        let expected_tasks = vec![(
+            TuduDate::new(10, 6, 2023),
+            Task::new(
+                String::from("Book flights, hotel and car"),
+                TaskState::Started,
+            ),
+        )];
+
+        let imported = import_tasks(contents).unwrap();
+
+        assert_eq!(imported, expected_tasks);
+    }
+
+    #[test]
+    fn import_tasks_without_summary_gives_error() {
+        let contents = "BEGIN:VTODO\r\nSTATUS:COMPLETED\r\nEND:VTODO\r\n";
+
+        let result = import_tasks(contents);
+
+        assert_eq!(result, Err(TuduError::BadTaskFormat));
+    }
+
+    #[test]
+    fn import_tasks_joins_summary_over_several_lines() {
+        let contents = "BEGIN:VTODO\r\nSUMMARY:first\\nC,injected\\n  C;est=30,Sub\r\nDTSTART:20230607\r\nEND:VTODO\r\n";
+
+        let imported = import_tasks(contents).unwrap();
+
+        assert_eq!(
+            imported,
+            vec![(
+                TuduDate::new(7, 6, 2023),
+                Task::new(
+                    String::from("first C,injected C;est=30,Sub"),
+                    TaskState::NotStarted
+                )
+            )]
+        );
+    }
+
+    #[test]
+    fn export_tasks_uses_task_id_for_uid() {
+        let date = TuduDate::new(7, 6, 2023);
+        let task_lists = vec![TaskList::from_tasks(
+            &date,
+            vec![
+                Task::new(String::from("No id"), TaskState::NotStarted),
+                Task {
+                    id: Some(String::from("k3j9x2")),
+                    ..Task::new(String::from("Has an id"), TaskState::NotStarted)
+                },
+            ],
+        )];
+
+        let exported = export_tasks(&task_lists, &[]);
+
+        assert!(exported.contains("UID:2023-06-07-1@tudu\r\n"));
+        assert!(exported.contains("UID:k3j9x2@tudu\r\n"));
+        assert!(!exported.contains("UID:2023-06-07-2@tudu"));
+    }
+
+    #[test]
+    fn export_then_import_keeps_subtasks() {
+        let date = TuduDate::new(7, 6, 2023);
+        let mut parent = Task::new(String::from("Ship it"), TaskState::Started);
+        parent.subtasks = vec![
+            Task::new(String::from("Write docs"), TaskState::Complete),
+            Task::new(String::from("Tag release"), TaskState::NotStarted),
+        ];
+        let tasks = vec![
+            parent,
+            Task::new(String::from("Rest"), TaskState::NotStarted),
+        ];
+        let task_lists = vec![TaskList::from_tasks(&date, tasks.clone())];
+
+        let exported = export_tasks(&task_lists, &[]);
+        let imported = import_tasks(&exported).unwrap();
+
+        assert!(exported.contains("UID:2023-06-07-1.1@tudu\r\n"));
+        assert!(exported.contains("RELATED-TO:2023-06-07-1@tudu\r\n"));
+        assert_eq!(
+            imported,
+            tasks
+                .into_iter()
+                .map(|task| (date.clone(), task))
+                .collect::<Vec<(TuduDate, Task)>>()
+        );
+    }
+
+    #[test]
+    fn export_then_import_keeps_due_date_apart_from_planned_day() {
+        let date = TuduDate::new(7, 6, 2023);
+        let due = TuduDate::new(9, 6, 2023);
+        let tasks = vec![
+            Task {
+                due: Some(due),
+                ..Task::new(String::from("File taxes"), TaskState::NotStarted)
+            },
+            Task::new(String::from("Rest"), TaskState::NotStarted),
+        ];
+        let task_lists = vec![TaskList::from_tasks(&date, tasks.clone())];
+
+        let exported = export_tasks(&task_lists, &[]);
+        let imported = import_tasks(&exported).unwrap();
+
+        assert!(exported.contains("DTSTART;VALUE=DATE:20230607\r\nDUE;VALUE=DATE:20230609\r\n"));
+        assert_eq!(exported.matches("DUE;").count(), 1);
+        assert_eq!(
+            imported,
+            tasks
+                .into_iter()
+                .map(|task| (date.clone(), task))
+                .collect::<Vec<(TuduDate, Task)>>()
+        );
+    }
+
+    #[test]
+    fn import_tasks_adds_children_from_other_clients_to_their_parent() {
+        let contents = "BEGIN:VTODO\r\nUID:child\r\nRELATED-TO:parent\r\nSUMMARY:Child\r\nDTSTART:20230607\r\nEND:VTODO\r\nBEGIN:VTODO\r\nUID:parent\r\nSUMMARY:Parent\r\nDTSTART:20230607\r\nEND:VTODO\r\nBEGIN:VTODO\r\nUID:sibling\r\nRELATED-TO;RELTYPE=SIBLING:parent\r\nSUMMARY:Sibling\r\nDTSTART:20230607\r\nEND:VTODO\r\n";
+
+        let date = TuduDate::new(7, 6, 2023);
+        let mut parent = Task::new(String::from("Parent"), TaskState::NotStarted);
+        parent.subtasks = vec![Task::new(String::from("Child"), TaskState::NotStarted)];
+
+        let imported = import_tasks(contents).unwrap();
+
+        assert_eq!(
+            imported,
+            vec![
+                (date.clone(), parent),
+                (
+                    date,
+                    Task::new(String::from("Sibling"), TaskState::NotStarted)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn fold_line_splits_long_lines() {
+        let line = format!("SUMMARY:{}", "a".repeat(80));
+
+        let folded = fold_line(&line);
+
+        let expected = format!("SUMMARY:{}\r\n {}\r\n", "a".repeat(67), "a".repeat(13));
+        assert_eq!(folded, expected);
+        assert_eq!(unfold_lines(&folded), vec![line]);
+    }
+}
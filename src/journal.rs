@@ -0,0 +1,207 @@
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+
+use crate::date::TuduDate;
+use crate::error::TuduError;
+use crate::storage::{create_filepath, read_file_if_exists, write_file};
+
+pub const JOURNAL_DIRECTORY: &str = "journal";
+const ENTRY_INDENT: &str = "  ";
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct JournalEntry {
+    pub time: DateTime<FixedOffset>,
+    pub text: String,
+}
+
+impl JournalEntry {
+    /// The time the entry was written, e.g. `09:15`
+    pub fn formatted_time(&self) -> String {
+        self.time.format("%H:%M").to_string()
+    }
+}
+
+/// The journal entries written for a date, kept in the journal folder of the tasks directory under
+/// the same name as the day file
+#[derive(Debug, PartialEq, Eq)]
+pub struct Journal<'a> {
+    entries: Vec<JournalEntry>,
+    date: &'a TuduDate,
+}
+
+impl Journal<'_> {
+    pub fn for_date(date: &TuduDate) -> Result<Journal<'_>, TuduError> {
+        let filepath = create_filepath(&journal_filename(date))?;
+
+        let entries = match read_file_if_exists(&filepath)? {
+            Some(contents) => parse_journal(&contents)?,
+            None => Vec::new(),
+        };
+
+        Ok(Journal { entries, date })
+    }
+
+    #[cfg(test)]
+    pub fn from_entries(date: &TuduDate, entries: Vec<JournalEntry>) -> Journal<'_> {
+        Journal { entries, date }
+    }
+
+    pub fn date(&self) -> &TuduDate {
+        self.date
+    }
+
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    pub fn add_entry(&mut self, text: &str, time: DateTime<FixedOffset>) -> Result<(), TuduError> {
+        let text = text.trim();
+
+        if text.is_empty() {
+            return Err(TuduError::InvalidArguments(String::from(
+                "the journal entry is empty",
+            )));
+        }
+
+        self.entries.push(JournalEntry {
+            time,
+            text: String::from(text),
+        });
+
+        Ok(())
+    }
+
+    pub fn write_to_file(&self) -> Result<(), TuduError> {
+        let filepath = create_filepath(&journal_filename(self.date))?;
+
+        write_file(&filepath, &format_journal(&self.entries))
+    }
+
+    /// Formats the entries for display beneath the tasks, with the time of each entry
+    pub fn get_formatted_entries(&self) -> String {
+        if self.entries.is_empty() {
+            return String::from("There are no journal entries for this date\n");
+        }
+
+        self.entries
+            .iter()
+            .map(|entry| {
+                let indent = " ".repeat(entry.formatted_time().len() + 4);
+                let text = entry.text.replace('\n', &format!("\n{indent}"));
+
+                format!("{}    {text}\n", entry.formatted_time())
+            })
+            .collect()
+    }
+}
+
+fn journal_filename(date: &TuduDate) -> String {
+    format!("{JOURNAL_DIRECTORY}/{}", date.to_filename())
+}
+
+/// Reads entries written as their timestamp and first line, with any further lines indented
+fn parse_journal(contents: &str) -> Result<Vec<JournalEntry>, TuduError> {
+    let mut entries: Vec<JournalEntry> = Vec::new();
+
+    for line in contents.lines() {
+        match line.strip_prefix(ENTRY_INDENT) {
+            Some(line) => match entries.last_mut() {
+                Some(entry) => {
+                    entry.text.push('\n');
+                    entry.text.push_str(line);
+                }
+                None => return Err(TuduError::BadTaskFormat),
+            },
+            None => {
+                let (time, text) = line.split_once(' ').ok_or(TuduError::BadTaskFormat)?;
+                let time =
+                    DateTime::parse_from_rfc3339(time).map_err(|_| TuduError::BadTaskFormat)?;
+
+                entries.push(JournalEntry {
+                    time,
+                    text: String::from(text),
+                });
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+fn format_journal(entries: &[JournalEntry]) -> String {
+    entries
+        .iter()
+        .map(|entry| {
+            let time = entry.time.to_rfc3339_opts(SecondsFormat::Secs, false);
+            let text = entry.text.replace('\n', &format!("\n{ENTRY_INDENT}"));
+
+            format!("{time} {text}\n")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(&format!("2023-06-07T{time}:00+01:00")).unwrap()
+    }
+
+    #[test]
+    fn format_then_parse_journal_gives_back_the_same_entries() {
+        let entries = vec![
+            JournalEntry {
+                time: at("09:15"),
+                text: String::from("Fixed the release script"),
+            },
+            JournalEntry {
+                time: at("17:30"),
+                text: String::from("Wrapped up:\n  - reviews"),
+            },
+        ];
+
+        let contents = format_journal(&entries);
+
+        assert_eq!(
+            contents,
+            "2023-06-07T09:15:00+01:00 Fixed the release script
+2023-06-07T17:30:00+01:00 Wrapped up:
+    - reviews
+"
+        );
+        assert_eq!(parse_journal(&contents).unwrap(), entries);
+    }
+
+    #[test]
+    fn parse_journal_with_bad_timestamp_gives_error() {
+        let result = parse_journal("yesterday Fixed the release script\n");
+
+        assert_eq!(result, Err(TuduError::BadTaskFormat));
+    }
+
+    #[test]
+    fn add_entry_when_empty_gives_error() {
+        let date = TuduDate::new(7, 6, 2023);
+        let mut journal = Journal::from_entries(&date, vec![]);
+
+        let result = journal.add_entry("  \n", at("09:00"));
+
+        assert!(matches!(result, Err(TuduError::InvalidArguments(_))));
+        assert!(journal.entries().is_empty());
+    }
+
+    #[test]
+    fn get_formatted_entries_lines_up_following_lines() {
+        let date = TuduDate::new(7, 6, 2023);
+        let mut journal = Journal::from_entries(&date, vec![]);
+        journal.add_entry("Standup went well", at("09:15")).unwrap();
+        journal.add_entry("Notes:\nship it", at("17:30")).unwrap();
+
+        let expected_formatting = "09:15    Standup went well
+17:30    Notes:
+         ship it
+";
+
+        assert_eq!(journal.get_formatted_entries(), expected_formatting);
+    }
+}
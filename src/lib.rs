@@ -1,207 +1,447 @@
+use std::path::PathBuf;
+
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
 use error::fail_with_error;
 use execute::execute_command;
 
-use crate::date::TuduDate;
+use crate::alias::expand_aliases;
+use crate::cli::{Cli, CliCommand, DateArgs};
+use crate::completions::Shell;
+use crate::config::{config_path, Config};
+use crate::date::{DateRange, TuduDate};
 use crate::error::TuduError;
+use crate::estimate::split_estimate;
+use crate::lists::validate_list_name;
 use crate::model::{
-    AddCommand, Command, EditCommand, RemoveCommand, SetCommand, TaskState, ViewCommand,
+    AddCommand, AtCommand, Command, CompletionsCommand, DependCommand, DueCommand, EditCommand,
+    ExportCommand, Format, GraphCommand, ImportCommand, JournalCommand, LogCommand, MergeCommand,
+    MoveCommand, NextCommand, NoteCommand, PriorityCommand, RemindCommand, RemoveCommand,
+    ReportCommand, ScriptCommand, SearchCommand, ServeCommand, SetCommand, ShowCommand,
+    SnoozeCommand, StandupCommand, StartCommand, StopCommand, TaskIndex, TaskState,
+    TimesheetCommand, ViewCommand,
 };
+use crate::priority::parse_priority;
+use crate::reminders::{parse_snooze, parse_time, Notifier};
+use crate::report::ReportFormat;
+use crate::scripts::find_script;
+use crate::standup::StandupFormat;
+mod alias;
+mod cli;
+mod completions;
+mod config;
+mod csv;
+mod daemon;
 mod date;
+mod dependency;
+mod due;
 mod error;
+mod estimate;
 mod execute;
+mod history;
+mod hooks;
+mod ical;
+mod journal;
+mod lists;
+mod merge;
 mod model;
+mod next;
+mod notes;
+mod priority;
+mod reminders;
+mod report;
+mod scripts;
+mod search;
+mod server;
+mod standup;
 mod storage;
+mod timelog;
+mod todotxt;
 
-fn parse_add_command(args: Vec<String>) -> Result<Command, TuduError> {
-    let task = args[0].to_owned();
-
-    let date =
-        match args.len() {
-            2 => Some(TuduDate::from_date(args[1].as_str())?),
-            1 => None,
-            _ => return Err(TuduError::InvalidArguments(String::from(
-                "`add` accepts a task, e.g. \"Example task\", and an optional date, e.g. 10-6-2023",
-            ))),
-        };
-
-    let config = AddCommand { task, date };
-
-    return Ok(Command::Add(config));
+fn parse_optional_date(date: &DateArgs) -> Result<Option<TuduDate>, TuduError> {
+    date.value().map(TuduDate::from_date).transpose()
 }
 
-fn parse_remove_command(args: Vec<String>) -> Result<Command, TuduError> {
-    let index = match args[0].parse::<usize>() {
-        Ok(index) => index,
-        Err(_) => {
-            return Err(TuduError::InvalidArguments(String::from(
-                "`remove` accepts a task number and an optional date, e.g. 10-06-2023",
-            )))
-        }
-    };
-
-    let date = match args.len() {
-        2 => Some(TuduDate::from_date(args[1].as_str())?),
-        1 => None,
-        _ => {
-            return Err(TuduError::InvalidArguments(String::from(
-                "`remove` accepts a task number and an optional date e.g. 10-6-2023",
-            )))
-        }
-    };
-
-    let config = RemoveCommand { index, date };
-
-    return Ok(Command::Remove(config));
+fn parse_state(state: &str) -> Result<TaskState, TuduError> {
+    TaskState::from_code(state).ok_or(TuduError::InvalidState)
 }
 
-fn parse_set_command(args: Vec<String>) -> Result<Command, TuduError> {
-    let index = match args[0].parse::<usize>() {
-        Ok(index) => index,
-        Err(_) => {
-            return Err(TuduError::InvalidArguments(String::from(
-                "`set` accepts a task number and a task state, for states see `tudu help`",
-            )))
+fn parse_cli_error(err: clap::Error) -> Result<Command, TuduError> {
+    match err.kind() {
+        ErrorKind::DisplayHelp | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand => {
+            Ok(Command::Help(err.render().to_string()))
         }
-    };
+        _ => {
+            let rendered = err.render().to_string();
+            let message = rendered.trim_start_matches("error: ").trim_end();
 
-    let arg_as_str = match args.get(1) {
-        Some(value) => value.as_str(),
-        None => {
-            return Err(TuduError::InvalidArguments(String::from(
-                "`set` accepts a task number and a task state, for states see `tudu help`",
-            )))
+            Err(TuduError::InvalidArguments(String::from(message)))
         }
-    };
-
-    let state = match arg_as_str {
-        "C" => TaskState::Complete,
-        "N" => TaskState::NotStarted,
-        "S" => TaskState::Started,
-        "F" => TaskState::Forwarded,
-        "X" => TaskState::Ignored,
-        _ => return Err(TuduError::InvalidState),
-    };
+    }
+}
 
-    let date = match args.len() {
-        2 => None,
-        3 => Some(TuduDate::from_date(args[2].as_str())?),
-        _ => return Err(TuduError::InvalidArguments(
-                    String::from("`set` accepts a task number, a task state, and an optional date, e.g. 10-6-2023. For states see `tudu help`")
-                )),
+/// Commands that are not built in are run by the user's script of the same name, if there is one
+fn parse_script_command(args: &[String]) -> Result<Command, TuduError> {
+    let Some(name) = args.get(1) else {
+        return Err(TuduError::InvalidCommand);
     };
 
-    let config = SetCommand { index, state, date };
-
-    return Ok(Command::Set(config));
+    match find_script(name)? {
+        Some(script) => Ok(Command::Script(ScriptCommand {
+            name: name.clone(),
+            script,
+            args: args[2..].to_vec(),
+        })),
+        None => Err(TuduError::InvalidCommand),
+    }
 }
 
-fn parse_complete_command(args: Vec<String>) -> Result<Command, TuduError> {
-    let index = match args[0].parse::<usize>() {
-        Ok(index) => index,
-        Err(_) => {
-            return Err(TuduError::InvalidArguments(String::from(
-                "`complete` accepts a task number and an optional date, e.g. 10-6-2023",
-            )))
+/// Takes the list chosen before the command, as in `tudu -l work d 3`, so that the command can
+/// still be an alias or a script
+fn take_list_option(mut args: Vec<String>) -> Result<(Option<String>, Vec<String>), TuduError> {
+    let list = match args.get(1).map(String::as_str) {
+        Some("-l" | "--list") if args.len() > 2 => {
+            let list = args.remove(2);
+            args.remove(1);
+            Some(list)
         }
-    };
-
-    let date = match args.len() {
-        1 => None,
-        2 => Some(TuduDate::from_date(args[1].as_str())?),
-        _ => {
+        Some("-l" | "--list") => {
             return Err(TuduError::InvalidArguments(String::from(
-                "`complete` accepts a task number and an optional date, e.g. 10-6-2023",
+                "give the name of the list to use",
             )))
         }
+        Some(arg) => match arg.strip_prefix("--list=").map(String::from) {
+            Some(list) => {
+                args.remove(1);
+                Some(list)
+            }
+            None => None,
+        },
+        None => None,
     };
 
-    let config = SetCommand {
-        index,
-        date,
-        state: TaskState::Complete,
-    };
+    list.as_deref().map(validate_list_name).transpose()?;
 
-    return Ok(Command::Set(config));
+    Ok((list, args))
 }
 
-fn parse_edit_command(args: Vec<String>) -> Result<Command, TuduError> {
-    let index = match args[0].parse::<usize>() {
-        Ok(index) => index,
-        Err(_) => {
-            return Err(TuduError::InvalidArguments(String::from(
-                "`edit` accepts a task number and the new task description",
-            )))
+/// Expands any alias the command starts with before parsing, giving a macro when the alias runs
+/// more than one command. The aliases are read from the config at the path given.
+fn parse_command(
+    args: Vec<String>,
+    config_path: fn() -> Result<PathBuf, TuduError>,
+) -> Result<Command, TuduError> {
+    let (list, args) = take_list_option(args)?;
+
+    // The config is only read when the command is not one tudu has, so the commands still work
+    // when the config is not valid
+    let builtins = builtin_command_names();
+
+    let args = match may_be_alias(&args, &builtins) {
+        true => {
+            let config = Config::load_from(&config_path()?)?;
+            expand_aliases(&config.aliases, &builtins, args)?
         }
+        false => vec![args],
     };
 
-    let task = args[1].to_owned();
+    let mut commands = args
+        .into_iter()
+        .map(parse_builtin_command)
+        .collect::<Result<Vec<Command>, TuduError>>()?;
 
-    let date = match args.len() {
-        1 | 2 => None,
-        3 => Some(TuduDate::from_date(args[2].as_str())?),
-        _ => {
-            return Err(TuduError::InvalidArguments(String::from(
-                "`edit` accepts a task number, the modified task description, and an optional date, e.g. 10-6-2023",
-            )))
-        }
+    let command = match commands.len() {
+        1 => commands.remove(0),
+        _ => Command::Macro(commands),
     };
 
-    let config = EditCommand { index, task, date };
-
-    return Ok(Command::Edit(config));
-}
-
-fn parse_view_command(args: Vec<String>) -> Result<Command, TuduError> {
-    if args.len() < 1 {
-        return Err(TuduError::InvalidArguments(String::from(
-            "`view` accepts a date, e.g. 10-6-2023",
-        )));
+    match list {
+        Some(list) => Ok(Command::InList(list, Box::new(command))),
+        None => Ok(command),
     }
+}
 
-    let date = TuduDate::from_date(args[0].as_str())?;
-
-    let config = ViewCommand { date };
+/// The names of the commands tudu has along with their shorter names, which aliases cannot take
+fn builtin_command_names() -> Vec<String> {
+    Cli::command()
+        .get_subcommands()
+        .flat_map(|command| std::iter::once(command.get_name()).chain(command.get_all_aliases()))
+        .chain(std::iter::once("help"))
+        .map(String::from)
+        .collect()
+}
 
-    return Ok(Command::View(config));
+/// Whether the command could be an alias, as it is not one tudu has or an option such as `--help`
+fn may_be_alias(args: &[String], builtins: &[String]) -> bool {
+    args.get(1)
+        .is_some_and(|command| !command.starts_with('-') && !builtins.contains(command))
 }
 
-fn parse_command(args: Vec<String>) -> Result<Command, TuduError> {
-    if args.len() == 1 {
-        let root_config = ViewCommand {
+fn parse_builtin_command(args: Vec<String>) -> Result<Command, TuduError> {
+    let cli = match Cli::try_parse_from(&args) {
+        Ok(cli) => cli,
+        Err(err) if err.kind() == ErrorKind::InvalidSubcommand => {
+            return parse_script_command(&args)
+        }
+        Err(err) => return parse_cli_error(err),
+    };
+
+    let command = match cli.command {
+        None => Command::View(ViewCommand {
             date: TuduDate::today(),
-        };
-        return Ok(Command::View(root_config));
-    }
+            journal: false,
+            all_lists: false,
+        }),
+        Some(CliCommand::Add {
+            task,
+            parent,
+            due,
+            priority,
+            at,
+            date,
+        }) => {
+            let (task, estimate) = split_estimate(&task)?;
+
+            Command::Add(AddCommand {
+                task,
+                estimate,
+                parent,
+                due: due.as_deref().map(TuduDate::from_date).transpose()?,
+                priority: priority.as_deref().map(parse_priority).transpose()?,
+                at: at.as_deref().map(parse_time).transpose()?,
+                date: parse_optional_date(&date)?,
+            })
+        }
+        Some(CliCommand::Remove { index, date }) => Command::Remove(RemoveCommand {
+            index: TaskIndex::parse(&index)?,
+            date: parse_optional_date(&date)?,
+        }),
+        Some(CliCommand::Set { index, state, date }) => Command::Set(SetCommand {
+            index: TaskIndex::parse(&index)?,
+            state: parse_state(&state)?,
+            date: parse_optional_date(&date)?,
+        }),
+        Some(CliCommand::Complete { index, date }) => Command::Set(SetCommand {
+            index: TaskIndex::parse(&index)?,
+            state: TaskState::Complete,
+            date: parse_optional_date(&date)?,
+        }),
+        Some(CliCommand::View {
+            date,
+            journal,
+            all_lists,
+        }) => Command::View(ViewCommand {
+            date: parse_optional_date(&date)?.unwrap_or_else(TuduDate::today),
+            journal,
+            all_lists,
+        }),
+        Some(CliCommand::Edit { index, task, date }) => {
+            let (task, estimate) = split_estimate(&task)?;
+
+            Command::Edit(EditCommand {
+                index: TaskIndex::parse(&index)?,
+                task,
+                estimate,
+                date: parse_optional_date(&date)?,
+            })
+        }
+        Some(CliCommand::Completions { shell }) => Command::Completions(CompletionsCommand {
+            shell: Shell::from_name(&shell)?,
+        }),
+        Some(CliCommand::Export { format, range }) => Command::Export(ExportCommand {
+            format: Format::from_name(&format)?,
+            range: match range {
+                Some(range) => DateRange::from_range(&range)?,
+                None => DateRange::single(TuduDate::today()),
+            },
+        }),
+        Some(CliCommand::Import { file, format }) => Command::Import(ImportCommand {
+            format: match format {
+                Some(format) => Format::from_name(&format)?,
+                None => Format::from_path(&file)?,
+            },
+            path: file,
+        }),
+        Some(CliCommand::Report { format, range }) => Command::Report(ReportCommand {
+            format: ReportFormat::from_name(&format)?,
+            range: DateRange::from_range(&range)?,
+        }),
+        Some(CliCommand::Standup { since, format }) => Command::Standup(StandupCommand {
+            since: since.as_deref().map(TuduDate::from_date).transpose()?,
+            format: StandupFormat::from_name(&format)?,
+        }),
+        Some(CliCommand::Start { index, date }) => Command::Start(StartCommand {
+            index,
+            date: parse_optional_date(&date)?,
+        }),
+        Some(CliCommand::Stop { index, date }) => Command::Stop(StopCommand {
+            index,
+            date: date.as_deref().map(TuduDate::from_date).transpose()?,
+        }),
+        Some(CliCommand::Timesheet { range }) => Command::Timesheet(TimesheetCommand {
+            range: DateRange::from_range(&range)?,
+        }),
+        Some(CliCommand::Depend {
+            index,
+            prerequisite,
+            date,
+            from,
+            remove,
+        }) => Command::Depend(DependCommand {
+            index: TaskIndex::parse(&index)?,
+            prerequisite: TaskIndex::parse(&prerequisite)?,
+            date: parse_optional_date(&date)?,
+            prerequisite_date: from.as_deref().map(TuduDate::from_date).transpose()?,
+            remove,
+        }),
+        Some(CliCommand::Graph { range }) => Command::Graph(GraphCommand {
+            range: match range {
+                Some(range) => DateRange::from_range(&range)?,
+                None => DateRange::single(TuduDate::today()),
+            },
+        }),
+        Some(CliCommand::Note { index, date }) => Command::Note(NoteCommand {
+            index: TaskIndex::parse(&index)?,
+            date: parse_optional_date(&date)?,
+        }),
+        Some(CliCommand::Show { index, date }) => Command::Show(ShowCommand {
+            index: TaskIndex::parse(&index)?,
+            date: parse_optional_date(&date)?,
+        }),
+        Some(CliCommand::Journal { date, message }) => Command::Journal(JournalCommand {
+            message,
+            date: parse_optional_date(&date)?,
+        }),
+        Some(CliCommand::Search { query, range }) => Command::Search(SearchCommand {
+            query,
+            range: range.as_deref().map(DateRange::from_range).transpose()?,
+        }),
+        // The due date is only missing when it is being cleared
+        Some(CliCommand::Due {
+            index,
+            due,
+            date,
+            clear: _,
+        }) => Command::Due(DueCommand {
+            index: TaskIndex::parse(&index)?,
+            due: due.as_deref().map(TuduDate::from_date).transpose()?,
+            date: parse_optional_date(&date)?,
+        }),
+        Some(CliCommand::Overdue) => Command::Overdue,
+        // The priority is only missing when it is being cleared
+        Some(CliCommand::Priority {
+            index,
+            priority,
+            date,
+            clear: _,
+        }) => Command::Priority(PriorityCommand {
+            index: TaskIndex::parse(&index)?,
+            priority: priority.as_deref().map(parse_priority).transpose()?,
+            date: parse_optional_date(&date)?,
+        }),
+        // The time is only missing when the reminder is being cleared
+        Some(CliCommand::At {
+            index,
+            at,
+            date,
+            clear: _,
+        }) => Command::At(AtCommand {
+            index: TaskIndex::parse(&index)?,
+            at: at.as_deref().map(parse_time).transpose()?,
+            date: parse_optional_date(&date)?,
+        }),
+        Some(CliCommand::Remind {
+            notify,
+            command,
+            once,
+        }) => Command::Remind(RemindCommand {
+            notifier: Notifier::from_name(&notify, command)?,
+            once,
+        }),
+        Some(CliCommand::Snooze {
+            index,
+            date,
+            duration,
+        }) => Command::Snooze(SnoozeCommand {
+            index: TaskIndex::parse(&index)?,
+            duration: parse_snooze(&duration)?,
+            date: parse_optional_date(&date)?,
+        }),
+        Some(CliCommand::Next { count, start, why }) => {
+            Command::Next(NextCommand { count, start, why })
+        }
+        Some(CliCommand::Log { date }) => Command::Log(LogCommand {
+            date: parse_optional_date(&date)?,
+        }),
+        Some(CliCommand::Sync) => Command::Sync,
+        Some(CliCommand::Merge {
+            base,
+            ours,
+            theirs,
+            output,
+        }) => Command::Merge(MergeCommand {
+            base,
+            ours,
+            theirs,
+            output,
+        }),
+        Some(CliCommand::Serve { port, host, token }) => {
+            Command::Serve(ServeCommand { host, port, token })
+        }
+        Some(CliCommand::Daemon) => Command::Daemon,
+        Some(CliCommand::Alias) => Command::Alias,
+        Some(CliCommand::Lists) => Command::Lists,
+        Some(CliCommand::Move { index, to, date }) => {
+            validate_list_name(&to)?;
+
+            Command::Move(MoveCommand {
+                index,
+                to,
+                date: parse_optional_date(&date)?,
+            })
+        }
+        Some(CliCommand::TaskCandidates { date }) => Command::TaskCandidates(
+            parse_optional_date(&date)
+                .ok()
+                .map(|date| date.unwrap_or_else(TuduDate::today)),
+        ),
+    };
 
-    match args[1].as_str() {
-        "add" => parse_add_command(args[2..].to_vec()),
-        "remove" => parse_remove_command(args[2..].to_vec()),
-        "set" => parse_set_command(args[2..].to_vec()),
-        "complete" => parse_complete_command(args[2..].to_vec()),
-        "view" => parse_view_command(args[2..].to_vec()),
-        "edit" => parse_edit_command(args[2..].to_vec()),
-        "help" => Ok(Command::Help),
-        _ => Err(TuduError::InvalidCommand),
+    // A list chosen after the command, as in `tudu add "Write docs" -l work`
+    match cli.list {
+        Some(list) => {
+            validate_list_name(&list)?;
+            Ok(Command::InList(list, Box::new(command)))
+        }
+        None => Ok(command),
     }
 }
 
 pub fn run(args: Vec<String>) {
-    let command = match parse_command(args) {
+    let command = match parse_command(args, config_path) {
         Ok(command) => command,
         Err(err) => return fail_with_error(err),
     };
 
     if let Err(err) = execute_command(command) {
-        return fail_with_error(err);
+        fail_with_error(err)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use chrono::{DateTime, Datelike, Local};
+    use std::path::PathBuf;
+
+    use chrono::{DateTime, Datelike, Duration, Local, NaiveTime};
 
     use super::*;
 
+    /// Parses with a config file that does not exist, so aliases in the user's config cannot change
+    /// what the args parse to
+    fn parse_without_config(args: Vec<String>) -> Result<Command, TuduError> {
+        parse_command(args, || Ok(PathBuf::from("./src/tests/no-config")))
+    }
+
     #[test]
     fn create_root_command_from_no_args() {
         let args = vec![String::from("tudu")];
@@ -213,10 +453,12 @@ mod tests {
 
         let expected_config = ViewCommand {
             date: TuduDate::new(day, month, year),
+            journal: false,
+            all_lists: false,
         };
         let expected_command = Command::View(expected_config);
 
-        let command = parse_command(args).unwrap();
+        let command = parse_without_config(args).unwrap();
 
         assert_eq!(command, expected_command);
     }
@@ -231,11 +473,16 @@ mod tests {
 
         let expected_config = AddCommand {
             task: String::from("Example task"),
+            estimate: None,
+            parent: None,
+            due: None,
+            priority: None,
+            at: None,
             date: None,
         };
         let expected_command = Command::Add(expected_config);
 
-        let command = parse_command(args).unwrap();
+        let command = parse_without_config(args).unwrap();
 
         assert_eq!(command, expected_command);
     }
@@ -251,11 +498,16 @@ mod tests {
 
         let expected_config = AddCommand {
             task: String::from("Example task"),
+            estimate: None,
+            parent: None,
+            due: None,
+            priority: None,
+            at: None,
             date: Some(TuduDate::new(10, 6, 2023)),
         };
         let expected_command = Command::Add(expected_config);
 
-        let command = parse_command(args).unwrap();
+        let command = parse_without_config(args).unwrap();
 
         assert_eq!(command, expected_command);
     }
@@ -269,12 +521,12 @@ mod tests {
         ];
 
         let expected_config = RemoveCommand {
-            index: 2,
+            index: TaskIndex::from(2),
             date: None,
         };
         let expected_command = Command::Remove(expected_config);
 
-        let command = parse_command(args).unwrap();
+        let command = parse_without_config(args).unwrap();
 
         assert_eq!(command, expected_command);
     }
@@ -289,12 +541,12 @@ mod tests {
         ];
 
         let expected_config = RemoveCommand {
-            index: 2,
+            index: TaskIndex::from(2),
             date: Some(TuduDate::new(10, 6, 2023)),
         };
         let expected_command = Command::Remove(expected_config);
 
-        let command = parse_command(args).unwrap();
+        let command = parse_without_config(args).unwrap();
         assert_eq!(command, expected_command);
     }
 
@@ -308,13 +560,13 @@ mod tests {
         ];
 
         let expected_config = SetCommand {
-            index: 3,
+            index: TaskIndex::from(3),
             state: TaskState::Started,
             date: None,
         };
         let expected_command = Command::Set(expected_config);
 
-        let command = parse_command(args).unwrap();
+        let command = parse_without_config(args).unwrap();
 
         assert_eq!(command, expected_command);
     }
@@ -330,13 +582,13 @@ mod tests {
         ];
 
         let expected_config = SetCommand {
-            index: 3,
+            index: TaskIndex::from(3),
             state: TaskState::Ignored,
             date: Some(TuduDate::new(10, 6, 2023)),
         };
         let expected_command = Command::Set(expected_config);
 
-        let command = parse_command(args).unwrap();
+        let command = parse_without_config(args).unwrap();
 
         assert_eq!(command, expected_command);
     }
@@ -350,13 +602,13 @@ mod tests {
         ];
 
         let expected_config = SetCommand {
-            index: 2,
+            index: TaskIndex::from(2),
             state: TaskState::Complete,
             date: None,
         };
         let expected_command = Command::Set(expected_config);
 
-        let command = parse_command(args).unwrap();
+        let command = parse_without_config(args).unwrap();
 
         assert_eq!(command, expected_command);
     }
@@ -371,13 +623,13 @@ mod tests {
         ];
 
         let expected_config = SetCommand {
-            index: 3,
+            index: TaskIndex::from(3),
             state: TaskState::Complete,
             date: Some(TuduDate::new(10, 6, 2023)),
         };
         let expected_command = Command::Set(expected_config);
 
-        let command = parse_command(args).unwrap();
+        let command = parse_without_config(args).unwrap();
 
         assert_eq!(command, expected_command);
     }
@@ -392,10 +644,12 @@ mod tests {
 
         let expected_config = ViewCommand {
             date: TuduDate::new(10, 6, 2023),
+            journal: false,
+            all_lists: false,
         };
         let expected_command = Command::View(expected_config);
 
-        let command = parse_command(args).unwrap();
+        let command = parse_without_config(args).unwrap();
 
         assert_eq!(command, expected_command);
     }
@@ -410,13 +664,887 @@ mod tests {
         ];
 
         let expected_config = EditCommand {
-            index: 2,
+            index: TaskIndex::from(2),
             task: String::from("Updated task"),
+            estimate: None,
+            date: None,
         };
         let expected_command = Command::Edit(expected_config);
 
-        let command = parse_command(args).unwrap();
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_completions_command_for_shell() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("completions"),
+            String::from("zsh"),
+        ];
+
+        let expected_config = CompletionsCommand { shell: Shell::Zsh };
+        let expected_command = Command::Completions(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_completions_command_without_shell_gives_error() {
+        let args = vec![String::from("tudu"), String::from("completions")];
+
+        let result = parse_without_config(args);
+
+        assert!(matches!(result, Err(TuduError::InvalidArguments(_))));
+    }
+
+    #[test]
+    fn create_add_command_with_date_flag() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("add"),
+            String::from("Example task"),
+            String::from("--date"),
+            String::from("10-6-2023"),
+        ];
+
+        let expected_config = AddCommand {
+            task: String::from("Example task"),
+            estimate: None,
+            parent: None,
+            due: None,
+            priority: None,
+            at: None,
+            date: Some(TuduDate::new(10, 6, 2023)),
+        };
+        let expected_command = Command::Add(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_add_command_without_task_gives_error() {
+        let args = vec![String::from("tudu"), String::from("add")];
+
+        let result = parse_without_config(args);
+
+        assert!(matches!(result, Err(TuduError::InvalidArguments(_))));
+    }
+
+    #[test]
+    fn create_edit_command_without_task_gives_error() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("edit"),
+            String::from("2"),
+        ];
+
+        let result = parse_without_config(args);
+
+        assert!(matches!(result, Err(TuduError::InvalidArguments(_))));
+    }
+
+    #[test]
+    fn create_set_command_with_unknown_state_gives_error() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("set"),
+            String::from("2"),
+            String::from("Q"),
+        ];
+
+        let result = parse_without_config(args);
+
+        assert_eq!(result, Err(TuduError::InvalidState));
+    }
+
+    #[test]
+    fn create_command_in_list_chosen_before_or_after_command() {
+        let args = |args: &[&str]| args.iter().map(|arg| String::from(*arg)).collect();
+        let remove_in_work = || {
+            Command::InList(
+                String::from("work"),
+                Box::new(Command::Remove(RemoveCommand {
+                    index: TaskIndex::from(2),
+                    date: None,
+                })),
+            )
+        };
+
+        let before = parse_without_config(args(&["tudu", "-l", "work", "remove", "2"]));
+        let after = parse_without_config(args(&["tudu", "remove", "2", "--list", "work"]));
+
+        assert_eq!(before, Ok(remove_in_work()));
+        assert_eq!(after, Ok(remove_in_work()));
+    }
+
+    #[test]
+    fn parse_command_reads_config_only_for_commands_tudu_does_not_have() {
+        let args = |args: &[&str]| args.iter().map(|arg| String::from(*arg)).collect();
+        // Not a config file, so reading it gives an error
+        let invalid_config = || Ok(PathBuf::from("./src/tests/2023-06-07.ics"));
+
+        let builtin = parse_command(args(&["tudu", "remove", "2"]), invalid_config);
+        let help = parse_command(args(&["tudu", "--help"]), invalid_config);
+        let alias = parse_command(args(&["tudu", "d", "2"]), invalid_config);
+
+        assert_eq!(
+            builtin,
+            Ok(Command::Remove(RemoveCommand {
+                index: TaskIndex::from(2),
+                date: None,
+            }))
+        );
+        assert!(matches!(help, Ok(Command::Help(_))));
+        assert!(matches!(alias, Err(TuduError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn create_command_in_list_with_invalid_name_gives_error() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("--list=../work"),
+            String::from("view"),
+        ];
+
+        let result = parse_without_config(args);
+
+        assert!(matches!(result, Err(TuduError::InvalidArguments(_))));
+    }
+
+    #[test]
+    fn create_unknown_command_gives_error() {
+        let args = vec![String::from("tudu"), String::from("unknown")];
+
+        let result = parse_without_config(args);
+
+        assert_eq!(result, Err(TuduError::InvalidCommand));
+    }
+
+    #[test]
+    fn create_task_candidates_command_for_date() {
+        let args = |date: &str| {
+            vec![
+                String::from("tudu"),
+                String::from("__complete"),
+                String::from("-d"),
+                String::from(date),
+            ]
+        };
+
+        let command = parse_without_config(args("7-6-2023")).unwrap();
+        let unreadable = parse_without_config(args("7-6-20x")).unwrap();
+
+        assert_eq!(
+            command,
+            Command::TaskCandidates(Some(TuduDate::new(7, 6, 2023)))
+        );
+        assert_eq!(unreadable, Command::TaskCandidates(None));
+    }
+
+    #[test]
+    fn create_help_command_for_subcommand_help_flag() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("add"),
+            String::from("--help"),
+        ];
+
+        let command = parse_without_config(args).unwrap();
+
+        match command {
+            Command::Help(usage) => assert!(usage.contains("Usage: tudu add")),
+            _ => panic!("expected help command, got {command:?}"),
+        }
+    }
+
+    #[test]
+    fn create_export_command_with_range() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("export"),
+            String::from("--format"),
+            String::from("todotxt"),
+            String::from("1-6-2023..7-6-2023"),
+        ];
+
+        let expected_config = ExportCommand {
+            format: Format::TodoTxt,
+            range: DateRange::new(TuduDate::new(1, 6, 2023), TuduDate::new(7, 6, 2023)).unwrap(),
+        };
+        let expected_command = Command::Export(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_import_command_guesses_format_from_file() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("import"),
+            String::from("todo.txt"),
+        ];
+
+        let expected_config = ImportCommand {
+            path: PathBuf::from("todo.txt"),
+            format: Format::TodoTxt,
+        };
+        let expected_command = Command::Import(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_report_command_with_range() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("report"),
+            String::from("--format"),
+            String::from("md"),
+            String::from("5-6-2023..9-6-2023"),
+        ];
+
+        let expected_config = ReportCommand {
+            format: ReportFormat::Markdown,
+            range: DateRange::new(TuduDate::new(5, 6, 2023), TuduDate::new(9, 6, 2023)).unwrap(),
+        };
+        let expected_command = Command::Report(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_standup_command_with_defaults() {
+        let args = vec![String::from("tudu"), String::from("standup")];
+
+        let expected_config = StandupCommand {
+            since: None,
+            format: StandupFormat::Markdown,
+        };
+        let expected_command = Command::Standup(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_standup_command_since_date() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("standup"),
+            String::from("--since"),
+            String::from("5-6-2023"),
+            String::from("--format"),
+            String::from("slack"),
+        ];
+
+        let expected_config = StandupCommand {
+            since: Some(TuduDate::new(5, 6, 2023)),
+            format: StandupFormat::Slack,
+        };
+        let expected_command = Command::Standup(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_start_command() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("start"),
+            String::from("2"),
+            String::from("7-6-2023"),
+        ];
+
+        let expected_config = StartCommand {
+            index: 2,
+            date: Some(TuduDate::new(7, 6, 2023)),
+        };
+        let expected_command = Command::Start(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_stop_command_without_index() {
+        let args = vec![String::from("tudu"), String::from("stop")];
+
+        let expected_config = StopCommand {
+            index: None,
+            date: None,
+        };
+        let expected_command = Command::Stop(expected_config);
+
+        let command = parse_without_config(args).unwrap();
 
         assert_eq!(command, expected_command);
     }
+
+    #[test]
+    fn create_stop_command_with_index_and_date() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("stop"),
+            String::from("3"),
+            String::from("--date"),
+            String::from("7-6-2023"),
+        ];
+
+        let expected_config = StopCommand {
+            index: Some(3),
+            date: Some(TuduDate::new(7, 6, 2023)),
+        };
+        let expected_command = Command::Stop(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_timesheet_command() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("timesheet"),
+            String::from("5-6-2023..9-6-2023"),
+        ];
+
+        let expected_config = TimesheetCommand {
+            range: DateRange::new(TuduDate::new(5, 6, 2023), TuduDate::new(9, 6, 2023)).unwrap(),
+        };
+        let expected_command = Command::Timesheet(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_add_command_with_estimate() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("add"),
+            String::from("Example task ~1h30m"),
+        ];
+
+        let expected_config = AddCommand {
+            task: String::from("Example task"),
+            estimate: Some(Duration::minutes(90)),
+            parent: None,
+            due: None,
+            priority: None,
+            at: None,
+            date: None,
+        };
+        let expected_command = Command::Add(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_edit_command_keeps_word_that_is_not_an_estimate() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("edit"),
+            String::from("1"),
+            String::from("Example task ~soon"),
+        ];
+
+        let expected_config = EditCommand {
+            index: TaskIndex::from(1),
+            task: String::from("Example task ~soon"),
+            estimate: None,
+            date: None,
+        };
+        let expected_command = Command::Edit(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_add_command_with_parent() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("add"),
+            String::from("Tag the commit"),
+            String::from("--parent"),
+            String::from("3"),
+        ];
+
+        let expected_config = AddCommand {
+            task: String::from("Tag the commit"),
+            estimate: None,
+            parent: Some(3),
+            due: None,
+            priority: None,
+            at: None,
+            date: None,
+        };
+        let expected_command = Command::Add(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_complete_command_for_subtask() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("complete"),
+            String::from("3.2"),
+        ];
+
+        let expected_config = SetCommand {
+            index: TaskIndex {
+                task: 3,
+                subtask: Some(2),
+            },
+            state: TaskState::Complete,
+            date: None,
+        };
+        let expected_command = Command::Set(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_remove_command_with_invalid_index_gives_error() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("remove"),
+            String::from("3.x"),
+        ];
+
+        let result = parse_without_config(args);
+
+        assert!(matches!(result, Err(TuduError::InvalidArguments(_))));
+    }
+
+    #[test]
+    fn create_depend_command_across_days() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("depend"),
+            String::from("4"),
+            String::from("2.1"),
+            String::from("--from"),
+            String::from("5-6-2023"),
+        ];
+
+        let expected_config = DependCommand {
+            index: TaskIndex::from(4),
+            prerequisite: TaskIndex {
+                task: 2,
+                subtask: Some(1),
+            },
+            date: None,
+            prerequisite_date: Some(TuduDate::new(5, 6, 2023)),
+            remove: false,
+        };
+        let expected_command = Command::Depend(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_graph_command_defaults_to_today() {
+        let args = vec![String::from("tudu"), String::from("graph")];
+
+        let expected_config = GraphCommand {
+            range: DateRange::single(TuduDate::today()),
+        };
+        let expected_command = Command::Graph(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_note_command_for_subtask_on_date() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("note"),
+            String::from("3.1"),
+            String::from("7-6-2023"),
+        ];
+
+        let expected_config = NoteCommand {
+            index: TaskIndex {
+                task: 3,
+                subtask: Some(1),
+            },
+            date: Some(TuduDate::new(7, 6, 2023)),
+        };
+        let expected_command = Command::Note(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_show_command() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("show"),
+            String::from("2"),
+        ];
+
+        let expected_config = ShowCommand {
+            index: TaskIndex::from(2),
+            date: None,
+        };
+        let expected_command = Command::Show(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_view_command_with_journal() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("view"),
+            String::from("--journal"),
+            String::from("7-6-2023"),
+        ];
+
+        let expected_config = ViewCommand {
+            date: TuduDate::new(7, 6, 2023),
+            journal: true,
+            all_lists: false,
+        };
+        let expected_command = Command::View(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_journal_command_with_message() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("journal"),
+            String::from("-m"),
+            String::from("Fixed the release script"),
+        ];
+
+        let expected_config = JournalCommand {
+            message: Some(String::from("Fixed the release script")),
+            date: None,
+        };
+        let expected_command = Command::Journal(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_search_command_with_range() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("search"),
+            String::from("release"),
+            String::from("1-6-2023..7-6-2023"),
+        ];
+
+        let expected_config = SearchCommand {
+            query: String::from("release"),
+            range: Some(
+                DateRange::new(TuduDate::new(1, 6, 2023), TuduDate::new(7, 6, 2023)).unwrap(),
+            ),
+        };
+        let expected_command = Command::Search(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_add_command_with_due_date() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("add"),
+            String::from("Send invoice"),
+            String::from("--due"),
+            String::from("9-6-2023"),
+        ];
+
+        let expected_config = AddCommand {
+            task: String::from("Send invoice"),
+            estimate: None,
+            parent: None,
+            due: Some(TuduDate::new(9, 6, 2023)),
+            priority: None,
+            at: None,
+            date: None,
+        };
+        let expected_command = Command::Add(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_due_command_on_date() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("due"),
+            String::from("2"),
+            String::from("9-6-2023"),
+            String::from("7-6-2023"),
+        ];
+
+        let expected_config = DueCommand {
+            index: TaskIndex::from(2),
+            due: Some(TuduDate::new(9, 6, 2023)),
+            date: Some(TuduDate::new(7, 6, 2023)),
+        };
+        let expected_command = Command::Due(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_due_command_clearing_due_date() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("due"),
+            String::from("2"),
+            String::from("--clear"),
+        ];
+
+        let expected_config = DueCommand {
+            index: TaskIndex::from(2),
+            due: None,
+            date: None,
+        };
+        let expected_command = Command::Due(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_due_command_without_due_date_gives_error() {
+        let args = vec![String::from("tudu"), String::from("due"), String::from("2")];
+
+        assert!(parse_without_config(args).is_err());
+    }
+
+    #[test]
+    fn create_priority_command_ignoring_case() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("priority"),
+            String::from("1.2"),
+            String::from("b"),
+        ];
+
+        let expected_config = PriorityCommand {
+            index: TaskIndex {
+                task: 1,
+                subtask: Some(2),
+            },
+            priority: Some('B'),
+            date: None,
+        };
+        let expected_command = Command::Priority(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_next_command_with_defaults() {
+        let args = vec![String::from("tudu"), String::from("next")];
+
+        let expected_config = NextCommand {
+            count: 3,
+            start: false,
+            why: false,
+        };
+        let expected_command = Command::Next(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_next_command_starting_top_task() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("next"),
+            String::from("-n"),
+            String::from("1"),
+            String::from("--start"),
+            String::from("--why"),
+        ];
+
+        let expected_config = NextCommand {
+            count: 1,
+            start: true,
+            why: true,
+        };
+        let expected_command = Command::Next(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_log_command_for_date() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("log"),
+            String::from("7-6-2023"),
+        ];
+
+        let expected_config = LogCommand {
+            date: Some(TuduDate::new(7, 6, 2023)),
+        };
+        let expected_command = Command::Log(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_merge_command_as_git_runs_it() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("merge"),
+            String::from(".merge_file_base"),
+            String::from(".merge_file_ours"),
+            String::from(".merge_file_theirs"),
+        ];
+
+        let expected_config = MergeCommand {
+            base: PathBuf::from(".merge_file_base"),
+            ours: PathBuf::from(".merge_file_ours"),
+            theirs: PathBuf::from(".merge_file_theirs"),
+            output: None,
+        };
+        let expected_command = Command::Merge(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_serve_command_on_localhost_by_default() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("serve"),
+            String::from("--port"),
+            String::from("8080"),
+        ];
+
+        let expected_config = ServeCommand {
+            host: String::from("127.0.0.1"),
+            port: 8080,
+            token: None,
+        };
+        let expected_command = Command::Serve(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_at_command_with_time() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("at"),
+            String::from("2"),
+            String::from("14:30"),
+        ];
+
+        let expected_config = AtCommand {
+            index: TaskIndex::from(2),
+            at: Some(NaiveTime::from_hms_opt(14, 30, 0).unwrap()),
+            date: None,
+        };
+        let expected_command = Command::At(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_remind_command_with_command_notifier() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("remind"),
+            String::from("--notify"),
+            String::from("command"),
+            String::from("--command"),
+            String::from("say \"$TUDU_REMINDER_TASK\""),
+        ];
+
+        let expected_config = RemindCommand {
+            notifier: Notifier::Command(String::from("say \"$TUDU_REMINDER_TASK\"")),
+            once: false,
+        };
+        let expected_command = Command::Remind(expected_config);
+
+        let command = parse_without_config(args).unwrap();
+
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn create_remind_command_with_command_notifier_and_no_command_gives_error() {
+        let args = vec![
+            String::from("tudu"),
+            String::from("remind"),
+            String::from("--notify"),
+            String::from("command"),
+        ];
+
+        let result = parse_without_config(args);
+
+        assert!(matches!(result, Err(TuduError::InvalidArguments(_))));
+    }
 }
@@ -0,0 +1,179 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::error::TuduError;
+use crate::storage::tasks_root;
+
+/// The list kept in the tasks directory itself, which is used when no other list is chosen
+pub const MAIN_LIST: &str = "main";
+pub const LISTS_DIRECTORY: &str = "lists";
+
+thread_local! {
+    /// The list the files of tasks are read from and written to, which is set from the default list
+    /// the first time it is needed. Each thread has its own, so switching lists on one does not
+    /// change the files another is using.
+    static CURRENT_LIST: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+pub fn current_list() -> Result<String, TuduError> {
+    if let Some(list) = CURRENT_LIST.with_borrow(Clone::clone) {
+        return Ok(list);
+    }
+
+    let list = default_list()?;
+    CURRENT_LIST.set(Some(list.clone()));
+
+    Ok(list)
+}
+
+/// The list set in the TUDU_LIST env variable, falling back to the `list` setting in the config and
+/// then the main list
+fn default_list() -> Result<String, TuduError> {
+    let list = match env::var("TUDU_LIST") {
+        Ok(list) => list,
+        Err(_) => Config::load()?
+            .list
+            .unwrap_or_else(|| String::from(MAIN_LIST)),
+    };
+
+    validate_list_name(&list)?;
+
+    Ok(list)
+}
+
+/// Runs with the files of another list, going back to the current list afterwards
+pub fn with_list<T>(
+    list: &str,
+    run: impl FnOnce() -> Result<T, TuduError>,
+) -> Result<T, TuduError> {
+    let _list = ListGuard::switch(list)?;
+
+    run()
+}
+
+/// Switches the thread to the files of another list until it is dropped, going back to the list
+/// before it even when what ran in between panicked
+#[must_use]
+pub struct ListGuard {
+    previous: Option<String>,
+}
+
+impl ListGuard {
+    pub fn switch(list: &str) -> Result<ListGuard, TuduError> {
+        validate_list_name(list)?;
+
+        Ok(ListGuard {
+            previous: CURRENT_LIST.replace(Some(String::from(list))),
+        })
+    }
+}
+
+impl Drop for ListGuard {
+    fn drop(&mut self) {
+        CURRENT_LIST.set(self.previous.take());
+    }
+}
+
+/// The folder a list keeps its tasks in, which for lists other than the main list is named after
+/// the list in the `lists` folder of the tasks directory
+pub fn list_directory(list: &str) -> Result<PathBuf, TuduError> {
+    match list {
+        MAIN_LIST => tasks_root(),
+        list => Ok(tasks_root()?.join(LISTS_DIRECTORY).join(list)),
+    }
+}
+
+/// The main list followed by the other lists in order of name
+pub fn list_names() -> Result<Vec<String>, TuduError> {
+    let entries = match fs::read_dir(tasks_root()?.join(LISTS_DIRECTORY)) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![String::from(MAIN_LIST)]),
+        Err(_) => return Err(TuduError::FailedToReadFile),
+    };
+
+    let mut lists: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|list| list != MAIN_LIST && validate_list_name(list).is_ok())
+        .collect();
+
+    lists.sort();
+    lists.insert(0, String::from(MAIN_LIST));
+
+    Ok(lists)
+}
+
+/// List names become folder names, so they are kept to letters, numbers, `-` and `_`
+pub fn validate_list_name(list: &str) -> Result<(), TuduError> {
+    let is_valid = !list.is_empty()
+        && list
+            .chars()
+            .all(|character| character.is_alphanumeric() || character == '-' || character == '_');
+
+    match is_valid {
+        true => Ok(()),
+        false => Err(TuduError::InvalidArguments(format!(
+            "`{list}` is not a list name, name lists with letters, numbers, - and _"
+        ))),
+    }
+}
+
+/// Lists the lists, marking the current one
+pub fn render_lists(lists: &[String], current_list: &str) -> String {
+    lists
+        .iter()
+        .map(|list| match list == current_list {
+            true => format!("* {list}\n"),
+            false => format!("  {list}\n"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_list_name_keeps_names_to_one_folder() {
+        assert_eq!(validate_list_name("work"), Ok(()));
+        assert_eq!(validate_list_name("side-project_2"), Ok(()));
+        assert!(validate_list_name("").is_err());
+        assert!(validate_list_name("../work").is_err());
+        assert!(validate_list_name(".hidden").is_err());
+    }
+
+    #[test]
+    fn with_list_goes_back_to_previous_list_after_panic() {
+        let _work = ListGuard::switch("work").unwrap();
+
+        let result = std::panic::catch_unwind(|| {
+            with_list("personal", || -> Result<(), TuduError> { panic!("failed") })
+        });
+        let list_in_other_thread = std::thread::spawn(|| {
+            let _personal = ListGuard::switch("personal").unwrap();
+            current_list()
+        })
+        .join()
+        .unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(current_list(), Ok(String::from("work")));
+        assert_eq!(list_in_other_thread, Ok(String::from("personal")));
+    }
+
+    #[test]
+    fn render_lists_marks_current_list() {
+        let lists = vec![
+            String::from("main"),
+            String::from("personal"),
+            String::from("work"),
+        ];
+
+        assert_eq!(render_lists(&lists, "work"), "  main\n  personal\n* work\n");
+    }
+}
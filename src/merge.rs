@@ -0,0 +1,403 @@
+use crate::dependency::Dependency;
+use crate::model::{Task, TaskState};
+
+/// The tasks of a day merged from two versions that were both changed from a common base, along
+/// with a description of every change that was made differently on both sides
+#[derive(Debug, PartialEq, Eq)]
+pub struct MergedTasks {
+    pub tasks: Vec<Task>,
+    pub conflicts: Vec<String>,
+}
+
+/// Merges the changes made to a day's tasks on both sides since the base version. Tasks keep our
+/// order, with tasks only added on their side following. A task changed on one side and removed
+/// on the other is kept with its changes.
+///
+/// When both sides change the same thing differently, the state with the highest precedence wins,
+/// so a completed task stays completed, and our version of anything else is kept.
+pub fn merge_tasks(base: &[Task], ours: &[Task], theirs: &[Task]) -> MergedTasks {
+    let mut conflicts = Vec::new();
+    let tasks = merge_task_lists(base, ours, theirs, &mut conflicts);
+
+    MergedTasks { tasks, conflicts }
+}
+
+fn merge_task_lists(
+    base: &[Task],
+    ours: &[Task],
+    theirs: &[Task],
+    conflicts: &mut Vec<String>,
+) -> Vec<Task> {
+    let our_matches = match_to_base(base, ours);
+    let their_matches = match_to_base(base, theirs);
+
+    let find_theirs = |base_index: usize| {
+        their_matches
+            .iter()
+            .position(|matched| *matched == Some(base_index))
+            .map(|index| &theirs[index])
+    };
+
+    let mut merged = Vec::new();
+
+    for (task, matched) in ours.iter().zip(our_matches.iter()) {
+        match matched.map(|base_index| (&base[base_index], find_theirs(base_index))) {
+            Some((base_task, Some(their_task))) => {
+                merged.push(merge_task(base_task, task, their_task, conflicts))
+            }
+            // Removed on their side, so it is only kept if we changed it
+            Some((base_task, None)) if task != base_task => merged.push(task.clone()),
+            Some((_, None)) => {}
+            None => merged.push(task.clone()),
+        }
+    }
+
+    for (task, matched) in theirs.iter().zip(their_matches.iter()) {
+        match matched {
+            // Removed on our side, so it is only kept if they changed it
+            Some(base_index) if !our_matches.contains(&Some(*base_index)) => {
+                if task != &base[*base_index] {
+                    merged.push(task.clone());
+                }
+            }
+            Some(_) => {}
+            // The same task added on both sides is only kept once
+            None => {
+                let is_added_by_us = ours
+                    .iter()
+                    .zip(our_matches.iter())
+                    .any(|(our_task, matched)| matched.is_none() && our_task.task == task.task);
+
+                if !is_added_by_us {
+                    merged.push(task.clone());
+                }
+            }
+        }
+    }
+
+    merged
+}
+
+fn has_same_id(task: &Task, base_task: &Task) -> bool {
+    task.id.is_some() && task.id == base_task.id
+}
+
+fn has_other_id(task: &Task, base_task: &Task) -> bool {
+    task.id.is_some() && base_task.id.is_some() && task.id != base_task.id
+}
+
+/// Whether a task at an index on a side came from the task at an index in the base version
+type IsSameTask = dyn Fn(usize, &Task, usize, &Task) -> bool;
+
+/// Finds the task in the base version that each task on a side came from, first by id, then by
+/// description and then, for tasks that were edited, by position
+fn match_to_base(base: &[Task], side: &[Task]) -> Vec<Option<usize>> {
+    let mut matches: Vec<Option<usize>> = vec![None; side.len()];
+    let mut is_base_matched = vec![false; base.len()];
+
+    let passes: [&IsSameTask; 3] = [
+        &|_, task, _, base_task| has_same_id(task, base_task),
+        &|_, task, _, base_task| !has_other_id(task, base_task) && task.task == base_task.task,
+        &|index, task, base_index, base_task| !has_other_id(task, base_task) && index == base_index,
+    ];
+
+    for is_same_task in passes {
+        for (index, task) in side.iter().enumerate() {
+            if matches[index].is_some() {
+                continue;
+            }
+
+            let found = base.iter().enumerate().position(|(base_index, base_task)| {
+                !is_base_matched[base_index] && is_same_task(index, task, base_index, base_task)
+            });
+
+            if let Some(base_index) = found {
+                matches[index] = Some(base_index);
+                is_base_matched[base_index] = true;
+            }
+        }
+    }
+
+    matches
+}
+
+fn merge_task(base: &Task, ours: &Task, theirs: &Task, conflicts: &mut Vec<String>) -> Task {
+    let description = &ours.task;
+
+    Task {
+        task: merge_field(
+            description,
+            "description",
+            &base.task,
+            &ours.task,
+            &theirs.task,
+            conflicts,
+        ),
+        state: match merge_value(&base.state, &ours.state, &theirs.state) {
+            Some(state) => state,
+            None => {
+                conflicts.push(format!(
+                    "`{description}` had its state changed on both sides"
+                ));
+                highest_precedence(&ours.state, &theirs.state)
+            }
+        },
+        estimate: merge_field(
+            description,
+            "estimate",
+            &base.estimate,
+            &ours.estimate,
+            &theirs.estimate,
+            conflicts,
+        ),
+        subtasks: merge_task_lists(&base.subtasks, &ours.subtasks, &theirs.subtasks, conflicts),
+        id: merge_field(description, "id", &base.id, &ours.id, &theirs.id, conflicts),
+        after: merge_dependencies(&base.after, &ours.after, &theirs.after),
+        due: merge_field(
+            description,
+            "due date",
+            &base.due,
+            &ours.due,
+            &theirs.due,
+            conflicts,
+        ),
+        priority: merge_field(
+            description,
+            "priority",
+            &base.priority,
+            &ours.priority,
+            &theirs.priority,
+            conflicts,
+        ),
+        at: merge_field(
+            description,
+            "reminder",
+            &base.at,
+            &ours.at,
+            &theirs.at,
+            conflicts,
+        ),
+    }
+}
+
+/// The value changed on either side, or `None` if both sides changed it differently
+fn merge_value<T: PartialEq + Clone>(base: &T, ours: &T, theirs: &T) -> Option<T> {
+    if ours == theirs || theirs == base {
+        Some(ours.clone())
+    } else if ours == base {
+        Some(theirs.clone())
+    } else {
+        None
+    }
+}
+
+/// Merges a value that keeps our version when both sides changed it differently
+fn merge_field<T: PartialEq + Clone>(
+    description: &str,
+    field: &str,
+    base: &T,
+    ours: &T,
+    theirs: &T,
+    conflicts: &mut Vec<String>,
+) -> T {
+    merge_value(base, ours, theirs).unwrap_or_else(|| {
+        conflicts.push(format!(
+            "`{description}` had its {field} changed on both sides"
+        ));
+        ours.clone()
+    })
+}
+
+/// Keeps the dependencies added on either side, without those removed on either side
+fn merge_dependencies(
+    base: &[Dependency],
+    ours: &[Dependency],
+    theirs: &[Dependency],
+) -> Vec<Dependency> {
+    let mut merged: Vec<Dependency> = ours
+        .iter()
+        .filter(|dependency| theirs.contains(dependency) || !base.contains(dependency))
+        .cloned()
+        .collect();
+
+    merged.extend(
+        theirs
+            .iter()
+            .filter(|dependency| !base.contains(dependency) && !ours.contains(dependency))
+            .cloned(),
+    );
+
+    merged
+}
+
+/// Finished states beat unfinished ones, so work done on either side is never undone
+fn highest_precedence(ours: &TaskState, theirs: &TaskState) -> TaskState {
+    let precedence = |state: &TaskState| match state {
+        TaskState::Complete => 4,
+        TaskState::Ignored => 3,
+        TaskState::Forwarded => 2,
+        TaskState::Started => 1,
+        TaskState::NotStarted => 0,
+    };
+
+    match precedence(theirs) > precedence(ours) {
+        true => theirs.clone(),
+        false => ours.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date::TuduDate;
+
+    fn task(task: &str, state: TaskState) -> Task {
+        Task::new(String::from(task), state)
+    }
+
+    #[test]
+    fn merge_tasks_keeps_additions_from_both_sides() {
+        let base = vec![task("Write docs", TaskState::NotStarted)];
+        let ours = vec![
+            task("Write docs", TaskState::NotStarted),
+            task("From the laptop", TaskState::NotStarted),
+            task("On both", TaskState::NotStarted),
+        ];
+        let theirs = vec![
+            task("Write docs", TaskState::NotStarted),
+            task("From the desktop", TaskState::NotStarted),
+            task("On both", TaskState::NotStarted),
+        ];
+
+        let merged = merge_tasks(&base, &ours, &theirs);
+
+        assert_eq!(
+            merged,
+            MergedTasks {
+                tasks: vec![
+                    task("Write docs", TaskState::NotStarted),
+                    task("From the laptop", TaskState::NotStarted),
+                    task("On both", TaskState::NotStarted),
+                    task("From the desktop", TaskState::NotStarted),
+                ],
+                conflicts: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn merge_tasks_combines_changes_to_different_tasks_and_fields() {
+        let base = vec![
+            task("Write docs", TaskState::NotStarted),
+            task("Fix bug", TaskState::NotStarted),
+            task("Tidy up", TaskState::NotStarted),
+        ];
+        let ours = vec![
+            task("Write the docs", TaskState::NotStarted),
+            task("Fix bug", TaskState::Started),
+            task("Tidy up", TaskState::NotStarted),
+        ];
+        let theirs = vec![
+            Task {
+                due: Some(TuduDate::new(9, 6, 2023)),
+                ..task("Write docs", TaskState::Complete)
+            },
+            task("Fix bug", TaskState::NotStarted),
+        ];
+
+        let merged = merge_tasks(&base, &ours, &theirs);
+
+        assert_eq!(
+            merged,
+            MergedTasks {
+                tasks: vec![
+                    Task {
+                        due: Some(TuduDate::new(9, 6, 2023)),
+                        ..task("Write the docs", TaskState::Complete)
+                    },
+                    task("Fix bug", TaskState::Started),
+                ],
+                conflicts: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn merge_tasks_resolves_conflicting_states_by_precedence() {
+        let base = vec![task("Release", TaskState::NotStarted)];
+        let ours = vec![task("Release", TaskState::Started)];
+        let theirs = vec![task("Release", TaskState::Complete)];
+
+        let merged = merge_tasks(&base, &ours, &theirs);
+
+        assert_eq!(merged.tasks, vec![task("Release", TaskState::Complete)]);
+        assert_eq!(
+            merged.conflicts,
+            vec!["`Release` had its state changed on both sides"]
+        );
+    }
+
+    #[test]
+    fn merge_tasks_keeps_task_changed_on_one_side_and_removed_on_other() {
+        let base = vec![
+            task("Changed here", TaskState::NotStarted),
+            task("Untouched", TaskState::NotStarted),
+        ];
+        let ours = vec![
+            task("Changed here", TaskState::Started),
+            task("Untouched", TaskState::NotStarted),
+        ];
+        let theirs = vec![];
+
+        let merged = merge_tasks(&base, &ours, &theirs);
+
+        assert_eq!(merged.tasks, vec![task("Changed here", TaskState::Started)]);
+    }
+
+    #[test]
+    fn merge_tasks_merges_subtasks_and_dependencies() {
+        let earlier = TuduDate::new(5, 6, 2023);
+        let dependency = |id: &str| Dependency {
+            date: earlier.clone(),
+            id: String::from(id),
+        };
+        let parent = |subtasks: Vec<Task>, after: Vec<Dependency>| Task {
+            subtasks,
+            after,
+            ..task("Release", TaskState::Started)
+        };
+
+        let base = vec![parent(
+            vec![task("Tag", TaskState::NotStarted)],
+            vec![dependency("spec"), dependency("review")],
+        )];
+        let ours = vec![parent(
+            vec![task("Tag", TaskState::Complete)],
+            vec![
+                dependency("spec"),
+                dependency("review"),
+                dependency("tests"),
+            ],
+        )];
+        let theirs = vec![parent(
+            vec![
+                task("Tag", TaskState::NotStarted),
+                task("Publish", TaskState::NotStarted),
+            ],
+            vec![dependency("spec")],
+        )];
+
+        let merged = merge_tasks(&base, &ours, &theirs);
+
+        assert_eq!(
+            merged.tasks,
+            vec![parent(
+                vec![
+                    task("Tag", TaskState::Complete),
+                    task("Publish", TaskState::NotStarted)
+                ],
+                vec![dependency("spec"), dependency("tests")],
+            )]
+        );
+    }
+}
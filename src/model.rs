@@ -1,4 +1,16 @@
-use crate::storage::{create_filepath, parse_task_file, write_tasks_to_file};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use chrono::{Duration, NaiveTime};
+
+use crate::completions::Shell;
+use crate::daemon::load_tasks;
+use crate::date::DateRange;
+use crate::dependency::{new_task_id, Dependency, BLOCKED_ICON};
+use crate::reminders::Notifier;
+use crate::report::ReportFormat;
+use crate::standup::StandupFormat;
+use crate::storage::{create_filepath, fingerprint_file, write_tasks_to_file};
 use crate::TuduDate;
 use crate::TuduError;
 
@@ -9,7 +21,44 @@ pub enum Command {
     Set(SetCommand),
     Edit(EditCommand),
     View(ViewCommand),
-    Help,
+    Completions(CompletionsCommand),
+    /// The tasks of a day offered by the completion scripts, with `None` when the date they were
+    /// given cannot be read, so that nothing is offered
+    TaskCandidates(Option<TuduDate>),
+    Export(ExportCommand),
+    Import(ImportCommand),
+    Report(ReportCommand),
+    Standup(StandupCommand),
+    Start(StartCommand),
+    Stop(StopCommand),
+    Timesheet(TimesheetCommand),
+    Depend(DependCommand),
+    Graph(GraphCommand),
+    Note(NoteCommand),
+    Show(ShowCommand),
+    Journal(JournalCommand),
+    Search(SearchCommand),
+    Due(DueCommand),
+    Overdue,
+    Priority(PriorityCommand),
+    Next(NextCommand),
+    Log(LogCommand),
+    Sync,
+    Merge(MergeCommand),
+    Serve(ServeCommand),
+    Daemon,
+    At(AtCommand),
+    Remind(RemindCommand),
+    Snooze(SnoozeCommand),
+    Script(ScriptCommand),
+    Alias,
+    /// The commands an alias runs, one after another
+    Macro(Vec<Command>),
+    /// A command run with the tasks of a list other than the current one
+    InList(String, Box<Command>),
+    Lists,
+    Move(MoveCommand),
+    Help(String),
 }
 
 #[derive(Eq, PartialEq, Debug, Clone)]
@@ -21,21 +70,78 @@ pub enum TaskState {
     Ignored,
 }
 
+impl TaskState {
+    pub const ALL: [TaskState; 5] = [
+        TaskState::NotStarted,
+        TaskState::Started,
+        TaskState::Complete,
+        TaskState::Forwarded,
+        TaskState::Ignored,
+    ];
+
+    /// Reads a state from the single letter code used on the command line and in task files
+    pub fn from_code(code: &str) -> Option<TaskState> {
+        match code {
+            "N" => Some(TaskState::NotStarted),
+            "S" => Some(TaskState::Started),
+            "C" => Some(TaskState::Complete),
+            "F" => Some(TaskState::Forwarded),
+            "X" => Some(TaskState::Ignored),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            TaskState::NotStarted => "N",
+            TaskState::Started => "S",
+            TaskState::Complete => "C",
+            TaskState::Forwarded => "F",
+            TaskState::Ignored => "X",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TaskState::NotStarted => "Not started",
+            TaskState::Started => "Started",
+            TaskState::Complete => "Completed",
+            TaskState::Forwarded => "Carried forward",
+            TaskState::Ignored => "Not doing",
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            TaskState::NotStarted => "◯",
+            TaskState::Started => "◐",
+            TaskState::Complete => "●",
+            TaskState::Forwarded => "►",
+            TaskState::Ignored => "x",
+        }
+    }
+}
+
 #[derive(Eq, PartialEq, Debug)]
 pub struct AddCommand {
     pub task: String,
+    pub estimate: Option<Duration>,
+    pub parent: Option<usize>,
+    pub due: Option<TuduDate>,
+    pub priority: Option<char>,
+    pub at: Option<NaiveTime>,
     pub date: Option<TuduDate>,
 }
 
 #[derive(Eq, PartialEq, Debug)]
 pub struct RemoveCommand {
-    pub index: usize,
+    pub index: TaskIndex,
     pub date: Option<TuduDate>,
 }
 
 #[derive(Eq, PartialEq, Debug)]
 pub struct SetCommand {
-    pub index: usize,
+    pub index: TaskIndex,
     pub date: Option<TuduDate>,
     pub state: TaskState,
 }
@@ -43,24 +149,328 @@ pub struct SetCommand {
 #[derive(Eq, PartialEq, Debug)]
 pub struct ViewCommand {
     pub date: TuduDate,
+    pub journal: bool,
+    pub all_lists: bool,
 }
 
 #[derive(Eq, PartialEq, Debug)]
 pub struct EditCommand {
-    pub index: usize,
+    pub index: TaskIndex,
     pub task: String,
+    pub estimate: Option<Duration>,
+    pub date: Option<TuduDate>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct CompletionsCommand {
+    pub shell: Shell,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Format {
+    TodoTxt,
+    Ics,
+    Csv,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Result<Format, TuduError> {
+        match name {
+            "todotxt" => Ok(Format::TodoTxt),
+            "ics" => Ok(Format::Ics),
+            "csv" => Ok(Format::Csv),
+            _ => Err(TuduError::InvalidArguments(format!(
+                "`{name}` is not a supported format"
+            ))),
+        }
+    }
+
+    /// Guesses the format of a file from its extension
+    pub fn from_path(path: &Path) -> Result<Format, TuduError> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("txt") => Ok(Format::TodoTxt),
+            Some("ics") => Ok(Format::Ics),
+            Some("csv") => Ok(Format::Csv),
+            _ => Err(TuduError::InvalidArguments(String::from(
+                "could not tell the format of the file, specify it with --format",
+            ))),
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct ExportCommand {
+    pub format: Format,
+    pub range: DateRange,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct ImportCommand {
+    pub path: PathBuf,
+    pub format: Format,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct ReportCommand {
+    pub format: ReportFormat,
+    pub range: DateRange,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct StandupCommand {
+    pub since: Option<TuduDate>,
+    pub format: StandupFormat,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct StartCommand {
+    pub index: usize,
     pub date: Option<TuduDate>,
 }
 
+#[derive(Eq, PartialEq, Debug)]
+pub struct StopCommand {
+    pub index: Option<usize>,
+    pub date: Option<TuduDate>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct TimesheetCommand {
+    pub range: DateRange,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct DependCommand {
+    pub index: TaskIndex,
+    pub prerequisite: TaskIndex,
+    pub date: Option<TuduDate>,
+    pub prerequisite_date: Option<TuduDate>,
+    pub remove: bool,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct GraphCommand {
+    pub range: DateRange,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct NoteCommand {
+    pub index: TaskIndex,
+    pub date: Option<TuduDate>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct ShowCommand {
+    pub index: TaskIndex,
+    pub date: Option<TuduDate>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct JournalCommand {
+    pub message: Option<String>,
+    pub date: Option<TuduDate>,
+}
+
+/// Sets the due date of a task, or clears it when there is no due date
+#[derive(Eq, PartialEq, Debug)]
+pub struct DueCommand {
+    pub index: TaskIndex,
+    pub due: Option<TuduDate>,
+    pub date: Option<TuduDate>,
+}
+
+/// Sets the priority of a task, or clears it when there is no priority
+#[derive(Eq, PartialEq, Debug)]
+pub struct PriorityCommand {
+    pub index: TaskIndex,
+    pub priority: Option<char>,
+    pub date: Option<TuduDate>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct NextCommand {
+    pub count: usize,
+    pub start: bool,
+    pub why: bool,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct LogCommand {
+    pub date: Option<TuduDate>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct MergeCommand {
+    pub base: PathBuf,
+    pub ours: PathBuf,
+    pub theirs: PathBuf,
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct AtCommand {
+    pub index: TaskIndex,
+    pub at: Option<NaiveTime>,
+    pub date: Option<TuduDate>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct RemindCommand {
+    pub notifier: Notifier,
+    pub once: bool,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct SnoozeCommand {
+    pub index: TaskIndex,
+    pub duration: Duration,
+    pub date: Option<TuduDate>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct MoveCommand {
+    pub index: usize,
+    pub to: String,
+    pub date: Option<TuduDate>,
+}
+
+/// A command that is not built in, run by the user's script of the same name
+#[derive(Eq, PartialEq, Debug)]
+pub struct ScriptCommand {
+    pub name: String,
+    pub script: PathBuf,
+    pub args: Vec<String>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct ServeCommand {
+    pub host: String,
+    pub port: u16,
+    pub token: Option<String>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct SearchCommand {
+    pub query: String,
+    pub range: Option<DateRange>,
+}
+
+/// The user facing index of a task, which starts from 1, or of a subtask within it such as `3.1`
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct TaskIndex {
+    pub task: usize,
+    pub subtask: Option<usize>,
+}
+
+impl TaskIndex {
+    pub fn parse(index: &str) -> Result<TaskIndex, TuduError> {
+        let invalid = || {
+            TuduError::InvalidArguments(format!("`{index}` is not a task number such as 3 or 3.1"))
+        };
+
+        let (task, subtask) = match index.split_once('.') {
+            Some((task, subtask)) => (task, Some(subtask)),
+            None => (index, None),
+        };
+
+        let task = task.parse().map_err(|_| invalid())?;
+        let subtask = subtask
+            .map(|subtask| subtask.parse().map_err(|_| invalid()))
+            .transpose()?;
+
+        Ok(TaskIndex { task, subtask })
+    }
+}
+
+impl From<usize> for TaskIndex {
+    fn from(task: usize) -> TaskIndex {
+        TaskIndex {
+            task,
+            subtask: None,
+        }
+    }
+}
+
+impl fmt::Display for TaskIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.subtask {
+            Some(subtask) => write!(f, "{}.{subtask}", self.task),
+            None => write!(f, "{}", self.task),
+        }
+    }
+}
+
+/// Why a task written over several lines is refused
+pub const MULTILINE_TASK: &str = "the task spans more than one line";
+
+/// Day files hold one task per line, so a task cannot be written over several
+pub fn check_single_line(task: &str) -> Result<(), TuduError> {
+    match task.contains(['\r', '\n']) {
+        true => Err(TuduError::InvalidArguments(String::from(MULTILINE_TASK))),
+        false => Ok(()),
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Task {
     pub task: String,
     pub state: TaskState,
+    pub estimate: Option<Duration>,
+    pub subtasks: Vec<Task>,
+    pub id: Option<String>,
+    pub after: Vec<Dependency>,
+    pub due: Option<TuduDate>,
+    pub priority: Option<char>,
+    pub at: Option<NaiveTime>,
 }
 
 impl Task {
     pub fn new(task: String, state: TaskState) -> Task {
-        Task { task, state }
+        Task {
+            task,
+            state,
+            estimate: None,
+            subtasks: Vec::new(),
+            id: None,
+            after: Vec::new(),
+            due: None,
+            priority: None,
+            at: None,
+        }
+    }
+
+    /// Checks the task and its subtasks can each be written on a line of their own
+    pub fn check(&self) -> Result<(), TuduError> {
+        check_single_line(&self.task)?;
+
+        self.subtasks.iter().try_for_each(Task::check)
+    }
+
+    /// Works out the state of a task from its subtasks: complete once every subtask is complete or
+    /// not being done, started once any progress is made, and not started otherwise
+    pub fn derived_state(&self) -> Option<TaskState> {
+        if self.subtasks.is_empty() {
+            return None;
+        }
+
+        let all = |is_state: fn(&TaskState) -> bool| {
+            self.subtasks.iter().all(|subtask| is_state(&subtask.state))
+        };
+        let any = |is_state: fn(&TaskState) -> bool| {
+            self.subtasks.iter().any(|subtask| is_state(&subtask.state))
+        };
+
+        let state = if all(|state| *state == TaskState::Ignored) {
+            TaskState::Ignored
+        } else if all(|state| matches!(state, TaskState::Complete | TaskState::Ignored)) {
+            TaskState::Complete
+        } else if any(|state| matches!(state, TaskState::Started | TaskState::Complete)) {
+            TaskState::Started
+        } else {
+            TaskState::NotStarted
+        };
+
+        Some(state)
     }
 }
 
@@ -68,97 +478,340 @@ impl Task {
 pub struct TaskList<'a> {
     tasks: Vec<Task>,
     date: &'a TuduDate,
+    /// The fingerprint of the day file when it was read, to notice it being changed elsewhere
+    fingerprint: Option<u64>,
 }
 
 impl TaskList<'_> {
-    pub fn for_date(date: &TuduDate) -> Result<TaskList, TuduError> {
-        let filename = date.to_filename();
-        let filepath = create_filepath(&filename)?;
+    pub fn for_date(date: &TuduDate) -> Result<TaskList<'_>, TuduError> {
+        match load_tasks(&date.to_filename())? {
+            Some((tasks, fingerprint)) => Ok(TaskList {
+                tasks,
+                date,
+                fingerprint: Some(fingerprint),
+            }),
+            None => Ok(TaskList::empty(date)),
+        }
+    }
 
-        match parse_task_file(&filepath) {
-            Ok(tasks) => Ok(TaskList { tasks, date }),
-            Err(TuduError::NoTaskFile) => Ok(TaskList::empty(date)),
-            Err(err) => Err(err),
+    #[cfg(test)]
+    pub fn from_tasks(date: &TuduDate, tasks: Vec<Task>) -> TaskList<'_> {
+        TaskList {
+            tasks,
+            date,
+            fingerprint: None,
         }
     }
 
-    pub fn add_task(&mut self, new_task: Task) {
+    pub fn date(&self) -> &TuduDate {
+        self.date
+    }
+
+    pub fn tasks(&self) -> &[Task] {
+        &self.tasks
+    }
+
+    pub fn add_task(&mut self, new_task: Task) -> Result<(), TuduError> {
+        new_task.check()?;
+
         self.tasks.push(new_task);
+
+        Ok(())
+    }
+
+    /// Adds a subtask to the end of the subtasks of the task at the given user facing index
+    pub fn add_subtask(&mut self, parent: usize, new_task: Task) -> Result<(), TuduError> {
+        new_task.check()?;
+
+        match self.get_task_mut(parent.into()) {
+            Some(task) => {
+                task.subtasks.push(new_task);
+                Ok(())
+            }
+            None => Err(TuduError::InvalidIndex),
+        }
     }
 
     pub fn set_task_state(
         &mut self,
-        index: usize,
+        index: TaskIndex,
         desired_state: TaskState,
     ) -> Result<(), TuduError> {
-        let corrected_index = index - 1;
-
-        match self.tasks.get_mut(corrected_index) {
-            Some(task) => Ok(task.state = desired_state),
+        match self.get_task_mut(index) {
+            Some(task) => {
+                task.state = desired_state;
+                Ok(())
+            }
             None => Err(TuduError::InvalidIndex),
         }
     }
 
-    pub fn remove_task(&mut self, index: usize) -> Result<(), TuduError> {
-        let corrected_index = index - 1;
+    pub fn remove_task(&mut self, index: TaskIndex) -> Result<(), TuduError> {
+        let tasks = match index.subtask {
+            Some(_) => match self.get_task_mut(index.task.into()) {
+                Some(task) => &mut task.subtasks,
+                None => return Err(TuduError::InvalidIndex),
+            },
+            None => &mut self.tasks,
+        };
+
+        let position = index.subtask.unwrap_or(index.task);
 
-        if corrected_index > self.tasks.len() {
+        if position == 0 || position > tasks.len() {
             return Err(TuduError::InvalidIndex);
         }
 
-        self.tasks.remove(corrected_index);
+        tasks.remove(position - 1);
 
         Ok(())
     }
 
-    pub fn edit_task(&mut self, index: usize, new_task: String) -> Result<(), TuduError> {
-        let corrected_index = index - 1;
+    /// Moves the task at a user facing index to another, shifting the tasks in between along
+    pub fn move_task(&mut self, from: usize, to: usize) -> Result<(), TuduError> {
+        let count = self.tasks.len();
+
+        if from == 0 || from > count || to == 0 || to > count {
+            return Err(TuduError::InvalidIndex);
+        }
+
+        let task = self.tasks.remove(from - 1);
+        self.tasks.insert(to - 1, task);
+
+        Ok(())
+    }
 
-        match self.tasks.get_mut(corrected_index) {
-            Some(task) => Ok(task.task = new_task),
+    pub fn edit_task(&mut self, index: TaskIndex, new_task: String) -> Result<(), TuduError> {
+        check_single_line(&new_task)?;
+
+        match self.get_task_mut(index) {
+            Some(task) => {
+                task.task = new_task;
+                Ok(())
+            }
             None => Err(TuduError::InvalidIndex),
         }
     }
 
-    pub fn get_formatted_tasks(&self) -> String {
-        if self.tasks.len() == 0 {
-            return format!("There are no tasks for this date");
+    pub fn set_task_estimate(
+        &mut self,
+        index: TaskIndex,
+        estimate: Option<Duration>,
+    ) -> Result<(), TuduError> {
+        match self.get_task_mut(index) {
+            Some(task) => {
+                task.estimate = estimate;
+                Ok(())
+            }
+            None => Err(TuduError::InvalidIndex),
+        }
+    }
+
+    pub fn set_task_due(
+        &mut self,
+        index: TaskIndex,
+        due: Option<TuduDate>,
+    ) -> Result<(), TuduError> {
+        match self.get_task_mut(index) {
+            Some(task) => {
+                task.due = due;
+                Ok(())
+            }
+            None => Err(TuduError::InvalidIndex),
+        }
+    }
+
+    pub fn set_task_at(
+        &mut self,
+        index: TaskIndex,
+        at: Option<NaiveTime>,
+    ) -> Result<(), TuduError> {
+        match self.get_task_mut(index) {
+            Some(task) => {
+                task.at = at;
+                Ok(())
+            }
+            None => Err(TuduError::InvalidIndex),
+        }
+    }
+
+    pub fn set_task_priority(
+        &mut self,
+        index: TaskIndex,
+        priority: Option<char>,
+    ) -> Result<(), TuduError> {
+        match self.get_task_mut(index) {
+            Some(task) => {
+                task.priority = priority;
+                Ok(())
+            }
+            None => Err(TuduError::InvalidIndex),
+        }
+    }
+
+    /// Gives the id of the task at the index, making one for it if it does not have one yet
+    pub fn ensure_task_id(&mut self, index: TaskIndex) -> Result<String, TuduError> {
+        match self.get_task_mut(index) {
+            Some(task) => Ok(task.id.get_or_insert_with(new_task_id).clone()),
+            None => Err(TuduError::InvalidIndex),
+        }
+    }
+
+    pub fn add_dependency(
+        &mut self,
+        index: TaskIndex,
+        dependency: Dependency,
+    ) -> Result<(), TuduError> {
+        match self.get_task_mut(index) {
+            Some(task) if task.after.contains(&dependency) => Ok(()),
+            Some(task) => {
+                task.after.push(dependency);
+                Ok(())
+            }
+            None => Err(TuduError::InvalidIndex),
+        }
+    }
+
+    /// Removes the dependency, giving whether the task had it
+    pub fn remove_dependency(
+        &mut self,
+        index: TaskIndex,
+        dependency: &Dependency,
+    ) -> Result<bool, TuduError> {
+        match self.get_task_mut(index) {
+            Some(task) => {
+                let dependency_count = task.after.len();
+                task.after.retain(|existing| existing != dependency);
+                Ok(task.after.len() != dependency_count)
+            }
+            None => Err(TuduError::InvalidIndex),
+        }
+    }
+
+    pub fn get_task(&self, index: TaskIndex) -> Option<&Task> {
+        let task = self.tasks.get(index.task.checked_sub(1)?)?;
+
+        match index.subtask {
+            Some(subtask) => task.subtasks.get(subtask.checked_sub(1)?),
+            None => Some(task),
+        }
+    }
+
+    /// Sets the state of every task with subtasks to the state derived from them
+    pub fn derive_parent_states(&mut self) {
+        for task in self.tasks.iter_mut() {
+            if let Some(state) = task.derived_state() {
+                task.state = state;
+            }
+        }
+    }
+
+    /// Formats the tasks for display with their subtasks indented beneath them, following each
+    /// task with any annotation given for its user facing index, such as the time spent on it.
+    /// Blocked tasks are shown with the blocked icon in place of their state.
+    pub fn get_formatted_tasks(
+        &self,
+        is_blocked: impl Fn(&Task) -> bool,
+        annotate: impl Fn(TaskIndex, &Task) -> Option<String>,
+    ) -> String {
+        if self.tasks.is_empty() {
+            return String::from("There are no tasks for this date");
         }
 
         let mut formatted_output = String::new();
 
-        self.tasks.iter().enumerate().for_each(|(index, task)| {
-            let icon = match task.state {
-                TaskState::NotStarted => "◯",
-                TaskState::Started => "◐",
-                TaskState::Complete => "●",
-                TaskState::Forwarded => "►",
-                TaskState::Ignored => "x",
+        let format_task = |indent: &str, index: TaskIndex, task: &Task| {
+            let icon = if is_blocked(task) {
+                BLOCKED_ICON
+            } else {
+                task.state.icon()
             };
             let description = &task.task;
-            let formatted_index = index + 1;
 
-            let formatted = format!("{formatted_index}    {icon} - {description}\n");
+            match annotate(index, task) {
+                Some(annotation) => {
+                    format!("{indent}{index}    {icon} - {description}    {annotation}\n")
+                }
+                None => format!("{indent}{index}    {icon} - {description}\n"),
+            }
+        };
+
+        self.tasks.iter().enumerate().for_each(|(index, task)| {
+            let formatted_index = TaskIndex::from(index + 1);
+
+            formatted_output.push_str(&format_task("", formatted_index, task));
+
+            task.subtasks
+                .iter()
+                .enumerate()
+                .for_each(|(subtask_index, subtask)| {
+                    let formatted_index = TaskIndex {
+                        subtask: Some(subtask_index + 1),
+                        ..formatted_index
+                    };
 
-            formatted_output.push_str(&formatted);
+                    formatted_output.push_str(&format_task("    ", formatted_index, subtask));
+                });
         });
 
         formatted_output
     }
 
-    fn empty(date: &TuduDate) -> TaskList {
+    /// Finds the task or subtask with the given user facing index
+    fn get_task_mut(&mut self, index: TaskIndex) -> Option<&mut Task> {
+        let task = self.tasks.get_mut(index.task.checked_sub(1)?)?;
+
+        match index.subtask {
+            Some(subtask) => task.subtasks.get_mut(subtask.checked_sub(1)?),
+            None => Some(task),
+        }
+    }
+
+    fn empty(date: &TuduDate) -> TaskList<'_> {
         TaskList {
             tasks: Vec::new(),
             date,
+            fingerprint: None,
+        }
+    }
+
+    /// Reads the tasks again if the day file has been changed since it was read, e.g. by tudu run
+    /// from a pre hook. Gives whether it had been changed.
+    pub fn reload_if_changed(&mut self) -> Result<bool, TuduError> {
+        let filepath = create_filepath(&self.date.to_filename())?;
+
+        if fingerprint_file(&filepath)? == self.fingerprint {
+            return Ok(false);
         }
+
+        let (tasks, fingerprint) = match load_tasks(&self.date.to_filename())? {
+            Some((tasks, fingerprint)) => (tasks, Some(fingerprint)),
+            None => (Vec::new(), None),
+        };
+
+        self.tasks = tasks;
+        self.fingerprint = fingerprint;
+
+        Ok(true)
     }
 
-    pub fn write_to_file(&self) -> Result<(), TuduError> {
+    /// Writes the tasks back to the day file, unless the file has been changed since it was read
+    pub fn write_to_file(&mut self) -> Result<(), TuduError> {
         let filename = self.date.to_filename();
 
         let filepath = create_filepath(&filename)?;
 
-        write_tasks_to_file(&filepath, &self.tasks)
+        if fingerprint_file(&filepath)? != self.fingerprint {
+            return Err(TuduError::ChangedElsewhere(self.date.to_iso()));
+        }
+
+        // Tasks changed in place, such as by a hook or script, are checked before being written
+        self.tasks.iter().try_for_each(Task::check)?;
+
+        write_tasks_to_file(&filepath, &self.tasks)?;
+
+        self.fingerprint = fingerprint_file(&filepath)?;
+
+        Ok(())
     }
 }
 
@@ -186,14 +839,16 @@ mod tests {
         let expected_task_list = TaskList {
             date: &date,
             tasks: vec![first_task.clone(), second_task.clone()],
+            fingerprint: None,
         };
 
         let mut task_list = TaskList {
             date: &date,
             tasks: vec![first_task.clone()],
+            fingerprint: None,
         };
 
-        task_list.add_task(second_task.clone());
+        task_list.add_task(second_task.clone()).unwrap();
 
         assert_eq!(task_list.tasks, expected_task_list.tasks);
     }
@@ -207,6 +862,7 @@ mod tests {
         let expected_task_list = TaskList {
             tasks: vec![first_task.clone(), second_task.clone()],
             date: &date,
+            fingerprint: None,
         };
 
         let mut task_list = TaskList {
@@ -215,9 +871,12 @@ mod tests {
                 first_task.clone(),
                 Task::new(String::from("BBB"), TaskState::NotStarted),
             ],
+            fingerprint: None,
         };
 
-        task_list.set_task_state(2, TaskState::Complete).unwrap();
+        task_list
+            .set_task_state(2.into(), TaskState::Complete)
+            .unwrap();
 
         assert_eq!(task_list.tasks, expected_task_list.tasks);
     }
@@ -229,11 +888,12 @@ mod tests {
         let mut task_list = TaskList {
             date: &date,
             tasks: vec![Task::new(String::from("AAA"), TaskState::NotStarted)],
+            fingerprint: None,
         };
 
         let expected_error = TuduError::InvalidIndex;
 
-        let result = task_list.set_task_state(2, TaskState::Complete);
+        let result = task_list.set_task_state(2.into(), TaskState::Complete);
 
         assert!(result.is_err());
         assert_eq!(result.err().unwrap(), expected_error);
@@ -248,18 +908,55 @@ mod tests {
         let expected_task_list = TaskList {
             tasks: vec![first_task.clone()],
             date: &date,
+            fingerprint: None,
         };
 
         let mut task_list = TaskList {
             date: &date,
             tasks: vec![first_task, second_task],
+            fingerprint: None,
         };
 
-        task_list.remove_task(2);
+        task_list.remove_task(2.into()).unwrap();
 
         assert_eq!(task_list.tasks, expected_task_list.tasks);
     }
 
+    #[test]
+    fn remove_task_at_invalid_index_throws_error() {
+        let date = TuduDate::new(1, 1, 2023);
+
+        let mut task_list = TaskList {
+            date: &date,
+            tasks: vec![Task::new(String::from("AAA"), TaskState::NotStarted)],
+            fingerprint: None,
+        };
+
+        assert_eq!(
+            task_list.remove_task(0.into()),
+            Err(TuduError::InvalidIndex)
+        );
+        assert_eq!(
+            task_list.remove_task(2.into()),
+            Err(TuduError::InvalidIndex)
+        );
+        assert_eq!(task_list.tasks.len(), 1);
+    }
+
+    #[test]
+    fn move_task_shifts_the_tasks_in_between() {
+        let date = TuduDate::new(1, 1, 2023);
+        let task = |task: &str| Task::new(String::from(task), TaskState::NotStarted);
+
+        let mut task_list =
+            TaskList::from_tasks(&date, vec![task("AAA"), task("BBB"), task("CCC")]);
+
+        task_list.move_task(3, 1).unwrap();
+
+        assert_eq!(task_list.tasks, vec![task("CCC"), task("AAA"), task("BBB")]);
+        assert_eq!(task_list.move_task(1, 4), Err(TuduError::InvalidIndex));
+    }
+
     #[test]
     fn edit_task_at_index_edits_that_task() {
         let date = TuduDate::new(1, 1, 2023);
@@ -269,6 +966,7 @@ mod tests {
         let expected_task_list = TaskList {
             tasks: vec![first_task.clone(), second_task.clone()],
             date: &date,
+            fingerprint: None,
         };
 
         let mut task_list = TaskList {
@@ -277,13 +975,52 @@ mod tests {
                 first_task.clone(),
                 Task::new(String::from("CCC"), TaskState::Complete),
             ],
+            fingerprint: None,
         };
 
-        task_list.edit_task(2, String::from("BBB")).unwrap();
+        task_list.edit_task(2.into(), String::from("BBB")).unwrap();
 
         assert_eq!(task_list.tasks, expected_task_list.tasks);
     }
 
+    #[test]
+    fn tasks_over_more_than_one_line_give_error() {
+        let date = TuduDate::new(1, 1, 2023);
+        let mut task_list = TaskList {
+            date: &date,
+            tasks: vec![Task::new(String::from("AAA"), TaskState::NotStarted)],
+            fingerprint: None,
+        };
+        let expected_error = Err(TuduError::InvalidArguments(String::from(MULTILINE_TASK)));
+
+        let mut parent = Task::new(String::from("Parent"), TaskState::NotStarted);
+        parent.subtasks = vec![Task::new(
+            String::from("Sub\r  C;est=30,Injected"),
+            TaskState::NotStarted,
+        )];
+
+        assert_eq!(
+            task_list.add_task(Task::new(
+                String::from("Write docs\nC,Injected"),
+                TaskState::NotStarted
+            )),
+            expected_error
+        );
+        assert_eq!(task_list.add_task(parent), expected_error);
+        assert_eq!(
+            task_list.add_subtask(1, Task::new(String::from("A\nB"), TaskState::NotStarted)),
+            expected_error
+        );
+        assert_eq!(
+            task_list.edit_task(1.into(), String::from("A\nB")),
+            expected_error
+        );
+        assert_eq!(
+            task_list.tasks,
+            vec![Task::new(String::from("AAA"), TaskState::NotStarted)]
+        );
+    }
+
     #[test]
     fn edit_task_at_index_if_no_task_at_index_throws_error() {
         let date = TuduDate::new(1, 1, 2023);
@@ -291,11 +1028,12 @@ mod tests {
         let mut task_list = TaskList {
             date: &date,
             tasks: vec![Task::new(String::from("AAA"), TaskState::NotStarted)],
+            fingerprint: None,
         };
 
         let expected_error = TuduError::InvalidIndex;
 
-        let result = task_list.edit_task(2, String::from("BBB"));
+        let result = task_list.edit_task(2.into(), String::from("BBB"));
 
         assert!(result.is_err());
         assert_eq!(result.err().unwrap(), expected_error);
@@ -314,6 +1052,7 @@ mod tests {
         let task_list = TaskList {
             date: &TuduDate::new(1, 1, 2023),
             tasks,
+            fingerprint: None,
         };
 
         let expected_formatting = String::from(
@@ -324,7 +1063,29 @@ mod tests {
 5    ◯ - Patience is a virtue\n",
         );
 
-        let formatted = task_list.get_formatted_tasks();
+        let formatted = task_list.get_formatted_tasks(|_| false, |_, _| None);
+
+        assert_eq!(formatted, expected_formatting);
+    }
+
+    #[test]
+    fn get_formatted_tasks_follows_tasks_with_annotations() {
+        let date = TuduDate::new(1, 1, 2023);
+        let task_list = TaskList {
+            date: &date,
+            tasks: vec![
+                Task::new(String::from("AAA"), TaskState::Started),
+                Task::new(String::from("BBB"), TaskState::NotStarted),
+            ],
+            fingerprint: None,
+        };
+
+        let expected_formatting = "1    ◐ - AAA    1h 05m\n2    ◯ - BBB\n";
+
+        let formatted = task_list.get_formatted_tasks(
+            |_| false,
+            |index, _| (index.task == 1).then(|| String::from("1h 05m")),
+        );
 
         assert_eq!(formatted, expected_formatting);
     }
@@ -336,8 +1097,184 @@ mod tests {
 
         let expected_message = "There are no tasks for this date";
 
-        let message = task_list.get_formatted_tasks();
+        let message = task_list.get_formatted_tasks(|_| false, |_, _| None);
 
         assert_eq!(message, expected_message);
     }
+
+    #[test]
+    fn task_index_parse_reads_tasks_and_subtasks() {
+        assert_eq!(TaskIndex::parse("3").unwrap(), TaskIndex::from(3));
+        assert_eq!(
+            TaskIndex::parse("3.1").unwrap(),
+            TaskIndex {
+                task: 3,
+                subtask: Some(1)
+            }
+        );
+        assert!(TaskIndex::parse("3.").is_err());
+        assert!(TaskIndex::parse("three").is_err());
+    }
+
+    #[test]
+    fn add_subtask_to_missing_task_throws_error() {
+        let date = TuduDate::new(1, 1, 2023);
+        let mut task_list = TaskList {
+            date: &date,
+            tasks: vec![Task::new(String::from("AAA"), TaskState::NotStarted)],
+            fingerprint: None,
+        };
+
+        let result =
+            task_list.add_subtask(2, Task::new(String::from("BBB"), TaskState::NotStarted));
+
+        assert_eq!(result, Err(TuduError::InvalidIndex));
+    }
+
+    #[test]
+    fn set_and_remove_subtask_at_index_changes_that_subtask() {
+        let date = TuduDate::new(1, 1, 2023);
+        let mut task_list = TaskList {
+            date: &date,
+            tasks: vec![Task::new(String::from("Parent"), TaskState::NotStarted)],
+            fingerprint: None,
+        };
+        task_list
+            .add_subtask(1, Task::new(String::from("First"), TaskState::NotStarted))
+            .unwrap();
+        task_list
+            .add_subtask(1, Task::new(String::from("Second"), TaskState::NotStarted))
+            .unwrap();
+
+        let second = TaskIndex::parse("1.2").unwrap();
+        task_list
+            .set_task_state(second, TaskState::Complete)
+            .unwrap();
+        task_list
+            .remove_task(TaskIndex::parse("1.1").unwrap())
+            .unwrap();
+
+        assert_eq!(
+            task_list.tasks()[0].subtasks,
+            vec![Task::new(String::from("Second"), TaskState::Complete)]
+        );
+        assert_eq!(task_list.tasks()[0].state, TaskState::NotStarted);
+        assert_eq!(
+            task_list.remove_task(TaskIndex::parse("1.2").unwrap()),
+            Err(TuduError::InvalidIndex)
+        );
+    }
+
+    #[test]
+    fn derived_state_follows_subtasks() {
+        let mut task = Task::new(String::from("Release 1.2"), TaskState::NotStarted);
+
+        assert_eq!(task.derived_state(), None);
+
+        task.subtasks = vec![
+            Task::new(String::from("Tag"), TaskState::NotStarted),
+            Task::new(String::from("Publish"), TaskState::Ignored),
+        ];
+        assert_eq!(task.derived_state(), Some(TaskState::NotStarted));
+
+        task.subtasks[0].state = TaskState::Started;
+        assert_eq!(task.derived_state(), Some(TaskState::Started));
+
+        task.subtasks[0].state = TaskState::Complete;
+        assert_eq!(task.derived_state(), Some(TaskState::Complete));
+
+        task.subtasks[0].state = TaskState::Ignored;
+        assert_eq!(task.derived_state(), Some(TaskState::Ignored));
+    }
+
+    #[test]
+    fn get_formatted_tasks_indents_subtasks() {
+        let date = TuduDate::new(1, 1, 2023);
+        let mut parent = Task::new(String::from("Release 1.2"), TaskState::Started);
+        parent.subtasks = vec![
+            Task::new(String::from("Tag"), TaskState::Complete),
+            Task::new(String::from("Publish"), TaskState::NotStarted),
+        ];
+        let task_list = TaskList {
+            date: &date,
+            tasks: vec![
+                parent,
+                Task::new(String::from("Tidy up"), TaskState::NotStarted),
+            ],
+            fingerprint: None,
+        };
+
+        let expected_formatting = "1    ◐ - Release 1.2
+    1.1    ● - Tag
+    1.2    ◯ - Publish
+2    ◯ - Tidy up
+";
+
+        let formatted = task_list.get_formatted_tasks(|_| false, |_, _| None);
+
+        assert_eq!(formatted, expected_formatting);
+    }
+
+    #[test]
+    fn get_formatted_tasks_shows_blocked_icon() {
+        let date = TuduDate::new(1, 1, 2023);
+        let task_list = TaskList {
+            date: &date,
+            tasks: vec![
+                Task::new(String::from("AAA"), TaskState::NotStarted),
+                Task::new(String::from("BBB"), TaskState::NotStarted),
+            ],
+            fingerprint: None,
+        };
+
+        let expected_formatting = "1    ◯ - AAA\n2    ⊘ - BBB\n";
+
+        let formatted = task_list.get_formatted_tasks(|task| task.task == "BBB", |_, _| None);
+
+        assert_eq!(formatted, expected_formatting);
+    }
+
+    #[test]
+    fn ensure_task_id_keeps_existing_id() {
+        let date = TuduDate::new(1, 1, 2023);
+        let mut task_list = TaskList {
+            date: &date,
+            tasks: vec![Task::new(String::from("AAA"), TaskState::NotStarted)],
+            fingerprint: None,
+        };
+
+        let id = task_list.ensure_task_id(1.into()).unwrap();
+
+        assert_eq!(task_list.tasks()[0].id, Some(id.clone()));
+        assert_eq!(task_list.ensure_task_id(1.into()).unwrap(), id);
+        assert_eq!(
+            task_list.ensure_task_id(2.into()),
+            Err(TuduError::InvalidIndex)
+        );
+    }
+
+    #[test]
+    fn add_then_remove_dependency_changes_task() {
+        let date = TuduDate::new(1, 1, 2023);
+        let mut task_list = TaskList {
+            date: &date,
+            tasks: vec![Task::new(String::from("AAA"), TaskState::NotStarted)],
+            fingerprint: None,
+        };
+        let dependency = Dependency {
+            date: date.clone(),
+            id: String::from("abc"),
+        };
+
+        task_list
+            .add_dependency(1.into(), dependency.clone())
+            .unwrap();
+        task_list
+            .add_dependency(1.into(), dependency.clone())
+            .unwrap();
+
+        assert_eq!(task_list.tasks()[0].after, vec![dependency.clone()]);
+        assert!(task_list.remove_dependency(1.into(), &dependency).unwrap());
+        assert!(!task_list.remove_dependency(1.into(), &dependency).unwrap());
+    }
 }
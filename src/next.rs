@@ -0,0 +1,273 @@
+use std::cmp::Reverse;
+
+use crate::date::TuduDate;
+use crate::dependency::{flatten_tasks, Prerequisites};
+use crate::due::is_overdue;
+use crate::model::{Task, TaskIndex, TaskList, TaskState};
+use crate::priority::format_priority;
+
+pub const RANKING_RULES: &str = "Tasks are ranked by being started, then by priority, then by the \
+earliest due date, then by how many times they have been carried forward, then by how long ago \
+they were first planned. Blocked tasks are left out.";
+
+/// A task that could be done next, along with what it is ranked on
+#[derive(Debug, PartialEq, Eq)]
+pub struct Candidate<'a> {
+    pub date: &'a TuduDate,
+    pub index: TaskIndex,
+    pub task: &'a Task,
+    pub times_forwarded: usize,
+    pub first_planned: &'a TuduDate,
+}
+
+impl Candidate<'_> {
+    /// Orders candidates so that the one to do next comes first. Tasks without a priority or due
+    /// date come after those with one.
+    fn rank(&self) -> impl Ord + '_ {
+        (
+            self.task.state != TaskState::Started,
+            self.task.priority.is_none(),
+            self.task.priority,
+            self.task.due.is_none(),
+            self.task.due.as_ref(),
+            Reverse(self.times_forwarded),
+            self.first_planned,
+            self.date,
+            self.index.task,
+            self.index.subtask,
+        )
+    }
+
+    /// The reasons the task is ranked where it is, following the ranking rules
+    fn reasons(&self, today: &TuduDate) -> Vec<String> {
+        let mut reasons = Vec::new();
+
+        if self.task.state == TaskState::Started {
+            reasons.push(String::from("started"));
+        }
+
+        if let Some(priority) = self.task.priority {
+            reasons.push(format!("priority {}", format_priority(priority)));
+        }
+
+        if let Some(due) = &self.task.due {
+            match is_overdue(self.task, today) {
+                true => reasons.push(format!("overdue since {}", due.to_iso())),
+                false => reasons.push(format!("due {}", due.to_iso())),
+            }
+        }
+
+        match self.times_forwarded {
+            0 => {}
+            1 => reasons.push(String::from("carried forward once")),
+            times => reasons.push(format!("carried forward {times} times")),
+        }
+
+        reasons.push(format!("first planned {}", self.first_planned.to_iso()));
+
+        reasons
+    }
+}
+
+/// Ranks the unfinished and unblocked tasks in the task lists, from the one to do next. A task that
+/// was carried forward is recognised on its earlier days by its description.
+pub fn rank_tasks<'a>(
+    task_lists: &'a [TaskList],
+    prerequisites: &Prerequisites,
+) -> Vec<Candidate<'a>> {
+    let forwarded: Vec<(&TuduDate, &Task)> = task_lists
+        .iter()
+        .flat_map(|task_list| {
+            flatten_tasks(task_list.tasks())
+                .into_iter()
+                .filter(|(_, task)| task.state == TaskState::Forwarded)
+                .map(|(_, task)| (task_list.date(), task))
+        })
+        .collect();
+
+    let mut candidates: Vec<Candidate> = task_lists
+        .iter()
+        .flat_map(|task_list| {
+            let date = task_list.date();
+
+            flatten_tasks(task_list.tasks())
+                .into_iter()
+                .filter(|(_, task)| {
+                    matches!(task.state, TaskState::NotStarted | TaskState::Started)
+                })
+                .filter(|(_, task)| !prerequisites.is_blocked(task))
+                .map(|(index, task)| {
+                    let earlier_days: Vec<&TuduDate> = forwarded
+                        .iter()
+                        .filter(|(forwarded_date, forwarded_task)| {
+                            *forwarded_date < date && forwarded_task.task == task.task
+                        })
+                        .map(|(forwarded_date, _)| *forwarded_date)
+                        .collect();
+
+                    Candidate {
+                        date,
+                        index,
+                        task,
+                        times_forwarded: earlier_days.len(),
+                        first_planned: earlier_days.into_iter().min().unwrap_or(date),
+                    }
+                })
+                .collect::<Vec<Candidate>>()
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| a.rank().cmp(&b.rank()));
+
+    candidates
+}
+
+/// Lists the candidates with the day each is planned on, and with the reasons for its ranking when
+/// asked why
+pub fn render_candidates(candidates: &[Candidate], today: &TuduDate, why: bool) -> String {
+    if candidates.is_empty() {
+        return String::from("There are no tasks left to do\n");
+    }
+
+    let mut rendered: String = candidates
+        .iter()
+        .map(|candidate| {
+            let line = format!(
+                "{}    {}    {} - {}\n",
+                candidate.date.to_iso(),
+                candidate.index,
+                candidate.task.state.icon(),
+                candidate.task.task
+            );
+
+            match why {
+                true => format!("{line}    {}\n", candidate.reasons(today).join(", ")),
+                false => line,
+            }
+        })
+        .collect();
+
+    if why {
+        rendered.push_str(&format!("\n{RANKING_RULES}\n"));
+    }
+
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::dependency::Dependency;
+
+    #[test]
+    fn rank_tasks_puts_started_then_priority_then_due_then_forwarded_first() {
+        let monday = TuduDate::new(5, 6, 2023);
+        let tuesday = TuduDate::new(6, 6, 2023);
+        let today = TuduDate::new(7, 6, 2023);
+
+        let task_lists = vec![
+            TaskList::from_tasks(
+                &monday,
+                vec![
+                    Task::new(String::from("Tidy desk"), TaskState::Forwarded),
+                    Task {
+                        id: Some(String::from("spec")),
+                        ..Task::new(String::from("Write spec"), TaskState::NotStarted)
+                    },
+                ],
+            ),
+            TaskList::from_tasks(
+                &tuesday,
+                vec![Task::new(String::from("Tidy desk"), TaskState::Forwarded)],
+            ),
+            TaskList::from_tasks(
+                &today,
+                vec![
+                    Task::new(String::from("Tidy desk"), TaskState::NotStarted),
+                    Task::new(String::from("Read email"), TaskState::NotStarted),
+                    Task {
+                        due: Some(TuduDate::new(9, 6, 2023)),
+                        ..Task::new(String::from("Send invoice"), TaskState::NotStarted)
+                    },
+                    Task {
+                        priority: Some('B'),
+                        ..Task::new(String::from("Fix bug"), TaskState::NotStarted)
+                    },
+                    Task {
+                        priority: Some('A'),
+                        ..Task::new(String::from("Ship release"), TaskState::NotStarted)
+                    },
+                    Task::new(String::from("Review PR"), TaskState::Started),
+                    Task {
+                        after: vec![Dependency {
+                            date: monday.clone(),
+                            id: String::from("spec"),
+                        }],
+                        ..Task::new(String::from("Implement spec"), TaskState::NotStarted)
+                    },
+                    Task::new(String::from("Done already"), TaskState::Complete),
+                ],
+            ),
+        ];
+        let prerequisites = Prerequisites::from_tasks_by_date(BTreeMap::from([(
+            monday.clone(),
+            task_lists[0].tasks().to_vec(),
+        )]));
+
+        let ranked: Vec<&str> = rank_tasks(&task_lists, &prerequisites)
+            .iter()
+            .map(|candidate| candidate.task.task.as_str())
+            .collect();
+
+        assert_eq!(
+            ranked,
+            vec![
+                "Review PR",
+                "Ship release",
+                "Fix bug",
+                "Send invoice",
+                "Tidy desk",
+                "Write spec",
+                "Read email"
+            ]
+        );
+    }
+
+    #[test]
+    fn render_candidates_explains_ranking_when_asked_why() {
+        let monday = TuduDate::new(5, 6, 2023);
+        let today = TuduDate::new(7, 6, 2023);
+        let task_lists = vec![
+            TaskList::from_tasks(
+                &monday,
+                vec![Task::new(String::from("Tidy desk"), TaskState::Forwarded)],
+            ),
+            TaskList::from_tasks(
+                &today,
+                vec![Task {
+                    priority: Some('A'),
+                    due: Some(monday.clone()),
+                    ..Task::new(String::from("Tidy desk"), TaskState::Started)
+                }],
+            ),
+        ];
+        let prerequisites = Prerequisites::from_tasks_by_date(BTreeMap::new());
+
+        let candidates = rank_tasks(&task_lists, &prerequisites);
+
+        let expected_rendering = format!(
+            "2023-06-07    1    ◐ - Tidy desk
+    started, priority (A), overdue since 2023-06-05, carried forward once, first planned 2023-06-05
+
+{RANKING_RULES}
+"
+        );
+
+        assert_eq!(
+            render_candidates(&candidates, &today, true),
+            expected_rendering
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::process;
+
+use crate::date::TuduDate;
+use crate::error::TuduError;
+use crate::storage::{create_filepath, read_file_if_exists, write_file};
+
+pub const NOTE_MARKER: &str = "✎";
+const NOTE_INDENT: &str = "  ";
+const DEFAULT_EDITOR: &str = "vi";
+
+/// The notes for the tasks on a date, kept in a sidecar to the day file by task id so that each
+/// note stays with its task when tasks are moved or removed
+#[derive(Debug, PartialEq, Eq)]
+pub struct Notes<'a> {
+    notes: BTreeMap<String, String>,
+    date: &'a TuduDate,
+}
+
+impl Notes<'_> {
+    pub fn for_date(date: &TuduDate) -> Result<Notes<'_>, TuduError> {
+        let filepath = create_filepath(&notes_filename(date))?;
+
+        let notes = match read_file_if_exists(&filepath)? {
+            Some(contents) => parse_notes(&contents)?,
+            None => BTreeMap::new(),
+        };
+
+        Ok(Notes { notes, date })
+    }
+
+    #[cfg(test)]
+    pub fn from_notes(date: &TuduDate, notes: BTreeMap<String, String>) -> Notes<'_> {
+        Notes { notes, date }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&str> {
+        self.notes.get(id).map(String::as_str)
+    }
+
+    /// Sets the note for a task, removing it if the note is left blank
+    pub fn set(&mut self, id: &str, note: &str) {
+        let note = note.trim_end().trim_start_matches(['\r', '\n']);
+
+        if note.trim().is_empty() {
+            self.notes.remove(id);
+        } else {
+            self.notes.insert(String::from(id), String::from(note));
+        }
+    }
+
+    pub fn remove(&mut self, id: &str) {
+        self.notes.remove(id);
+    }
+
+    pub fn write_to_file(&self) -> Result<(), TuduError> {
+        let filepath = create_filepath(&notes_filename(self.date))?;
+
+        write_file(&filepath, &format_notes(&self.notes))
+    }
+}
+
+fn notes_filename(date: &TuduDate) -> String {
+    format!("{}.notes", date.to_iso())
+}
+
+/// Reads notes written as the task id on its own line, followed by each line of the note indented
+fn parse_notes(contents: &str) -> Result<BTreeMap<String, String>, TuduError> {
+    let mut notes: BTreeMap<String, String> = BTreeMap::new();
+    let mut current_id: Option<&str> = None;
+
+    for line in contents.lines() {
+        match (line.strip_prefix(NOTE_INDENT), current_id) {
+            (Some(line), Some(id)) => {
+                let note = notes.entry(String::from(id)).or_default();
+                if !note.is_empty() {
+                    note.push('\n');
+                }
+                note.push_str(line);
+            }
+            (Some(_), None) => return Err(TuduError::BadTaskFormat),
+            (None, _) if line.is_empty() => return Err(TuduError::BadTaskFormat),
+            (None, _) => current_id = Some(line),
+        }
+    }
+
+    Ok(notes)
+}
+
+fn format_notes(notes: &BTreeMap<String, String>) -> String {
+    notes
+        .iter()
+        .map(|(id, note)| {
+            let lines: String = note
+                .lines()
+                .map(|line| format!("{NOTE_INDENT}{line}\n"))
+                .collect();
+
+            format!("{id}\n{lines}")
+        })
+        .collect()
+}
+
+/// Opens the note in the editor from the EDITOR env variable, falling back to vi, and gives back
+/// the note once the editor is closed
+pub fn edit_in_editor(id: &str, note: &str) -> Result<String, TuduError> {
+    let editor = env::var("EDITOR").unwrap_or_else(|_| String::from(DEFAULT_EDITOR));
+
+    // The editor can be given with arguments, e.g. `code --wait`
+    let mut editor_words = editor.split_whitespace();
+    let program = editor_words.next().unwrap_or(DEFAULT_EDITOR);
+
+    let path = env::temp_dir().join(format!("tudu-note-{id}.md"));
+
+    write_file(&path, note)?;
+
+    let status = process::Command::new(program)
+        .args(editor_words)
+        .arg(&path)
+        .status();
+
+    let edited = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+
+    match (status, edited) {
+        (Ok(status), Ok(edited)) if status.success() => Ok(edited),
+        _ => Err(TuduError::FailedToOpenEditor(editor)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_then_parse_notes_gives_back_the_same_notes() {
+        let notes = BTreeMap::from([
+            (
+                String::from("abc"),
+                String::from("See https://example.com\n\n  Indented line"),
+            ),
+            (String::from("def"), String::from("One line")),
+        ]);
+
+        let contents = format_notes(&notes);
+
+        assert_eq!(
+            contents,
+            "abc\n  See https://example.com\n  \n    Indented line\ndef\n  One line\n"
+        );
+        assert_eq!(parse_notes(&contents).unwrap(), notes);
+    }
+
+    #[test]
+    fn parse_notes_with_line_before_any_id_gives_error() {
+        let result = parse_notes("  orphaned line\nabc\n  note\n");
+
+        assert_eq!(result, Err(TuduError::BadTaskFormat));
+    }
+
+    #[test]
+    fn set_blank_note_removes_it() {
+        let date = TuduDate::new(7, 6, 2023);
+        let mut notes = Notes {
+            notes: BTreeMap::new(),
+            date: &date,
+        };
+
+        notes.set("abc", "Acceptance criteria\n- fast\n\n");
+        assert_eq!(notes.get("abc"), Some("Acceptance criteria\n- fast"));
+
+        notes.set("abc", "  \n");
+        assert_eq!(notes.get("abc"), None);
+    }
+}
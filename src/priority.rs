@@ -0,0 +1,41 @@
+use crate::error::TuduError;
+
+/// Reads a priority given as a single letter from A, the most important, to Z, ignoring case
+pub fn parse_priority(priority: &str) -> Result<char, TuduError> {
+    let mut letters = priority.chars();
+
+    match (letters.next(), letters.next()) {
+        (Some(letter), None) if letter.is_ascii_alphabetic() => Ok(letter.to_ascii_uppercase()),
+        _ => Err(TuduError::InvalidArguments(format!(
+            "`{priority}` is not a priority, use a letter from A to Z"
+        ))),
+    }
+}
+
+pub fn format_priority(priority: char) -> String {
+    format!("({priority})")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_priority_reads_letters_ignoring_case() {
+        assert_eq!(parse_priority("A").unwrap(), 'A');
+        assert_eq!(parse_priority("c").unwrap(), 'C');
+    }
+
+    #[test]
+    fn parse_priority_rejects_other_text() {
+        for priority in ["", "AB", "1", "!"] {
+            assert!(
+                matches!(
+                    parse_priority(priority),
+                    Err(TuduError::InvalidArguments(_))
+                ),
+                "{priority} should be rejected"
+            );
+        }
+    }
+}
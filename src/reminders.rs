@@ -0,0 +1,563 @@
+use std::collections::BTreeMap;
+use std::process;
+use std::thread;
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveTime};
+
+use crate::date::{now, TuduDate};
+use crate::dependency::flatten_tasks;
+use crate::error::{user_error_message, TuduError};
+use crate::estimate::parse_estimate;
+use crate::model::{Task, TaskIndex, TaskList, TaskState};
+use crate::storage::{create_filepath, read_file_if_exists, write_file, TasksLock};
+
+pub const REMINDER_ICON: &str = "⏰";
+const TIME_FORMAT: &str = "%H:%M";
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Reads a time of day such as `14:30` or `9:05`
+pub fn parse_time(time: &str) -> Result<NaiveTime, TuduError> {
+    NaiveTime::parse_from_str(time, TIME_FORMAT).map_err(|_| {
+        TuduError::InvalidArguments(format!(
+            "`{time}` is not a time of day, write it like 14:30"
+        ))
+    })
+}
+
+/// Reads how long to snooze a reminder for, such as `10m` or `1h`
+pub fn parse_snooze(duration: &str) -> Result<Duration, TuduError> {
+    parse_estimate(duration).map_err(|_| {
+        TuduError::InvalidArguments(format!(
+            "`{duration}` is not a length of time, write it like 10m, 1h or 1h30m"
+        ))
+    })
+}
+
+pub fn format_time(time: NaiveTime) -> String {
+    time.format(TIME_FORMAT).to_string()
+}
+
+/// Describes the time a task is reminded at for display next to it
+pub fn format_reminder(task: &Task) -> Option<String> {
+    task.at
+        .map(|at| format!("{REMINDER_ICON} {}", format_time(at)))
+}
+
+/// Reminders are kept by task id so they stay with their task when tasks are moved, falling back
+/// to the description for tasks given a time by editing the day file
+pub fn reminder_key(task: &Task) -> String {
+    task.id.clone().unwrap_or_else(|| task.task.clone())
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ReminderStatus {
+    /// Fired for the time the task had, so changing the time sets the reminder again
+    Fired(NaiveTime),
+    Snoozed(DateTime<FixedOffset>),
+    /// Snoozed past the end of the day its task is on, which is the date given, so the reminder is
+    /// kept with the day it is snoozed until
+    SnoozedFrom(TuduDate, DateTime<FixedOffset>),
+}
+
+/// What has happened to the reminders for the tasks on a date, kept in a sidecar to the day file
+#[derive(Debug, PartialEq, Eq)]
+pub struct Reminders<'a> {
+    statuses: BTreeMap<String, ReminderStatus>,
+    date: &'a TuduDate,
+}
+
+impl Reminders<'_> {
+    pub fn for_date(date: &TuduDate) -> Result<Reminders<'_>, TuduError> {
+        let filepath = create_filepath(&reminders_filename(date))?;
+
+        let statuses = match read_file_if_exists(&filepath)? {
+            Some(contents) => parse_statuses(&contents)?,
+            None => BTreeMap::new(),
+        };
+
+        Ok(Reminders { statuses, date })
+    }
+
+    #[cfg(test)]
+    pub fn from_statuses(
+        date: &TuduDate,
+        statuses: BTreeMap<String, ReminderStatus>,
+    ) -> Reminders<'_> {
+        Reminders { statuses, date }
+    }
+
+    pub fn fire(&mut self, key: &str, at: NaiveTime) {
+        self.statuses
+            .insert(String::from(key), ReminderStatus::Fired(at));
+    }
+
+    pub fn snooze(&mut self, key: &str, until: DateTime<FixedOffset>) {
+        self.statuses
+            .insert(String::from(key), ReminderStatus::Snoozed(until));
+    }
+
+    /// Keeps a snooze for a task on an earlier date, as the watcher only looks at the day it is
+    pub fn snooze_from(&mut self, key: &str, date: &TuduDate, until: DateTime<FixedOffset>) {
+        self.statuses.insert(
+            String::from(key),
+            ReminderStatus::SnoozedFrom(date.clone(), until),
+        );
+    }
+
+    pub fn forget(&mut self, key: &str) {
+        self.statuses.remove(key);
+    }
+
+    /// The snoozes of tasks on earlier dates that have run out by the moment, with those dates
+    pub fn snoozed_from_earlier(&self, now: DateTime<FixedOffset>) -> Vec<(TuduDate, String)> {
+        self.statuses
+            .iter()
+            .filter_map(|(key, status)| match status {
+                ReminderStatus::SnoozedFrom(date, until) if *until <= now => {
+                    Some((date.clone(), key.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn write_to_file(&self) -> Result<(), TuduError> {
+        let filepath = create_filepath(&reminders_filename(self.date))?;
+
+        write_file(&filepath, &format_statuses(&self.statuses))
+    }
+}
+
+/// A task whose time has come
+#[derive(Debug, PartialEq, Eq)]
+pub struct Reminder {
+    pub index: TaskIndex,
+    pub key: String,
+    pub task: String,
+    pub at: NaiveTime,
+}
+
+/// The reminders to fire at a moment, for the unfinished tasks whose time has passed that have not
+/// fired for that time yet and are not snoozed until later
+pub fn due_reminders(
+    task_list: &TaskList,
+    reminders: &Reminders,
+    now: DateTime<FixedOffset>,
+) -> Result<Vec<Reminder>, TuduError> {
+    let date = task_list.date().to_naive_date()?;
+
+    let due = flatten_tasks(task_list.tasks())
+        .into_iter()
+        .filter(|(_, task)| matches!(task.state, TaskState::NotStarted | TaskState::Started))
+        .filter_map(|(index, task)| task.at.map(|at| (index, task, at)))
+        .filter(|(_, _, at)| date.and_time(*at) <= now.naive_local())
+        .filter(
+            |(_, task, at)| match reminders.statuses.get(&reminder_key(task)) {
+                Some(ReminderStatus::Fired(fired_at)) => fired_at != at,
+                Some(ReminderStatus::Snoozed(until)) => *until <= now,
+                Some(ReminderStatus::SnoozedFrom(..)) | None => true,
+            },
+        )
+        .map(|(index, task, at)| Reminder {
+            index,
+            key: reminder_key(task),
+            task: task.task.clone(),
+            at,
+        })
+        .collect();
+
+    Ok(due)
+}
+
+/// The reminder for the unfinished task with the key, if it still has a time to be reminded at
+fn find_reminder(task_list: &TaskList, key: &str) -> Option<Reminder> {
+    flatten_tasks(task_list.tasks())
+        .into_iter()
+        .filter(|(_, task)| matches!(task.state, TaskState::NotStarted | TaskState::Started))
+        .find(|(_, task)| reminder_key(task) == key)
+        .and_then(|(index, task)| {
+            task.at.map(|at| Reminder {
+                index,
+                key: String::from(key),
+                task: task.task.clone(),
+                at,
+            })
+        })
+}
+
+/// Where reminders are sent
+#[derive(Debug, PartialEq, Eq)]
+pub enum Notifier {
+    /// Printed by the watcher
+    Stdout,
+    /// Run with `sh -c`, given the reminder in TUDU_REMINDER_TASK, TUDU_REMINDER_INDEX,
+    /// TUDU_REMINDER_TIME and TUDU_REMINDER_DATE env variables
+    Command(String),
+    /// Shown with notify-send, or on macOS with osascript
+    Desktop,
+}
+
+impl Notifier {
+    pub fn from_name(name: &str, command: Option<String>) -> Result<Notifier, TuduError> {
+        match (name, command) {
+            ("stdout", None) => Ok(Notifier::Stdout),
+            ("desktop", None) => Ok(Notifier::Desktop),
+            ("command", Some(command)) => Ok(Notifier::Command(command)),
+            ("command", None) => Err(TuduError::InvalidArguments(String::from(
+                "the command notifier needs a --command to run",
+            ))),
+            ("stdout" | "desktop", Some(_)) => Err(TuduError::InvalidArguments(String::from(
+                "--command is only used by the command notifier",
+            ))),
+            _ => Err(TuduError::InvalidArguments(String::from(
+                "`--notify` accepts one of stdout, command or desktop",
+            ))),
+        }
+    }
+
+    pub fn notify(&self, date: &TuduDate, reminder: &Reminder) -> Result<(), TuduError> {
+        let at = format_time(reminder.at);
+
+        let command = match self {
+            Notifier::Stdout => {
+                let snooze = match *date == TuduDate::today() {
+                    true => format!("tudu snooze {}", reminder.index),
+                    false => format!("tudu snooze {} {}", reminder.index, date.to_iso()),
+                };
+
+                println!(
+                    "{REMINDER_ICON} {at}    {} - {}    snooze with `{snooze}`",
+                    reminder.index, reminder.task
+                );
+                return Ok(());
+            }
+            Notifier::Command(command) => {
+                let mut shell = process::Command::new("sh");
+                shell
+                    .args(["-c", command])
+                    .env("TUDU_REMINDER_TASK", &reminder.task)
+                    .env("TUDU_REMINDER_INDEX", reminder.index.to_string())
+                    .env("TUDU_REMINDER_TIME", &at)
+                    .env("TUDU_REMINDER_DATE", date.to_iso());
+                shell
+            }
+            Notifier::Desktop if cfg!(target_os = "macos") => {
+                let script = format!(
+                    "display notification {:?} with title \"tudu\" subtitle {at:?}",
+                    reminder.task
+                );
+                let mut osascript = process::Command::new("osascript");
+                osascript.args(["-e", &script]);
+                osascript
+            }
+            Notifier::Desktop => {
+                let mut notify_send = process::Command::new("notify-send");
+                notify_send.args([&format!("tudu {at}"), &reminder.task]);
+                notify_send
+            }
+        };
+
+        run_notifier(command)
+    }
+}
+
+fn run_notifier(mut command: process::Command) -> Result<(), TuduError> {
+    let program = command.get_program().to_string_lossy().to_string();
+
+    match command.status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(TuduError::FailedToNotify(format!(
+            "`{program}` exited with {status}"
+        ))),
+        Err(err) => Err(TuduError::FailedToNotify(format!("`{program}` {err}"))),
+    }
+}
+
+/// Sends the reminders due on the date at the moment, along with those of earlier days snoozed
+/// until the date, recording each one sent so it only fires once. The tasks are only locked while
+/// reading and recording, so a notifier can run tudu.
+pub fn check_reminders(
+    date: &TuduDate,
+    now: DateTime<FixedOffset>,
+    notifier: &Notifier,
+) -> Result<usize, TuduError> {
+    let (due, finished) = {
+        let _lock = TasksLock::acquire()?;
+
+        let task_list = TaskList::for_date(date)?;
+        let reminders = Reminders::for_date(date)?;
+
+        let mut due: Vec<(TuduDate, Reminder)> = due_reminders(&task_list, &reminders, now)?
+            .into_iter()
+            .map(|reminder| (date.clone(), reminder))
+            .collect();
+
+        // A snoozed task that has since been finished or lost its time is not reminded of
+        let mut finished = Vec::new();
+
+        for (earlier, key) in reminders.snoozed_from_earlier(now) {
+            match find_reminder(&TaskList::for_date(&earlier)?, &key) {
+                Some(reminder) => due.push((earlier, reminder)),
+                None => finished.push(key),
+            }
+        }
+
+        (due, finished)
+    };
+
+    let mut sent = Vec::new();
+    let mut failure = None;
+
+    for (reminder_date, reminder) in due {
+        match notifier.notify(&reminder_date, &reminder) {
+            Ok(()) => sent.push((reminder_date, reminder)),
+            Err(err) => failure = Some(err),
+        }
+    }
+
+    if !sent.is_empty() || !finished.is_empty() {
+        let _lock = TasksLock::acquire()?;
+
+        let mut reminders = Reminders::for_date(date)?;
+        finished.iter().for_each(|key| reminders.forget(key));
+
+        for (reminder_date, reminder) in sent.iter() {
+            if reminder_date == date {
+                reminders.fire(&reminder.key, reminder.at);
+                continue;
+            }
+
+            reminders.forget(&reminder.key);
+
+            let mut earlier_reminders = Reminders::for_date(reminder_date)?;
+            earlier_reminders.fire(&reminder.key, reminder.at);
+            earlier_reminders.write_to_file()?;
+        }
+
+        reminders.write_to_file()?;
+    }
+
+    match failure {
+        Some(err) => Err(err),
+        None => Ok(sent.len()),
+    }
+}
+
+/// Checks today's reminders every few seconds until the process is stopped, or only once. A
+/// notifier failing is reported without stopping the watcher, so the reminder is tried again.
+pub fn watch(notifier: &Notifier, once: bool) -> Result<(), TuduError> {
+    loop {
+        let checked = check_reminders(&TuduDate::today(), now(), notifier);
+
+        match (checked, once) {
+            (result, true) => return result.map(|_| ()),
+            (Err(err), false) => println!("{}", user_error_message(err)),
+            (Ok(_), false) => {}
+        }
+
+        thread::sleep(CHECK_INTERVAL);
+    }
+}
+
+fn reminders_filename(date: &TuduDate) -> String {
+    format!("{}.reminders", date.to_iso())
+}
+
+/// Reads statuses written as the status followed by the task they are for, e.g. `fired=14:30,id`
+fn parse_statuses(contents: &str) -> Result<BTreeMap<String, ReminderStatus>, TuduError> {
+    contents
+        .lines()
+        .map(|line| {
+            let (status, key) = line.split_once(',').ok_or(TuduError::BadTaskFormat)?;
+
+            let status = match status.split_once('=') {
+                Some(("fired", at)) => ReminderStatus::Fired(
+                    NaiveTime::parse_from_str(at, TIME_FORMAT)
+                        .map_err(|_| TuduError::BadTaskFormat)?,
+                ),
+                Some(("snoozed", until)) => ReminderStatus::Snoozed(
+                    DateTime::parse_from_rfc3339(until).map_err(|_| TuduError::BadTaskFormat)?,
+                ),
+                Some(("snoozed-from", snooze)) => {
+                    let (date, until) = snooze.split_once('/').ok_or(TuduError::BadTaskFormat)?;
+
+                    ReminderStatus::SnoozedFrom(
+                        TuduDate::from_iso(date).map_err(|_| TuduError::BadTaskFormat)?,
+                        DateTime::parse_from_rfc3339(until)
+                            .map_err(|_| TuduError::BadTaskFormat)?,
+                    )
+                }
+                _ => return Err(TuduError::BadTaskFormat),
+            };
+
+            Ok((String::from(key), status))
+        })
+        .collect()
+}
+
+fn format_statuses(statuses: &BTreeMap<String, ReminderStatus>) -> String {
+    statuses
+        .iter()
+        .map(|(key, status)| match status {
+            ReminderStatus::Fired(at) => format!("fired={},{key}\n", format_time(*at)),
+            ReminderStatus::Snoozed(until) => format!(
+                "snoozed={},{key}\n",
+                until.to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+            ),
+            ReminderStatus::SnoozedFrom(date, until) => format!(
+                "snoozed-from={}/{},{key}\n",
+                date.to_iso(),
+                until.to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+            ),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::storage::test_directory;
+
+    fn at(time: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(&format!("2023-06-07T{time}:00+00:00")).unwrap()
+    }
+
+    fn timed(task: &str, time: &str) -> Task {
+        Task {
+            id: Some(task.to_lowercase()),
+            at: Some(parse_time(time).unwrap()),
+            ..Task::new(String::from(task), TaskState::NotStarted)
+        }
+    }
+
+    fn due_tasks(task_list: &TaskList, reminders: &Reminders, now: &str) -> Vec<String> {
+        due_reminders(task_list, reminders, at(now))
+            .unwrap()
+            .into_iter()
+            .map(|reminder| reminder.task)
+            .collect()
+    }
+
+    #[test]
+    fn due_reminders_fire_once_their_time_has_passed() {
+        let date = TuduDate::new(7, 6, 2023);
+        let task_list = TaskList::from_tasks(
+            &date,
+            vec![
+                timed("Standup", "09:30"),
+                Task {
+                    state: TaskState::Complete,
+                    ..timed("Done", "09:00")
+                },
+                Task {
+                    subtasks: vec![timed("Dentist", "14:30")],
+                    ..Task::new(String::from("Errands"), TaskState::NotStarted)
+                },
+            ],
+        );
+        let reminders = Reminders::from_statuses(&date, BTreeMap::new());
+
+        assert_eq!(
+            due_tasks(&task_list, &reminders, "09:00"),
+            Vec::<String>::new()
+        );
+        assert_eq!(due_tasks(&task_list, &reminders, "09:30"), vec!["Standup"]);
+        assert_eq!(
+            due_tasks(&task_list, &reminders, "15:00"),
+            vec!["Standup", "Dentist"]
+        );
+    }
+
+    #[test]
+    fn due_reminders_skips_fired_until_time_changes_and_snoozed_until_later() {
+        let date = TuduDate::new(7, 6, 2023);
+        let task_list = TaskList::from_tasks(
+            &date,
+            vec![
+                timed("Standup", "09:30"),
+                timed("Moved", "11:00"),
+                timed("Dentist", "10:00"),
+            ],
+        );
+        let mut reminders = Reminders::from_statuses(&date, BTreeMap::new());
+        reminders.fire("standup", parse_time("09:30").unwrap());
+        reminders.fire("moved", parse_time("10:00").unwrap());
+        reminders.snooze("dentist", at("11:10"));
+
+        assert_eq!(due_tasks(&task_list, &reminders, "11:05"), vec!["Moved"]);
+        assert_eq!(
+            due_tasks(&task_list, &reminders, "11:10"),
+            vec!["Moved", "Dentist"]
+        );
+    }
+
+    #[test]
+    fn format_then_parse_statuses_gives_back_the_same_statuses() {
+        let statuses = BTreeMap::from([
+            (
+                String::from("lq8x2k"),
+                ReminderStatus::Fired(parse_time("14:30").unwrap()),
+            ),
+            (
+                String::from("Call, the dentist"),
+                ReminderStatus::Snoozed(at("14:40")),
+            ),
+            (
+                String::from("lq9a1b"),
+                ReminderStatus::SnoozedFrom(TuduDate::new(6, 6, 2023), at("00:20")),
+            ),
+        ]);
+
+        assert_eq!(
+            parse_statuses(&format_statuses(&statuses)).unwrap(),
+            statuses
+        );
+    }
+
+    #[test]
+    fn command_notifier_runs_command_with_the_reminder() {
+        let directory = test_directory("reminded");
+        fs::create_dir_all(&directory).unwrap();
+        let output = directory.join("reminded.txt");
+        let notifier = Notifier::Command(format!(
+            "echo \"$TUDU_REMINDER_DATE $TUDU_REMINDER_TIME $TUDU_REMINDER_INDEX $TUDU_REMINDER_TASK\" > {}",
+            output.display()
+        ));
+        let reminder = Reminder {
+            index: TaskIndex {
+                task: 3,
+                subtask: Some(1),
+            },
+            key: String::from("dentist"),
+            task: String::from("Call dentist"),
+            at: parse_time("14:30").unwrap(),
+        };
+
+        notifier
+            .notify(&TuduDate::new(7, 6, 2023), &reminder)
+            .unwrap();
+        let reminded = fs::read_to_string(&output).unwrap();
+
+        // cleanup
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(reminded, "2023-06-07 14:30 3.1 Call dentist\n");
+    }
+
+    #[test]
+    fn failing_command_notifier_gives_error() {
+        let notifier = Notifier::Command(String::from("exit 3"));
+        let reminder = Reminder {
+            index: TaskIndex::from(1),
+            key: String::from("standup"),
+            task: String::from("Standup"),
+            at: parse_time("09:30").unwrap(),
+        };
+
+        let result = notifier.notify(&TuduDate::new(7, 6, 2023), &reminder);
+
+        assert!(matches!(result, Err(TuduError::FailedToNotify(_))));
+    }
+}
@@ -0,0 +1,261 @@
+use crate::error::TuduError;
+use crate::model::{Task, TaskList, TaskState};
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    pub fn from_name(name: &str) -> Result<ReportFormat, TuduError> {
+        match name {
+            "md" => Ok(ReportFormat::Markdown),
+            "html" => Ok(ReportFormat::Html),
+            _ => Err(TuduError::InvalidArguments(String::from(
+                "`report` accepts a format of md or html",
+            ))),
+        }
+    }
+}
+
+/// Renders the task lists as a report, with a summary of how many tasks and subtasks are in each
+/// state followed by a checklist for every day that has tasks, with subtasks under their task
+pub fn render_report(task_lists: &[TaskList], format: &ReportFormat) -> String {
+    let title = match (task_lists.first(), task_lists.last()) {
+        (Some(first), Some(last)) if first.date() != last.date() => format!(
+            "Tasks from {} to {}",
+            first.date().to_iso(),
+            last.date().to_iso()
+        ),
+        (Some(first), _) => format!("Tasks for {}", first.date().to_iso()),
+        _ => String::from("Tasks"),
+    };
+
+    let counts = count_states(task_lists);
+
+    let days: Vec<&TaskList> = task_lists
+        .iter()
+        .filter(|task_list| !task_list.tasks().is_empty())
+        .collect();
+
+    match format {
+        ReportFormat::Markdown => render_markdown(&title, &counts, &days),
+        ReportFormat::Html => render_html(&title, &counts, &days),
+    }
+}
+
+fn count_states(task_lists: &[TaskList]) -> Vec<(&'static TaskState, usize)> {
+    TaskState::ALL
+        .iter()
+        .map(|state| {
+            let count = task_lists
+                .iter()
+                .flat_map(|task_list| task_list.tasks())
+                .flat_map(|task| std::iter::once(task).chain(task.subtasks.iter()))
+                .filter(|task| task.state == *state)
+                .count();
+
+            (state, count)
+        })
+        .collect()
+}
+
+fn render_markdown(title: &str, counts: &[(&TaskState, usize)], days: &[&TaskList]) -> String {
+    let mut report = format!("# {title}\n\n| State | Count |\n| --- | --- |\n");
+
+    for (state, count) in counts {
+        report.push_str(&format!("| {} | {count} |\n", state.name()));
+    }
+
+    for task_list in days {
+        report.push_str(&format!("\n## {}\n\n", task_list.date().to_iso()));
+
+        for task in task_list.tasks() {
+            report.push_str(&format!("{}\n", render_markdown_task(task)));
+
+            for subtask in &task.subtasks {
+                report.push_str(&format!("  {}\n", render_markdown_task(subtask)));
+            }
+        }
+    }
+
+    report
+}
+
+fn render_markdown_task(task: &Task) -> String {
+    let description = &task.task;
+
+    match task.state {
+        TaskState::NotStarted => format!("- [ ] {description}"),
+        TaskState::Started => format!("- [ ] {description} (started)"),
+        TaskState::Complete => format!("- [x] {description}"),
+        TaskState::Forwarded => format!("- [ ] {description} → carried forward"),
+        TaskState::Ignored => format!("- [ ] ~~{description}~~"),
+    }
+}
+
+fn render_html(title: &str, counts: &[(&TaskState, usize)], days: &[&TaskList]) -> String {
+    let mut report = format!(
+        "<h1>{}</h1>\n<table>\n<thead><tr><th>State</th><th>Count</th></tr></thead>\n<tbody>\n",
+        escape_html(title)
+    );
+
+    for (state, count) in counts {
+        report.push_str(&format!(
+            "<tr><td>{}</td><td>{count}</td></tr>\n",
+            state.name()
+        ));
+    }
+
+    report.push_str("</tbody>\n</table>\n");
+
+    for task_list in days {
+        report.push_str(&format!("<h2>{}</h2>\n<ul>\n", task_list.date().to_iso()));
+
+        for task in task_list.tasks() {
+            report.push_str(&format!("{}\n", render_html_task(task)));
+        }
+
+        report.push_str("</ul>\n");
+    }
+
+    report
+}
+
+/// Renders the task as a list item, with its subtasks in a list of their own within it
+fn render_html_task(task: &Task) -> String {
+    let description = escape_html(&task.task);
+
+    let item = match task.state {
+        TaskState::NotStarted => format!("<input type=\"checkbox\" disabled> {description}"),
+        TaskState::Started => {
+            format!("<input type=\"checkbox\" disabled> {description} (started)")
+        }
+        TaskState::Complete => {
+            format!("<input type=\"checkbox\" checked disabled> {description}")
+        }
+        TaskState::Forwarded => {
+            format!("<input type=\"checkbox\" disabled> {description} &rarr; carried forward")
+        }
+        TaskState::Ignored => format!("<input type=\"checkbox\" disabled> <s>{description}</s>"),
+    };
+
+    if task.subtasks.is_empty() {
+        return format!("<li>{item}</li>");
+    }
+
+    let subtasks: String = task
+        .subtasks
+        .iter()
+        .map(|subtask| format!("{}\n", render_html_task(subtask)))
+        .collect();
+
+    format!("<li>{item}\n<ul>\n{subtasks}</ul>\n</li>")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date::TuduDate;
+
+    fn example_tasks() -> Vec<Task> {
+        vec![
+            Task::new(String::from("This task is started"), TaskState::Started),
+            Task::new(String::from("This one is completed"), TaskState::Complete),
+            Task::new(String::from("Didn't like this one"), TaskState::Ignored),
+            Task::new(String::from("This one's for later"), TaskState::Forwarded),
+            Task::new(String::from("Patience is a virtue"), TaskState::NotStarted),
+        ]
+    }
+
+    #[test]
+    fn render_report_as_markdown_summarises_and_lists_each_day() {
+        let first_date = TuduDate::new(7, 6, 2023);
+        let empty_date = TuduDate::new(8, 6, 2023);
+        let last_date = TuduDate::new(9, 6, 2023);
+        let task_lists = vec![
+            TaskList::from_tasks(&first_date, example_tasks()),
+            TaskList::from_tasks(&empty_date, vec![]),
+            TaskList::from_tasks(
+                &last_date,
+                vec![Task::new(String::from("Ship it"), TaskState::Complete)],
+            ),
+        ];
+
+        let expected_report = "# Tasks from 2023-06-07 to 2023-06-09
+
+| State | Count |
+| --- | --- |
+| Not started | 1 |
+| Started | 1 |
+| Completed | 2 |
+| Carried forward | 1 |
+| Not doing | 1 |
+
+## 2023-06-07
+
+- [ ] This task is started (started)
+- [x] This one is completed
+- [ ] ~~Didn't like this one~~
+- [ ] This one's for later → carried forward
+- [ ] Patience is a virtue
+
+## 2023-06-09
+
+- [x] Ship it
+";
+
+        let report = render_report(&task_lists, &ReportFormat::Markdown);
+
+        assert_eq!(report, expected_report);
+    }
+
+    #[test]
+    fn render_report_includes_subtasks() {
+        let date = TuduDate::new(7, 6, 2023);
+        let mut parent = Task::new(String::from("Ship it"), TaskState::Started);
+        parent.subtasks = vec![
+            Task::new(String::from("Write docs"), TaskState::Complete),
+            Task::new(String::from("Tag release"), TaskState::NotStarted),
+        ];
+        let task_lists = vec![TaskList::from_tasks(&date, vec![parent])];
+
+        let markdown = render_report(&task_lists, &ReportFormat::Markdown);
+        let html = render_report(&task_lists, &ReportFormat::Html);
+
+        assert!(markdown.contains("| Completed | 1 |"));
+        assert!(
+            markdown.contains("- [ ] Ship it (started)\n  - [x] Write docs\n  - [ ] Tag release\n")
+        );
+        assert!(html.contains("<li><input type=\"checkbox\" disabled> Ship it (started)\n<ul>\n<li><input type=\"checkbox\" checked disabled> Write docs</li>\n"));
+    }
+
+    #[test]
+    fn render_report_as_html_escapes_tasks() {
+        let date = TuduDate::new(7, 6, 2023);
+        let task_lists = vec![TaskList::from_tasks(
+            &date,
+            vec![
+                Task::new(String::from("Fix <br> & co"), TaskState::Ignored),
+                Task::new(String::from("Review"), TaskState::Complete),
+            ],
+        )];
+
+        let report = render_report(&task_lists, &ReportFormat::Html);
+
+        assert!(report.starts_with("<h1>Tasks for 2023-06-07</h1>\n"));
+        assert!(report.contains("<tr><td>Not doing</td><td>1</td></tr>"));
+        assert!(report.contains(
+            "<li><input type=\"checkbox\" disabled> <s>Fix &lt;br&gt; &amp; co</s></li>"
+        ));
+        assert!(report.contains("<li><input type=\"checkbox\" checked disabled> Review</li>"));
+    }
+}
@@ -0,0 +1,351 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Scope};
+use serde_json::Value;
+
+use crate::date::{DateRange, TuduDate};
+use crate::error::{user_error_message, TuduError};
+use crate::estimate::split_estimate;
+use crate::execute::{
+    add_task, derive_parent_states_if_enabled, print_tasks, remove_task, set_task_state,
+};
+use crate::history::record_change;
+use crate::hooks::Hook;
+use crate::model::{Task, TaskIndex, TaskList, TaskState};
+use crate::server::{parse_date, task_to_json};
+use crate::storage::{create_root_filepath, list_file_dates, TasksLock};
+
+pub const SCRIPTS_DIRECTORY: &str = "scripts";
+const SCRIPT_EXTENSION: &str = "rhai";
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Finds the script for a command that is not built in, e.g. `scripts/triage.rhai` for
+/// `tudu triage`
+pub fn find_script(name: &str) -> Result<Option<PathBuf>, TuduError> {
+    // Only scripts directly in the scripts directory are commands
+    if name.is_empty() || name.starts_with('.') || name.contains('/') {
+        return Ok(None);
+    }
+
+    let script = scripts_directory()?.join(format!("{name}.{SCRIPT_EXTENSION}"));
+
+    Ok(script.is_file().then_some(script))
+}
+
+/// Scripts are kept in the directory set in the TUDU_SCRIPTS env variable, falling back to the
+/// `scripts` folder of the tasks directory
+fn scripts_directory() -> Result<PathBuf, TuduError> {
+    match env::var("TUDU_SCRIPTS") {
+        Ok(directory) => Ok(PathBuf::from(directory)),
+        Err(_) => create_root_filepath(SCRIPTS_DIRECTORY),
+    }
+}
+
+/// Runs the script with the arguments given after the command in `ARGS`
+pub fn run_script(name: &str, script: &Path, args: &[String]) -> Result<(), TuduError> {
+    let engine = create_engine();
+
+    let mut scope = Scope::new();
+    let args: Array = args.iter().cloned().map(Dynamic::from).collect();
+    scope.push_constant("ARGS", args);
+
+    engine
+        .run_file_with_scope(&mut scope, script.to_path_buf())
+        .map_err(|err| TuduError::ScriptFailed(format!("`{name}` {err}")))
+}
+
+/// An engine with the api scripts have over the tasks. Dates can be given in ISO form, as they are
+/// given back, or in any form the cli takes, and each change is saved, recorded and runs the hooks
+/// the same as the command making it would.
+fn create_engine() -> Engine {
+    let mut engine = Engine::new();
+
+    engine
+        .register_fn("today", || TuduDate::today().to_iso())
+        .register_fn("date", |date: &str| {
+            to_script(parse_date(date)).map(|date| date.to_iso())
+        })
+        .register_fn("add_days", |date: &str, days: i64| {
+            to_script(parse_date(date).and_then(|date| date.add_days(days)))
+                .map(|date| date.to_iso())
+        })
+        .register_fn("dates", || to_script(task_dates()))
+        .register_fn("dates", |from: &str, to: &str| {
+            to_script(dates_between(from, to))
+        })
+        .register_fn("tasks", |date: &str| to_script(day_tasks(date)))
+        .register_fn("view", |date: &str| to_script(view_day(date)))
+        .register_fn("add", |date: &str, task: &str| {
+            to_script(add_to_day(date, task, None))
+        })
+        .register_fn("add", |date: &str, task: &str, parent: i64| {
+            to_script(add_to_day(date, task, Some(parent)))
+        })
+        .register_fn("set", |date: &str, index: Dynamic, state: &str| {
+            to_script(parse_index(index).and_then(|index| set_in_day(date, index, state)))
+        })
+        .register_fn("edit", |date: &str, index: Dynamic, task: &str| {
+            to_script(parse_index(index).and_then(|index| edit_in_day(date, index, task)))
+        })
+        .register_fn("remove", |date: &str, index: Dynamic| {
+            to_script(parse_index(index).and_then(|index| remove_from_day(date, index)))
+        });
+
+    engine
+}
+
+/// Gives an error to the script as the message the cli would print for it
+fn to_script<T>(result: Result<T, TuduError>) -> ScriptResult<T> {
+    result.map_err(|err| user_error_message(err).into())
+}
+
+/// Reads an index given as a number such as `3` or a string such as `"3.1"`. Other numbers are
+/// refused, as a float such as `3.10` would be read as subtask 1 rather than 10.
+fn parse_index(index: Dynamic) -> Result<TaskIndex, TuduError> {
+    if let Ok(index) = index.as_int() {
+        return usize::try_from(index)
+            .map(TaskIndex::from)
+            .map_err(|_| TuduError::InvalidIndex);
+    }
+
+    match index.into_string() {
+        Ok(index) => TaskIndex::parse(&index),
+        Err(type_name) => Err(TuduError::InvalidArguments(format!(
+            "a task index is given as a number such as 3 or a string such as \"3.1\", not a {type_name}"
+        ))),
+    }
+}
+
+/// The dates that have tasks, in order
+fn task_dates() -> Result<Array, TuduError> {
+    Ok(list_file_dates("")?
+        .iter()
+        .map(|date| Dynamic::from(date.to_iso()))
+        .collect())
+}
+
+/// Every date from one to another, whether or not they have tasks
+fn dates_between(from: &str, to: &str) -> Result<Array, TuduError> {
+    Ok(DateRange::new(parse_date(from)?, parse_date(to)?)?
+        .dates()?
+        .iter()
+        .map(|date| Dynamic::from(date.to_iso()))
+        .collect())
+}
+
+/// The tasks of a day as maps, with the same fields as the server gives
+fn day_tasks(date: &str) -> Result<Array, TuduError> {
+    let date = parse_date(date)?;
+    let task_list = TaskList::for_date(&date)?;
+
+    Ok(task_list
+        .tasks()
+        .iter()
+        .enumerate()
+        .map(|(position, task)| json_to_dynamic(&task_to_json((position + 1).into(), task)))
+        .collect())
+}
+
+fn view_day(date: &str) -> Result<(), TuduError> {
+    let date = parse_date(date)?;
+
+    print_tasks(&TaskList::for_date(&date)?)
+}
+
+/// Adds a task to a day, giving back its index
+fn add_to_day(date: &str, task: &str, parent: Option<i64>) -> Result<String, TuduError> {
+    let (task, estimate) = split_estimate(task)?;
+    let parent = parent
+        .map(|parent| usize::try_from(parent).map_err(|_| TuduError::InvalidIndex))
+        .transpose()?;
+
+    let mut new_task = Task::new(task, TaskState::NotStarted);
+    new_task.estimate = estimate;
+
+    change_day(date, |task_list| {
+        let message = format!("add {} {}", task_list.date().to_iso(), new_task.task);
+        let post_add = add_task(task_list, new_task, parent)?;
+
+        let index = match parent {
+            Some(parent) => TaskIndex {
+                task: parent,
+                subtask: task_list
+                    .get_task(parent.into())
+                    .map(|parent| parent.subtasks.len()),
+            },
+            None => task_list.tasks().len().into(),
+        };
+
+        Ok((index.to_string(), message, vec![post_add]))
+    })
+}
+
+fn set_in_day(date: &str, index: TaskIndex, state: &str) -> Result<(), TuduError> {
+    let state = TaskState::from_code(state).ok_or(TuduError::InvalidState)?;
+
+    change_day(date, |task_list| {
+        let message = format!(
+            "set {} #{index} → {}",
+            task_list.date().to_iso(),
+            state.name()
+        );
+        let hooks = set_task_state(task_list, index, state)?;
+
+        Ok(((), message, hooks))
+    })
+}
+
+fn edit_in_day(date: &str, index: TaskIndex, task: &str) -> Result<(), TuduError> {
+    let (task, estimate) = split_estimate(task)?;
+
+    change_day(date, |task_list| {
+        task_list.edit_task(index, task)?;
+
+        // An edit without an estimate keeps the one the task already had
+        if estimate.is_some() {
+            task_list.set_task_estimate(index, estimate)?;
+        }
+
+        let message = format!("edit {} #{index}", task_list.date().to_iso());
+
+        Ok(((), message, Vec::new()))
+    })
+}
+
+/// Removes a task the same as the command does, which saves the day itself along with the notes
+/// and time of the task
+fn remove_from_day(date: &str, index: TaskIndex) -> Result<(), TuduError> {
+    let date = parse_date(date)?;
+
+    let lock = TasksLock::acquire()?;
+
+    let mut task_list = TaskList::for_date(&date)?;
+
+    let (message, on_remove) = remove_task(&mut task_list, index)?;
+
+    record_change(&message)?;
+
+    drop(lock);
+    on_remove.run_after_change();
+
+    Ok(())
+}
+
+/// Changes the tasks of a day while holding the lock on them, then saves and records the change
+/// before running the hooks for it
+fn change_day<T>(
+    date: &str,
+    change: impl FnOnce(&mut TaskList) -> Result<(T, String, Vec<Hook>), TuduError>,
+) -> Result<T, TuduError> {
+    let date = parse_date(date)?;
+
+    let lock = TasksLock::acquire()?;
+
+    let mut task_list = TaskList::for_date(&date)?;
+
+    let (changed, message, hooks) = change(&mut task_list)?;
+
+    derive_parent_states_if_enabled(&mut task_list);
+
+    task_list.write_to_file()?;
+
+    record_change(&message)?;
+
+    drop(lock);
+    hooks.iter().for_each(Hook::run_after_change);
+
+    Ok(changed)
+}
+
+fn json_to_dynamic(value: &Value) -> Dynamic {
+    match value {
+        Value::Null => Dynamic::UNIT,
+        Value::Bool(value) => Dynamic::from(*value),
+        Value::Number(value) => value.as_i64().map_or(Dynamic::UNIT, Dynamic::from),
+        Value::String(value) => Dynamic::from(value.clone()),
+        Value::Array(values) => Dynamic::from_array(values.iter().map(json_to_dynamic).collect()),
+        Value::Object(fields) => Dynamic::from_map(
+            fields
+                .iter()
+                .map(|(field, value)| (field.into(), json_to_dynamic(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn find_script_ignores_names_outside_scripts_directory() {
+        assert_eq!(find_script("../secrets"), Ok(None));
+        assert_eq!(find_script(".hidden"), Ok(None));
+        assert_eq!(find_script(""), Ok(None));
+    }
+
+    #[test]
+    fn engine_reads_dates_like_the_cli() {
+        let engine = create_engine();
+
+        let date: String = engine.eval(r#"date("7-6-2023")"#).unwrap();
+        let next_day: String = engine.eval(r#"add_days("2023-06-30", 1)"#).unwrap();
+        let range: Array = engine.eval(r#"dates("2023-06-07", "9-6-2023")"#).unwrap();
+        let invalid = engine.eval::<String>(r#"date("the 7th")"#);
+
+        assert_eq!(date, "2023-06-07");
+        assert_eq!(next_day, "2023-07-01");
+        assert_eq!(range.len(), 3);
+        assert!(invalid
+            .unwrap_err()
+            .to_string()
+            .contains("The date entered is not a valid date"));
+    }
+
+    #[test]
+    fn parse_index_takes_numbers_and_strings_but_not_floats() {
+        assert_eq!(parse_index(Dynamic::from(3_i64)), Ok(TaskIndex::from(3)));
+        assert_eq!(
+            parse_index(Dynamic::from("3.10")),
+            Ok(TaskIndex {
+                task: 3,
+                subtask: Some(10)
+            })
+        );
+        assert_eq!(
+            parse_index(Dynamic::from(-1_i64)),
+            Err(TuduError::InvalidIndex)
+        );
+        assert!(matches!(
+            parse_index(Dynamic::from(3.1_f64)),
+            Err(TuduError::InvalidArguments(_))
+        ));
+    }
+
+    #[test]
+    fn json_to_dynamic_gives_maps_scripts_can_read() {
+        let task = json_to_dynamic(&json!({
+            "task": "Write docs",
+            "estimate_minutes": 30,
+            "due": null,
+            "subtasks": [{ "task": "Intro" }],
+        }));
+
+        let engine = create_engine();
+        let mut scope = Scope::new();
+        scope.push("task", task);
+
+        let summary: String = engine
+            .eval_with_scope(
+                &mut scope,
+                r#"`${task.task} ${task.estimate_minutes} ${task.due == ()} ${task.subtasks[0].task}`"#,
+            )
+            .unwrap();
+
+        assert_eq!(summary, "Write docs 30 true Intro");
+    }
+}
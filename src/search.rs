@@ -0,0 +1,105 @@
+use crate::dependency::flatten_tasks;
+use crate::journal::Journal;
+use crate::model::TaskList;
+use crate::notes::{Notes, NOTE_MARKER};
+
+/// Finds the tasks, notes and journal entries for a day that contain the query, ignoring case. Each
+/// match is given as a line starting with the date, followed by the task number or the time of the
+/// journal entry.
+pub fn search_day(task_list: &TaskList, notes: &Notes, journal: &Journal, query: &str) -> String {
+    let query = query.to_lowercase();
+    let is_match = |text: &str| text.to_lowercase().contains(&query);
+
+    let date = task_list.date().to_iso();
+    let mut results = String::new();
+
+    for (index, task) in flatten_tasks(task_list.tasks()) {
+        if is_match(&task.task) {
+            results.push_str(&format!(
+                "{date}    {index}    {} - {}\n",
+                task.state.icon(),
+                task.task
+            ));
+        }
+
+        let note = task.id.as_deref().and_then(|id| notes.get(id));
+
+        for line in note.into_iter().flat_map(str::lines) {
+            if is_match(line) {
+                results.push_str(&format!(
+                    "{date}    {index}    {NOTE_MARKER} {}\n",
+                    line.trim()
+                ));
+            }
+        }
+    }
+
+    for entry in journal.entries() {
+        if is_match(&entry.text) {
+            results.push_str(&format!(
+                "{date}    {}    {}\n",
+                entry.formatted_time(),
+                entry.text.replace('\n', " ")
+            ));
+        }
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date::TuduDate;
+    use crate::model::{Task, TaskState};
+    use chrono::DateTime;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn search_day_finds_tasks_and_journal_entries_ignoring_case() {
+        let date = TuduDate::new(7, 6, 2023);
+        let mut release = Task::new(String::from("Release 1.2"), TaskState::Started);
+        release.id = Some(String::from("abc"));
+        release.subtasks = vec![Task::new(
+            String::from("Tag the release"),
+            TaskState::Complete,
+        )];
+        let task_list = TaskList::from_tasks(
+            &date,
+            vec![
+                Task::new(String::from("Unrelated"), TaskState::NotStarted),
+                release,
+            ],
+        );
+        let notes = Notes::from_notes(
+            &date,
+            BTreeMap::from([(
+                String::from("abc"),
+                String::from("Steps:\n  - update the release notes"),
+            )]),
+        );
+        let mut journal = Journal::from_entries(&date, vec![]);
+        journal
+            .add_entry(
+                "Fixed the RELEASE script\nafter lunch",
+                DateTime::parse_from_rfc3339("2023-06-07T09:15:00+01:00").unwrap(),
+            )
+            .unwrap();
+        journal
+            .add_entry(
+                "Nothing to see",
+                DateTime::parse_from_rfc3339("2023-06-07T10:00:00+01:00").unwrap(),
+            )
+            .unwrap();
+
+        let expected_results = "2023-06-07    2    ◐ - Release 1.2
+2023-06-07    2    ✎ - update the release notes
+2023-06-07    2.1    ● - Tag the release
+2023-06-07    09:15    Fixed the RELEASE script after lunch
+";
+
+        let results = search_day(&task_list, &notes, &journal, "release");
+
+        assert_eq!(results, expected_results);
+    }
+}
@@ -0,0 +1,576 @@
+use std::net::IpAddr;
+
+use chrono::Duration;
+use serde_json::{json, Value};
+use tiny_http::{Header, Response, Server};
+
+use crate::date::{DateRange, TuduDate};
+use crate::dependency::{new_task_id, Dependency};
+use crate::error::{user_error_message, TuduError};
+use crate::estimate::split_estimate;
+use crate::execute::{
+    self, derive_parent_states_if_enabled, move_task, remove_task, set_task_state,
+};
+use crate::history::record_change;
+use crate::hooks::Hook;
+use crate::model::{Task, TaskIndex, TaskList, TaskState};
+use crate::priority::parse_priority;
+use crate::reminders::{format_time, parse_time};
+use crate::storage::TasksLock;
+
+/// The most days that can be fetched at once, so a mistyped year does not read thousands of days
+const MAX_RANGE_DAYS: usize = 366;
+
+/// A request to the server, reduced to what the endpoints use
+#[derive(Debug, PartialEq, Eq)]
+pub struct ApiRequest {
+    pub method: String,
+    pub url: String,
+    pub authorization: Option<String>,
+    pub body: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+/// Serves the tasks as JSON until the process is stopped, handling one request at a time. When a
+/// token is given every request has to send it as `Authorization: Bearer TOKEN`.
+pub fn serve(host: &str, port: u16, token: Option<&str>) -> Result<(), TuduError> {
+    let server = Server::http((host, port)).map_err(|err| {
+        TuduError::InvalidArguments(format!("could not serve on {host}:{port}, {err}"))
+    })?;
+
+    let is_local = host == "localhost" || host.parse().is_ok_and(|ip: IpAddr| ip.is_loopback());
+
+    if !is_local && token.is_none() {
+        println!("Anyone who can reach {host} can change the tasks, give a --token to require one");
+    }
+
+    println!("Serving the tasks on http://{host}:{port}");
+
+    for mut request in server.incoming_requests() {
+        let mut body = String::new();
+
+        let response = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => {
+                let authorization = request
+                    .headers()
+                    .iter()
+                    .find(|header| header.field.equiv("Authorization"))
+                    .map(|header| header.value.to_string());
+
+                handle_request(
+                    &ApiRequest {
+                        method: request.method().to_string(),
+                        url: request.url().to_string(),
+                        authorization,
+                        body,
+                    },
+                    token,
+                )
+            }
+            Err(_) => error_response(
+                400,
+                String::from("The body of the request has to be UTF-8 JSON"),
+            ),
+        };
+
+        let content_type = Header::from_bytes("Content-Type", "application/json")
+            .expect("the content type header is valid");
+
+        // A client that went away before the response was sent is not a reason to stop serving
+        let _ = request.respond(
+            Response::from_string(response.body.to_string())
+                .with_status_code(response.status)
+                .with_header(content_type),
+        );
+    }
+
+    Ok(())
+}
+
+/// Routes the request to its endpoint:
+///
+/// - `GET /days/DATE` gives the tasks for a date
+/// - `GET /days?from=DATE&to=DATE` gives the tasks for every date in the range
+/// - `POST /days/DATE/tasks` adds a task, e.g. `{"task": "Write docs ~30m", "parent": 1}`
+/// - `PATCH /days/DATE/tasks/INDEX` changes a task, e.g. `{"state": "C", "task": "Write the docs"}`
+/// - `DELETE /days/DATE/tasks/INDEX` removes a task
+/// - `POST /days/DATE/tasks/INDEX/move` moves a task, e.g. `{"to": 1}`
+pub fn handle_request(request: &ApiRequest, token: Option<&str>) -> ApiResponse {
+    if let Some(token) = token {
+        if request.authorization.as_deref() != Some(&format!("Bearer {token}")) {
+            return error_response(
+                401,
+                String::from("A valid token has to be sent as `Authorization: Bearer TOKEN`"),
+            );
+        }
+    }
+
+    let (path, query) = request
+        .url
+        .split_once('?')
+        .unwrap_or((request.url.as_str(), ""));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let body = request.body.as_str();
+
+    let result = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["days"]) => get_days(query),
+        ("GET", ["days", date]) => get_day(date),
+        ("POST", ["days", date, "tasks"]) => add_task(date, body),
+        ("PATCH", ["days", date, "tasks", index]) => update_task(date, index, body),
+        ("DELETE", ["days", date, "tasks", index]) => delete_task(date, index),
+        ("POST", ["days", date, "tasks", index, "move"]) => move_task_to(date, index, body),
+        (method, _) => {
+            return error_response(404, format!("There is no endpoint for {method} {path}"))
+        }
+    };
+
+    match result {
+        Ok(response) => response,
+        Err(err) => error_response(error_status(&err), user_error_message(err)),
+    }
+}
+
+fn get_day(date: &str) -> Result<ApiResponse, TuduError> {
+    let date = parse_date(date)?;
+    let task_list = TaskList::for_date(&date)?;
+
+    Ok(ok_response(200, task_list_to_json(&task_list)))
+}
+
+fn get_days(query: &str) -> Result<ApiResponse, TuduError> {
+    let parameter = |name: &str| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    };
+
+    let from = match parameter("from") {
+        Some(from) => parse_date(from)?,
+        None => TuduDate::today(),
+    };
+    let to = match parameter("to") {
+        Some(to) => parse_date(to)?,
+        None => from.clone(),
+    };
+
+    let dates = DateRange::new(from, to)?.dates()?;
+
+    if dates.len() > MAX_RANGE_DAYS {
+        return Err(TuduError::InvalidArguments(format!(
+            "at most {MAX_RANGE_DAYS} days can be fetched at once"
+        )));
+    }
+
+    let days = dates
+        .iter()
+        .map(|date| TaskList::for_date(date).map(|task_list| task_list_to_json(&task_list)))
+        .collect::<Result<Vec<Value>, TuduError>>()?;
+
+    Ok(ok_response(200, Value::Array(days)))
+}
+
+fn add_task(date: &str, body: &str) -> Result<ApiResponse, TuduError> {
+    let date = parse_date(date)?;
+    let body = parse_body(body)?;
+
+    let task = string_field(&body, "task")?
+        .ok_or_else(|| TuduError::InvalidArguments(String::from("the task is missing")))?;
+    let (task, estimate) = split_estimate(task)?;
+
+    let message = format!("add {} {}", date.to_iso(), task);
+
+    let mut new_task = Task::new(task, TaskState::NotStarted);
+    new_task.estimate = estimate;
+    new_task.due = string_field(&body, "due")?.map(parse_date).transpose()?;
+    new_task.priority = string_field(&body, "priority")?
+        .map(parse_priority)
+        .transpose()?;
+    new_task.at = string_field(&body, "at")?.map(parse_time).transpose()?;
+
+    // Reminders are kept by task id
+    if new_task.at.is_some() {
+        new_task.id = Some(new_task_id());
+    }
+
+    let lock = TasksLock::acquire()?;
+
+    let mut task_list = TaskList::for_date(&date)?;
+
+    let post_add = execute::add_task(&mut task_list, new_task, index_field(&body, "parent")?)?;
+
+    derive_parent_states_if_enabled(&mut task_list);
+
+    task_list.write_to_file()?;
+
+    record_change(&message)?;
+
+    drop(lock);
+    post_add.run_after_change();
+
+    Ok(ok_response(201, task_list_to_json(&task_list)))
+}
+
+fn update_task(date: &str, index: &str, body: &str) -> Result<ApiResponse, TuduError> {
+    let date = parse_date(date)?;
+    let index = TaskIndex::parse(index)?;
+    let body = parse_body(body)?;
+
+    let state = string_field(&body, "state")?
+        .map(|state| TaskState::from_code(state).ok_or(TuduError::InvalidState))
+        .transpose()?;
+    let task = string_field(&body, "task")?
+        .map(split_estimate)
+        .transpose()?;
+
+    if state.is_none() && task.is_none() {
+        return Err(TuduError::InvalidArguments(String::from(
+            "give a state or task to change",
+        )));
+    }
+
+    let lock = TasksLock::acquire()?;
+
+    let mut task_list = TaskList::for_date(&date)?;
+
+    let mut changes = Vec::new();
+    let mut hooks = Vec::new();
+
+    if let Some((task, estimate)) = task {
+        task_list.edit_task(index, task)?;
+
+        // A description without an estimate keeps the one the task already had
+        if estimate.is_some() {
+            task_list.set_task_estimate(index, estimate)?;
+        }
+
+        changes.push(format!("edit {} #{index}", date.to_iso()));
+    }
+
+    if let Some(state) = state {
+        changes.push(format!("set {} #{index} → {}", date.to_iso(), state.name()));
+
+        hooks = set_task_state(&mut task_list, index, state)?;
+    }
+
+    derive_parent_states_if_enabled(&mut task_list);
+
+    task_list.write_to_file()?;
+
+    record_change(&changes.join(", "))?;
+
+    drop(lock);
+    hooks.iter().for_each(Hook::run_after_change);
+
+    Ok(ok_response(200, task_list_to_json(&task_list)))
+}
+
+fn delete_task(date: &str, index: &str) -> Result<ApiResponse, TuduError> {
+    let date = parse_date(date)?;
+    let index = TaskIndex::parse(index)?;
+
+    let lock = TasksLock::acquire()?;
+
+    let mut task_list = TaskList::for_date(&date)?;
+
+    let (message, on_remove) = remove_task(&mut task_list, index)?;
+
+    record_change(&message)?;
+
+    drop(lock);
+    on_remove.run_after_change();
+
+    Ok(ok_response(200, task_list_to_json(&task_list)))
+}
+
+fn move_task_to(date: &str, index: &str, body: &str) -> Result<ApiResponse, TuduError> {
+    let date = parse_date(date)?;
+    let index = TaskIndex::parse(index)?;
+    let body = parse_body(body)?;
+
+    if index.subtask.is_some() {
+        return Err(TuduError::InvalidArguments(String::from(
+            "only tasks can be moved, not subtasks",
+        )));
+    }
+
+    let to = index_field(&body, "to")?.ok_or_else(|| {
+        TuduError::InvalidArguments(String::from("the index to move the task to is missing"))
+    })?;
+
+    let _lock = TasksLock::acquire()?;
+
+    let mut task_list = TaskList::for_date(&date)?;
+
+    let message = move_task(&mut task_list, index.task, to)?;
+
+    record_change(&message)?;
+
+    Ok(ok_response(200, task_list_to_json(&task_list)))
+}
+
+/// Reads a date in ISO form, as the server gives them back, or in any form the cli takes
+pub fn parse_date(date: &str) -> Result<TuduDate, TuduError> {
+    TuduDate::from_iso(date).or_else(|_| TuduDate::from_date(date))
+}
+
+fn parse_body(body: &str) -> Result<Value, TuduError> {
+    serde_json::from_str(body)
+        .map_err(|err| TuduError::InvalidArguments(format!("the body is not valid JSON, {err}")))
+}
+
+fn string_field<'a>(body: &'a Value, field: &str) -> Result<Option<&'a str>, TuduError> {
+    match body.get(field) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(_) => Err(TuduError::InvalidArguments(format!(
+            "`{field}` has to be a string"
+        ))),
+    }
+}
+
+fn index_field(body: &Value, field: &str) -> Result<Option<usize>, TuduError> {
+    match body.get(field) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => match value.as_u64() {
+            Some(index) => Ok(Some(index as usize)),
+            None => Err(TuduError::InvalidArguments(format!(
+                "`{field}` has to be the number of a task"
+            ))),
+        },
+    }
+}
+
+pub fn task_list_to_json(task_list: &TaskList) -> Value {
+    let tasks: Vec<Value> = task_list
+        .tasks()
+        .iter()
+        .enumerate()
+        .map(|(position, task)| task_to_json((position + 1).into(), task))
+        .collect();
+
+    json!({
+        "date": task_list.date().to_iso(),
+        "tasks": tasks,
+    })
+}
+
+pub fn task_to_json(index: TaskIndex, task: &Task) -> Value {
+    let after: Vec<String> = task.after.iter().map(|after| after.format()).collect();
+
+    let subtasks: Vec<Value> = task
+        .subtasks
+        .iter()
+        .enumerate()
+        .map(|(subtask, task)| {
+            let index = TaskIndex {
+                task: index.task,
+                subtask: Some(subtask + 1),
+            };
+
+            task_to_json(index, task)
+        })
+        .collect();
+
+    json!({
+        "index": index.to_string(),
+        "task": task.task,
+        "state": task.state.code(),
+        "state_name": task.state.name(),
+        "estimate_minutes": task.estimate.map(|estimate| estimate.num_minutes()),
+        "due": task.due.as_ref().map(TuduDate::to_iso),
+        "priority": task.priority.map(String::from),
+        "at": task.at.map(format_time),
+        "id": task.id,
+        "after": after,
+        "subtasks": subtasks,
+    })
+}
+
+/// Reads a task back from the JSON given by `task_to_json`, which keeps everything in the day file
+pub fn task_from_json(value: &Value) -> Result<Task, TuduError> {
+    let text = |field: &str| match &value[field] {
+        Value::Null => Ok(None),
+        Value::String(text) => Ok(Some(text.as_str())),
+        _ => Err(TuduError::BadTaskFormat),
+    };
+    let list = |field: &str| match &value[field] {
+        Value::Null => Ok(Vec::new()),
+        Value::Array(values) => Ok(values.clone()),
+        _ => Err(TuduError::BadTaskFormat),
+    };
+
+    let description = text("task")?.ok_or(TuduError::BadTaskFormat)?;
+    let state = text("state")?
+        .and_then(TaskState::from_code)
+        .ok_or(TuduError::BadTaskFormat)?;
+
+    let mut task = Task::new(String::from(description), state);
+
+    task.estimate = match &value["estimate_minutes"] {
+        Value::Null => None,
+        minutes => Some(Duration::minutes(
+            minutes.as_i64().ok_or(TuduError::BadTaskFormat)?,
+        )),
+    };
+    task.due = text("due")?
+        .map(|due| TuduDate::from_iso(due).map_err(|_| TuduError::BadTaskFormat))
+        .transpose()?;
+    task.priority = text("priority")?
+        .map(|priority| parse_priority(priority).map_err(|_| TuduError::BadTaskFormat))
+        .transpose()?;
+    task.at = text("at")?
+        .map(|at| parse_time(at).map_err(|_| TuduError::BadTaskFormat))
+        .transpose()?;
+    task.id = text("id")?.map(String::from);
+    task.after = list("after")?
+        .iter()
+        .map(|after| Dependency::parse(after.as_str().ok_or(TuduError::BadTaskFormat)?))
+        .collect::<Result<Vec<Dependency>, TuduError>>()?;
+    task.subtasks = list("subtasks")?
+        .iter()
+        .map(task_from_json)
+        .collect::<Result<Vec<Task>, TuduError>>()?;
+
+    Ok(task)
+}
+
+fn ok_response(status: u16, body: Value) -> ApiResponse {
+    ApiResponse { status, body }
+}
+
+fn error_response(status: u16, error: String) -> ApiResponse {
+    ApiResponse {
+        status,
+        body: json!({ "error": error }),
+    }
+}
+
+pub fn error_status(error: &TuduError) -> u16 {
+    match error {
+        TuduError::InvalidDate | TuduError::InvalidArguments(_) | TuduError::InvalidState => 400,
+        TuduError::InvalidIndex => 404,
+        TuduError::ChangedElsewhere(_) => 409,
+        TuduError::HookFailed(_) => 422,
+        _ => 500,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, url: &str, body: &str) -> ApiRequest {
+        ApiRequest {
+            method: String::from(method),
+            url: String::from(url),
+            authorization: None,
+            body: String::from(body),
+        }
+    }
+
+    #[test]
+    fn handle_request_without_the_token_is_unauthorised() {
+        let response = handle_request(&request("GET", "/days/today", ""), Some("secret"));
+
+        assert_eq!(response.status, 401);
+    }
+
+    #[test]
+    fn handle_request_for_unknown_endpoint_is_not_found() {
+        let response = handle_request(&request("PUT", "/days/today", ""), None);
+
+        assert_eq!(
+            response,
+            error_response(
+                404,
+                String::from("There is no endpoint for PUT /days/today")
+            )
+        );
+    }
+
+    #[test]
+    fn handle_request_with_invalid_input_is_a_bad_request() {
+        let bad_date = handle_request(&request("GET", "/days/31-2-2023x", ""), None);
+        let bad_json = handle_request(&request("POST", "/days/today/tasks", "{task"), None);
+        let bad_state = handle_request(
+            &request("PATCH", "/days/today/tasks/1", r#"{"state": "Q"}"#),
+            None,
+        );
+
+        assert_eq!(bad_date.status, 400);
+        assert_eq!(bad_json.status, 400);
+        assert_eq!(bad_state.status, 400);
+    }
+
+    #[test]
+    fn task_list_to_json_includes_subtasks_with_their_indexes() {
+        let date = TuduDate::new(7, 6, 2023);
+        let task_list = TaskList::from_tasks(
+            &date,
+            vec![Task {
+                estimate: Some(Duration::minutes(30)),
+                priority: Some('A'),
+                subtasks: vec![Task::new(String::from("Tag"), TaskState::Complete)],
+                ..Task::new(String::from("Release"), TaskState::Started)
+            }],
+        );
+
+        let expected_json = json!({
+            "date": "2023-06-07",
+            "tasks": [{
+                "index": "1",
+                "task": "Release",
+                "state": "S",
+                "state_name": TaskState::Started.name(),
+                "estimate_minutes": 30,
+                "due": null,
+                "priority": "A",
+                "at": null,
+                "id": null,
+                "after": [],
+                "subtasks": [{
+                    "index": "1.1",
+                    "task": "Tag",
+                    "state": "C",
+                    "state_name": TaskState::Complete.name(),
+                    "estimate_minutes": null,
+                    "due": null,
+                    "priority": null,
+                    "at": null,
+                    "id": null,
+                    "after": [],
+                    "subtasks": [],
+                }],
+            }],
+        });
+
+        assert_eq!(task_list_to_json(&task_list), expected_json);
+    }
+
+    #[test]
+    fn task_from_json_gives_back_the_task() {
+        let mut task = Task::new(String::from("Write docs"), TaskState::Started);
+        task.estimate = Some(Duration::minutes(90));
+        task.id = Some(String::from("lxq3v2"));
+        task.after = vec![Dependency::parse("2023-06-06/lxq2a1").unwrap()];
+        task.due = Some(TuduDate::new(9, 6, 2023));
+        task.priority = Some('B');
+        task.at = Some(parse_time("14:30").unwrap());
+        task.subtasks = vec![Task::new(String::from("Intro"), TaskState::Complete)];
+
+        let json = task_to_json(1.into(), &task);
+
+        assert_eq!(task_from_json(&json), Ok(task));
+        assert_eq!(
+            task_from_json(&json!({ "task": "Write docs", "state": "Done" })),
+            Err(TuduError::BadTaskFormat)
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use crate::date::{DateRange, TuduDate};
+use crate::dependency::{flatten_tasks, Prerequisites};
+use crate::error::TuduError;
+use crate::model::{Task, TaskList, TaskState};
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum StandupFormat {
+    Markdown,
+    Slack,
+}
+
+impl StandupFormat {
+    pub fn from_name(name: &str) -> Result<StandupFormat, TuduError> {
+        match name {
+            "md" => Ok(StandupFormat::Markdown),
+            "slack" => Ok(StandupFormat::Slack),
+            _ => Err(TuduError::InvalidArguments(String::from(
+                "`standup` accepts a format of md or slack",
+            ))),
+        }
+    }
+}
+
+/// Finds the dates to report on as done since the last standup. This is the previous working day,
+/// skipping weekends, unless an earlier date is given to report on everything since.
+pub fn previous_dates(
+    today: &TuduDate,
+    since: Option<&TuduDate>,
+) -> Result<Vec<TuduDate>, TuduError> {
+    let yesterday = today.add_days(-1)?;
+
+    let since = match since {
+        Some(since) => since,
+        None => {
+            let mut previous_day = yesterday;
+            while previous_day.is_weekend()? {
+                previous_day = previous_day.add_days(-1)?;
+            }
+            return Ok(vec![previous_day]);
+        }
+    };
+
+    if *since > yesterday {
+        return Ok(Vec::new());
+    }
+
+    DateRange::new(since.clone(), yesterday)?.dates()
+}
+
+/// Renders a standup from the tasks completed or started on the previous days, the tasks still to
+/// do today, and the tasks of today waiting on a prerequisite that is not finished
+pub fn render_standup(
+    previous_task_lists: &[TaskList],
+    today_task_list: &TaskList,
+    prerequisites: &Prerequisites,
+    format: &StandupFormat,
+) -> String {
+    let done: Vec<String> = previous_task_lists
+        .iter()
+        .flat_map(|task_list| task_list.tasks())
+        .filter(|task| matches!(task.state, TaskState::Complete | TaskState::Started))
+        .map(format_task)
+        .collect();
+
+    let planned: Vec<String> = today_task_list
+        .tasks()
+        .iter()
+        .filter(|task| is_unfinished(task))
+        .map(format_task)
+        .collect();
+
+    let blockers: Vec<String> = flatten_tasks(today_task_list.tasks())
+        .into_iter()
+        .filter(|(_, task)| is_unfinished(task))
+        .filter_map(|(_, task)| {
+            let waiting_on: Vec<&str> = prerequisites
+                .unfinished(task)
+                .into_iter()
+                .map(|(_, _, prerequisite)| prerequisite.task.as_str())
+                .collect();
+
+            (!waiting_on.is_empty())
+                .then(|| format!("{} (waiting on {})", task.task, waiting_on.join(", ")))
+        })
+        .collect();
+
+    let sections = [
+        ("Yesterday", done),
+        ("Today", planned),
+        ("Blockers", blockers),
+    ];
+
+    let mut standup = String::new();
+
+    for (heading, items) in sections {
+        match format {
+            StandupFormat::Markdown => standup.push_str(&format!("**{heading}**\n")),
+            StandupFormat::Slack => standup.push_str(&format!("*{heading}*\n")),
+        }
+
+        if items.is_empty() {
+            standup.push_str(&format_item(format, "None"));
+        }
+
+        for item in items {
+            standup.push_str(&format_item(format, &item));
+        }
+    }
+
+    standup
+}
+
+fn is_unfinished(task: &Task) -> bool {
+    matches!(task.state, TaskState::NotStarted | TaskState::Started)
+}
+
+fn format_task(task: &Task) -> String {
+    match task.state {
+        TaskState::Complete => format!("{} ✅", task.task),
+        TaskState::Started => format!("{} (in progress)", task.task),
+        _ => task.task.clone(),
+    }
+}
+
+fn format_item(format: &StandupFormat, item: &str) -> String {
+    match format {
+        StandupFormat::Markdown => format!("- {item}\n"),
+        StandupFormat::Slack => format!("• {item}\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::dependency::Dependency;
+
+    #[test]
+    fn previous_dates_on_monday_skips_the_weekend() {
+        let monday = TuduDate::new(12, 6, 2023);
+
+        let dates = previous_dates(&monday, None).unwrap();
+
+        assert_eq!(dates, vec![TuduDate::new(9, 6, 2023)]);
+    }
+
+    #[test]
+    fn previous_dates_midweek_is_yesterday() {
+        let wednesday = TuduDate::new(14, 6, 2023);
+
+        let dates = previous_dates(&wednesday, None).unwrap();
+
+        assert_eq!(dates, vec![TuduDate::new(13, 6, 2023)]);
+    }
+
+    #[test]
+    fn previous_dates_since_a_date_includes_every_day_up_to_yesterday() {
+        let wednesday = TuduDate::new(14, 6, 2023);
+        let since = TuduDate::new(11, 6, 2023);
+
+        let dates = previous_dates(&wednesday, Some(&since)).unwrap();
+
+        let expected_dates = vec![
+            TuduDate::new(11, 6, 2023),
+            TuduDate::new(12, 6, 2023),
+            TuduDate::new(13, 6, 2023),
+        ];
+
+        assert_eq!(dates, expected_dates);
+    }
+
+    #[test]
+    fn render_standup_gathers_done_and_planned_tasks() {
+        let yesterday = TuduDate::new(9, 6, 2023);
+        let today = TuduDate::new(12, 6, 2023);
+        let previous_task_lists = vec![TaskList::from_tasks(
+            &yesterday,
+            vec![
+                Task::new(String::from("Ship the release"), TaskState::Complete),
+                Task::new(String::from("Write the docs"), TaskState::Started),
+                Task::new(String::from("Dropped idea"), TaskState::Ignored),
+                Task::new(String::from("Moved on"), TaskState::Forwarded),
+            ],
+        )];
+        let today_task_list = TaskList::from_tasks(
+            &today,
+            vec![
+                Task::new(String::from("Write the docs"), TaskState::Started),
+                Task::new(String::from("Plan the sprint"), TaskState::NotStarted),
+                Task::new(String::from("Already done"), TaskState::Complete),
+            ],
+        );
+
+        let expected_standup = "**Yesterday**
+- Ship the release ✅
+- Write the docs (in progress)
+**Today**
+- Write the docs (in progress)
+- Plan the sprint
+**Blockers**
+- None
+";
+
+        let standup = render_standup(
+            &previous_task_lists,
+            &today_task_list,
+            &Prerequisites::from_tasks_by_date(BTreeMap::new()),
+            &StandupFormat::Markdown,
+        );
+
+        assert_eq!(standup, expected_standup);
+    }
+
+    #[test]
+    fn render_standup_for_slack_uses_slack_formatting() {
+        let today = TuduDate::new(12, 6, 2023);
+        let today_task_list = TaskList::from_tasks(
+            &today,
+            vec![Task::new(
+                String::from("Plan the sprint"),
+                TaskState::NotStarted,
+            )],
+        );
+
+        let expected_standup = "*Yesterday*
+• None
+*Today*
+• Plan the sprint
+*Blockers*
+• None
+";
+
+        let standup = render_standup(
+            &[],
+            &today_task_list,
+            &Prerequisites::from_tasks_by_date(BTreeMap::new()),
+            &StandupFormat::Slack,
+        );
+
+        assert_eq!(standup, expected_standup);
+    }
+
+    #[test]
+    fn render_standup_gives_tasks_waiting_on_unfinished_prerequisites_as_blockers() {
+        let yesterday = TuduDate::new(9, 6, 2023);
+        let today = TuduDate::new(12, 6, 2023);
+
+        let mut review = Task::new(String::from("Review the PR"), TaskState::Started);
+        review.id = Some(String::from("r1"));
+        let mut tests = Task::new(String::from("Write the tests"), TaskState::Complete);
+        tests.id = Some(String::from("t1"));
+        let prerequisites = Prerequisites::from_tasks_by_date(BTreeMap::from([(
+            yesterday.clone(),
+            vec![review, tests],
+        )]));
+
+        let mut deploy = Task::new(String::from("Deploy"), TaskState::NotStarted);
+        deploy.after = vec![
+            Dependency::parse("2023-06-09/r1").unwrap(),
+            Dependency::parse("2023-06-09/t1").unwrap(),
+        ];
+        let mut release = Task::new(String::from("Release"), TaskState::Complete);
+        release.after = deploy.after.clone();
+        let today_task_list = TaskList::from_tasks(&today, vec![deploy, release]);
+
+        let standup = render_standup(
+            &[],
+            &today_task_list,
+            &prerequisites,
+            &StandupFormat::Markdown,
+        );
+
+        assert!(standup.ends_with("**Blockers**\n- Deploy (waiting on Review the PR)\n"));
+    }
+}
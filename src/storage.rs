@@ -1,9 +1,22 @@
+use chrono::Duration;
+
+use crate::date::TuduDate;
+use crate::dependency::Dependency;
 use crate::error::TuduError;
+use crate::lists::{current_list, list_directory};
 use crate::model::{Task, TaskState};
+use crate::priority::parse_priority;
+use crate::reminders::{format_time, parse_time};
 use std::fs::{File, OpenOptions};
-use std::io::{Read, Write};
+use std::io::{ErrorKind, Read, Write};
 use std::path::{Path, PathBuf};
-use std::{env, fs};
+use std::{env, fs, process};
+
+const SUBTASK_INDENT: &str = "  ";
+const LOCK_FILENAME: &str = ".lock";
+
+/// Set for tudu run by a pre hook, which runs while the tudu running the hook holds the lock
+pub const LOCK_HELD_VARIABLE: &str = "TUDU_LOCK_HELD";
 
 pub fn parse_task_file(filename: &PathBuf) -> Result<Vec<Task>, TuduError> {
     let mut file = match File::open(filename) {
@@ -13,40 +26,112 @@ pub fn parse_task_file(filename: &PathBuf) -> Result<Vec<Task>, TuduError> {
 
     let mut contents = String::new();
 
-    if let Err(_) = file.read_to_string(&mut contents) {
+    if file.read_to_string(&mut contents).is_err() {
         return Err(TuduError::FailedToReadFile);
     }
 
-    let tasks = contents
-        .lines()
-        .map(|line| parse_task_line(line))
-        .collect::<Result<Vec<Task>, TuduError>>()?;
+    parse_tasks(&contents)
+}
+
+/// Reads the tasks from the contents of a day file
+pub fn parse_tasks(contents: &str) -> Result<Vec<Task>, TuduError> {
+    let mut tasks: Vec<Task> = Vec::new();
+
+    for line in contents.lines() {
+        // Subtasks are indented beneath the task they belong to
+        match line.strip_prefix(SUBTASK_INDENT) {
+            Some(line) => match tasks.last_mut() {
+                Some(parent) => parent.subtasks.push(parse_task_line(line)?),
+                None => return Err(TuduError::BadTaskFormat),
+            },
+            None => tasks.push(parse_task_line(line)?),
+        }
+    }
 
     Ok(tasks)
 }
 
 fn parse_task_line(line: &str) -> Result<Task, TuduError> {
-    let sections: Vec<&str> = line.split(',').collect();
+    // Only the first comma separates the state, so task descriptions can contain commas
+    let (header, task) = match line.split_once(',') {
+        Some(sections) => sections,
+        None => return Err(TuduError::BadTaskFormat),
+    };
 
-    if sections.len() != 2 {
-        return Err(TuduError::BadTaskFormat);
-    }
+    // Any attributes follow the state, e.g. `S;estimate=30,Task`
+    let mut attributes = header.split(';');
 
-    let state = match sections[0] {
-        "S" => TaskState::Started,
-        "C" => TaskState::Complete,
-        "X" => TaskState::Ignored,
-        "F" => TaskState::Forwarded,
-        "N" => TaskState::NotStarted,
-        _ => return Err(TuduError::FailedToReadFile),
+    let state = match attributes.next().and_then(TaskState::from_code) {
+        Some(state) => state,
+        None => return Err(TuduError::FailedToReadFile),
     };
 
-    let task = sections[1].to_owned();
+    let mut task = Task::new(task.to_owned(), state);
+
+    for attribute in attributes {
+        match attribute.split_once('=') {
+            Some(("estimate", minutes)) => {
+                let minutes = minutes.parse().map_err(|_| TuduError::BadTaskFormat)?;
+                task.estimate = Some(Duration::minutes(minutes));
+            }
+            Some(("id", id)) if !id.is_empty() => task.id = Some(String::from(id)),
+            Some(("after", dependencies)) => {
+                task.after = dependencies
+                    .split('+')
+                    .map(Dependency::parse)
+                    .collect::<Result<Vec<Dependency>, TuduError>>()?;
+            }
+            Some(("due", due)) => {
+                task.due = Some(TuduDate::from_iso(due).map_err(|_| TuduError::BadTaskFormat)?);
+            }
+            Some(("priority", priority)) => {
+                task.priority =
+                    Some(parse_priority(priority).map_err(|_| TuduError::BadTaskFormat)?);
+            }
+            Some(("at", at)) => {
+                task.at = Some(parse_time(at).map_err(|_| TuduError::BadTaskFormat)?);
+            }
+            _ => return Err(TuduError::BadTaskFormat),
+        }
+    }
 
-    Ok(Task { task, state })
+    Ok(task)
 }
 
-pub fn create_filepath(filename: &str) -> Result<PathBuf, TuduError> {
+fn format_task_line(task: &Task) -> String {
+    let mut header = String::from(task.state.code());
+
+    if let Some(estimate) = task.estimate {
+        header.push_str(&format!(";estimate={}", estimate.num_minutes()));
+    }
+
+    if let Some(id) = &task.id {
+        header.push_str(&format!(";id={id}"));
+    }
+
+    if !task.after.is_empty() {
+        let dependencies: Vec<String> = task.after.iter().map(Dependency::format).collect();
+        header.push_str(&format!(";after={}", dependencies.join("+")));
+    }
+
+    if let Some(due) = &task.due {
+        header.push_str(&format!(";due={}", due.to_iso()));
+    }
+
+    if let Some(priority) = task.priority {
+        header.push_str(&format!(";priority={priority}"));
+    }
+
+    if let Some(at) = task.at {
+        header.push_str(&format!(";at={}", format_time(at)));
+    }
+
+    format!("{header},{}\n", task.task)
+}
+
+/// The tasks directory set in the TUDU_TASKS env variable, falling back to `.tudu` in the home
+/// directory. It keeps the main list along with everything the lists share.
+pub fn tasks_root() -> Result<PathBuf, TuduError> {
     let tasks_directory = match env::var("TUDU_TASKS") {
         Ok(path) => path,
         Err(env::VarError::NotPresent) => {
@@ -58,43 +143,154 @@ pub fn create_filepath(filename: &str) -> Result<PathBuf, TuduError> {
         Err(_) => return Err(TuduError::InvalidTaskDirectory),
     };
 
-    let filepath = PathBuf::from(format!("{tasks_directory}/{filename}"));
+    Ok(PathBuf::from(tasks_directory))
+}
 
-    Ok(filepath)
+/// The path of a file of the current list, such as a day file
+pub fn create_filepath(filename: &str) -> Result<PathBuf, TuduError> {
+    Ok(list_directory(&current_list()?)?.join(filename))
 }
 
-pub fn write_tasks_to_file(filename: &PathBuf, tasks: &Vec<Task>) -> Result<(), TuduError> {
-    let mut file = match OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open(filename)
-    {
-        Ok(f) => f,
-        Err(_) => return Err(TuduError::FailedToWriteFile),
-    };
+/// The path of a file shared by every list, which is kept in the tasks directory itself
+pub fn create_root_filepath(filename: &str) -> Result<PathBuf, TuduError> {
+    Ok(tasks_root()?.join(filename))
+}
 
-    let lines = tasks.iter().map(|task| {
-        let state = match task.state {
-            TaskState::NotStarted => "N",
-            TaskState::Started => "S",
-            TaskState::Complete => "C",
-            TaskState::Forwarded => "F",
-            TaskState::Ignored => "X",
-        };
+pub fn write_tasks_to_file(filename: &Path, tasks: &[Task]) -> Result<(), TuduError> {
+    let contents: String = tasks
+        .iter()
+        .flat_map(|task| {
+            let subtask_lines = task
+                .subtasks
+                .iter()
+                .map(|subtask| format!("{SUBTASK_INDENT}{}", format_task_line(subtask)));
+
+            std::iter::once(format_task_line(task)).chain(subtask_lines)
+        })
+        .collect();
+
+    // A list's folder is made when its first task is written
+    write_file(filename, &contents)
+}
 
-        return format!("{},{}\n", state, task.task);
-    });
+/// A hash of the contents of a file, giving `None` if it does not exist yet, used to tell whether
+/// the file has been changed since it was read
+pub fn fingerprint_file(filename: &Path) -> Result<Option<u64>, TuduError> {
+    let contents = read_file_if_exists(filename)?;
 
-    for line in lines {
-        if let Err(_) = file.write_all(line.as_bytes()) {
-            return Err(TuduError::FailedToWriteFile);
+    Ok(contents.as_deref().map(fingerprint_contents))
+}
+
+/// The 64 bit FNV-1a hash of the contents. Fingerprints are passed between the daemon and the cli,
+/// so the hash has to be the same in every process and every build of tudu.
+pub fn fingerprint_contents(contents: &str) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    contents.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Reads a whole file, giving `None` if it does not exist yet
+pub fn read_file_if_exists(filename: &Path) -> Result<Option<String>, TuduError> {
+    match fs::read_to_string(filename) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(_) => Err(TuduError::FailedToReadFile),
+    }
+}
+
+/// Writes a whole file, making the folder it is in if needed
+pub fn write_file(filename: &Path, contents: &str) -> Result<(), TuduError> {
+    if let Some(directory) = filename.parent() {
+        if fs::create_dir_all(directory).is_err() {
+            return Err(TuduError::FailedToMakeDirectory);
         }
     }
 
+    write_atomically(filename, contents)
+}
+
+/// Writes the contents to a temporary file beside the file before renaming it into place, so the
+/// file is never left half written if tudu is stopped or the disk fills up part way through
+fn write_atomically(filename: &Path, contents: &str) -> Result<(), TuduError> {
+    let Some(name) = filename.file_name().and_then(|name| name.to_str()) else {
+        return Err(TuduError::FailedToWriteFile);
+    };
+
+    let temp_filename = filename.with_file_name(format!(".{name}.{}.tmp", process::id()));
+
+    let written = File::create(&temp_filename)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_filename, filename));
+
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_filename);
+        return Err(TuduError::FailedToWriteFile);
+    }
+
     Ok(())
 }
 
+/// An advisory lock on the tasks directory, held while reading, changing and writing back files so
+/// that two tudu processes cannot overwrite each other's changes. It covers every list, so a task
+/// can be moved between them. It is released when dropped.
+pub struct TasksLock {
+    _file: Option<File>,
+}
+
+impl TasksLock {
+    /// Waits for any other tudu process to release the lock before taking it. Tudu run by a pre
+    /// hook is given [`LOCK_HELD_VARIABLE`] and goes ahead without it, as the tudu waiting on the
+    /// hook already holds it.
+    pub fn acquire() -> Result<TasksLock, TuduError> {
+        if env::var_os(LOCK_HELD_VARIABLE).is_some() {
+            return Ok(TasksLock { _file: None });
+        }
+
+        let filepath = create_root_filepath(LOCK_FILENAME)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(filepath)
+            .map_err(|_| TuduError::FailedToWriteFile)?;
+
+        file.lock().map_err(|_| TuduError::FailedToWriteFile)?;
+
+        Ok(TasksLock { _file: Some(file) })
+    }
+}
+
+/// Finds the dates of the `.txt` files named after a date in a folder of the tasks directory, which
+/// is the tasks directory itself when the folder is empty
+pub fn list_file_dates(directory: &str) -> Result<Vec<TuduDate>, TuduError> {
+    let directory_path = create_filepath(directory)?;
+
+    let entries = match fs::read_dir(&directory_path) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(_) => return Err(TuduError::FailedToReadFile),
+    };
+
+    let mut dates: Vec<TuduDate> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let filename = entry.file_name().into_string().ok()?;
+            TuduDate::from_iso(filename.strip_suffix(".txt")?).ok()
+        })
+        .collect();
+
+    dates.sort();
+
+    Ok(dates)
+}
+
 fn build_dir_if_needed(dir_path: &String) -> Result<(), TuduError> {
     if !Path::is_dir(&PathBuf::from(&dir_path)) {
         return match fs::create_dir_all(dir_path) {
@@ -106,6 +302,13 @@ fn build_dir_if_needed(dir_path: &String) -> Result<(), TuduError> {
     Ok(())
 }
 
+/// A directory of its own in the system's temporary directory for a test to write to, so tests
+/// running at the same time never share files
+#[cfg(test)]
+pub fn test_directory(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("tudu-{name}-{}", process::id()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -138,6 +341,60 @@ mod tests {
         assert_eq!(task, expected_task);
     }
 
+    #[test]
+    fn parse_task_line_keeps_commas_in_task() {
+        let line = "N,Buy eggs, milk, and bread";
+
+        let expected_task = Task::new(
+            String::from("Buy eggs, milk, and bread"),
+            TaskState::NotStarted,
+        );
+
+        let task = parse_task_line(line).unwrap();
+
+        assert_eq!(task, expected_task);
+    }
+
+    #[test]
+    fn parse_task_line_reads_estimate_attribute() {
+        let line = "S;estimate=90,Write the docs";
+
+        let mut expected_task = Task::new(String::from("Write the docs"), TaskState::Started);
+        expected_task.estimate = Some(Duration::minutes(90));
+
+        let task = parse_task_line(line).unwrap();
+
+        assert_eq!(task, expected_task);
+    }
+
+    #[test]
+    fn parse_task_line_with_unknown_attribute_gives_error() {
+        let result = parse_task_line("S;colour=red,Write the docs");
+
+        assert_eq!(result, Err(TuduError::BadTaskFormat));
+    }
+
+    #[test]
+    fn format_then_parse_task_line_gives_back_the_same_task() {
+        let mut task = Task::new(String::from("Write, then review"), TaskState::NotStarted);
+        task.estimate = Some(Duration::minutes(30));
+        task.id = Some(String::from("lq8x2k"));
+        task.after = vec![
+            Dependency::parse("2023-06-05/abc").unwrap(),
+            Dependency::parse("2023-06-07/def").unwrap(),
+        ];
+        task.due = Some(TuduDate::new(9, 6, 2023));
+        task.priority = Some('A');
+
+        let line = format_task_line(&task);
+
+        assert_eq!(
+            line,
+            "N;estimate=30;id=lq8x2k;after=2023-06-05/abc+2023-06-07/def;due=2023-06-09;priority=A,Write, then review\n"
+        );
+        assert_eq!(parse_task_line(line.trim_end()).unwrap(), task);
+    }
+
     #[test]
     fn write_tasks_to_file_writes_with_correct_format() {
         let filename = "./src/tests/2023-01-01.txt";
@@ -168,4 +425,80 @@ N,Patience is a virtue
         // cleanup
         std::fs::remove_file(filename).unwrap();
     }
+
+    #[test]
+    fn write_then_parse_task_file_keeps_subtasks_under_their_task() {
+        let filename = "./src/tests/2023-01-02.txt";
+
+        let mut release = Task::new(String::from("Release 1.2"), TaskState::Started);
+        release.subtasks = vec![
+            Task::new(String::from("Tag the commit"), TaskState::Complete),
+            Task::new(String::from("Publish"), TaskState::NotStarted),
+        ];
+        let tasks = vec![
+            release,
+            Task::new(String::from("Tidy up"), TaskState::NotStarted),
+        ];
+
+        let expected_contents = "S,Release 1.2
+  C,Tag the commit
+  N,Publish
+N,Tidy up
+";
+
+        write_tasks_to_file(&PathBuf::from(filename), &tasks).unwrap();
+
+        let contents = std::fs::read_to_string(filename).unwrap();
+        let parsed_tasks = parse_task_file(&PathBuf::from(filename)).unwrap();
+
+        // cleanup
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(contents, expected_contents);
+        assert_eq!(parsed_tasks, tasks);
+    }
+
+    #[test]
+    fn write_file_replaces_contents_without_leaving_temporary_files() {
+        let directory = test_directory("atomic");
+        let filename = directory.join("2023-01-03.txt");
+
+        write_file(&filename, "N,First\n").unwrap();
+        write_file(&filename, "S,Second\n").unwrap();
+
+        let contents = fs::read_to_string(&filename).unwrap();
+        let files = fs::read_dir(&directory).unwrap().count();
+
+        // cleanup
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(contents, "S,Second\n");
+        assert_eq!(files, 1);
+    }
+
+    #[test]
+    fn fingerprint_contents_is_fnv_1a() {
+        assert_eq!(fingerprint_contents(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fingerprint_contents("a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fingerprint_contents("foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn fingerprint_file_changes_with_contents() {
+        let directory = test_directory("fingerprint");
+        let filename = directory.join("2023-01-04.txt");
+
+        let missing = fingerprint_file(&filename).unwrap();
+        write_file(&filename, "N,First\n").unwrap();
+        let first = fingerprint_file(&filename).unwrap();
+        write_file(&filename, "N,Second\n").unwrap();
+        let second = fingerprint_file(&filename).unwrap();
+
+        // cleanup
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(missing, None);
+        assert!(first.is_some());
+        assert_ne!(first, second);
+    }
 }
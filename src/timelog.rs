@@ -0,0 +1,489 @@
+use chrono::{DateTime, Duration, FixedOffset, SecondsFormat};
+
+use crate::date::TuduDate;
+use crate::error::TuduError;
+use crate::estimate::format_estimate;
+use crate::model::TaskList;
+use crate::storage::{create_filepath, read_file_if_exists, write_file};
+
+/// A stretch of time spent on the task with an id, which is still running until it has been
+/// stopped
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Interval {
+    pub id: String,
+    pub start: DateTime<FixedOffset>,
+    pub stop: Option<DateTime<FixedOffset>>,
+}
+
+/// The time tracked against the tasks on a date, kept in a sidecar to the day file by task id so
+/// that the time stays with its task when tasks are moved, merged or removed
+#[derive(Debug, PartialEq, Eq)]
+pub struct TimeLog<'a> {
+    intervals: Vec<Interval>,
+    date: &'a TuduDate,
+}
+
+impl TimeLog<'_> {
+    pub fn for_date(date: &TuduDate) -> Result<TimeLog<'_>, TuduError> {
+        let filepath = create_filepath(&time_log_filename(date))?;
+
+        let intervals = match read_file_if_exists(&filepath)? {
+            Some(contents) => parse_time_log(&contents)?,
+            None => Vec::new(),
+        };
+
+        Ok(TimeLog { intervals, date })
+    }
+
+    pub fn start_timer(&mut self, id: &str, now: DateTime<FixedOffset>) -> Result<(), TuduError> {
+        if self.is_running(id) {
+            return Err(TuduError::InvalidArguments(String::from(
+                "the task is already being timed",
+            )));
+        }
+
+        self.intervals.push(Interval {
+            id: String::from(id),
+            start: now,
+            stop: None,
+        });
+
+        Ok(())
+    }
+
+    /// Stops the timer for the task with the id, or every running timer when no id is given,
+    /// giving the ids of the tasks that were stopped
+    pub fn stop_timers(
+        &mut self,
+        id: Option<&str>,
+        now: DateTime<FixedOffset>,
+    ) -> Result<Vec<String>, TuduError> {
+        let mut stopped = Vec::new();
+
+        self.intervals
+            .iter_mut()
+            .filter(|interval| interval.stop.is_none())
+            .filter(|interval| id.is_none() || id == Some(interval.id.as_str()))
+            .for_each(|interval| {
+                interval.stop = Some(now);
+                stopped.push(interval.id.clone());
+            });
+
+        if stopped.is_empty() {
+            return Err(TuduError::InvalidArguments(match id {
+                Some(_) => String::from("the task is not being timed"),
+                None => String::from("no tasks are being timed"),
+            }));
+        }
+
+        Ok(stopped)
+    }
+
+    pub fn is_running(&self, id: &str) -> bool {
+        self.intervals
+            .iter()
+            .any(|interval| interval.id == id && interval.stop.is_none())
+    }
+
+    /// Totals the time spent on the task with the id, counting running timers up to now
+    pub fn time_spent(&self, id: &str, now: DateTime<FixedOffset>) -> Duration {
+        self.intervals
+            .iter()
+            .filter(|interval| interval.id == id)
+            .map(|interval| interval.stop.unwrap_or(now) - interval.start)
+            .fold(Duration::zero(), |total, duration| total + duration)
+    }
+
+    /// The time spent on the task with the id, to be taken along when it is moved to another list
+    pub fn intervals_for(&self, id: &str) -> Vec<Interval> {
+        self.intervals
+            .iter()
+            .filter(|interval| interval.id == id)
+            .cloned()
+            .collect()
+    }
+
+    pub fn add_intervals(&mut self, intervals: Vec<Interval>) {
+        self.intervals.extend(intervals);
+    }
+
+    /// Drops the time for a removed task
+    pub fn remove_task(&mut self, id: &str) {
+        self.intervals.retain(|interval| interval.id != id);
+    }
+
+    pub fn write_to_file(&self) -> Result<(), TuduError> {
+        let filepath = create_filepath(&time_log_filename(self.date))?;
+
+        write_file(&filepath, &format_time_log(&self.intervals))
+    }
+}
+
+fn time_log_filename(date: &TuduDate) -> String {
+    format!("{}.time", date.to_iso())
+}
+
+fn parse_time_log(contents: &str) -> Result<Vec<Interval>, TuduError> {
+    contents.lines().map(parse_interval).collect()
+}
+
+fn parse_interval(line: &str) -> Result<Interval, TuduError> {
+    let sections: Vec<&str> = line.split(',').collect();
+
+    let [id, start, stop] = sections[..] else {
+        return Err(TuduError::BadTaskFormat);
+    };
+
+    if id.is_empty() {
+        return Err(TuduError::BadTaskFormat);
+    }
+
+    let id = String::from(id);
+    let start = DateTime::parse_from_rfc3339(start).map_err(|_| TuduError::BadTaskFormat)?;
+    let stop = match stop {
+        "" => None,
+        stop => Some(DateTime::parse_from_rfc3339(stop).map_err(|_| TuduError::BadTaskFormat)?),
+    };
+
+    Ok(Interval { id, start, stop })
+}
+
+fn format_time_log(intervals: &[Interval]) -> String {
+    intervals
+        .iter()
+        .map(|interval| {
+            let stop = interval
+                .stop
+                .map(|stop| stop.to_rfc3339_opts(SecondsFormat::Secs, false))
+                .unwrap_or_default();
+
+            format!(
+                "{},{},{}\n",
+                interval.id,
+                interval.start.to_rfc3339_opts(SecondsFormat::Secs, false),
+                stop
+            )
+        })
+        .collect()
+}
+
+/// Formats a duration in hours and minutes, e.g. `1h 05m`
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+/// Totals the time spent on each task and each day, comparing it with any estimate for the task.
+/// Tasks with neither time tracked nor an estimate are skipped, and time tracked against tasks that
+/// are no longer in the list is shown as removed.
+pub fn render_timesheet(days: &[(TaskList, TimeLog)], now: DateTime<FixedOffset>) -> String {
+    let mut timesheet = String::new();
+    let mut total = Duration::zero();
+
+    for (task_list, time_log) in days {
+        let mut rows = Vec::new();
+
+        for (position, task) in task_list.tasks().iter().enumerate() {
+            let time_spent = match &task.id {
+                Some(id) => time_log.time_spent(id, now),
+                None => Duration::zero(),
+            };
+            let is_timed = task
+                .id
+                .as_deref()
+                .is_some_and(|id| !time_log.intervals_for(id).is_empty());
+
+            if !is_timed && task.estimate.is_none() {
+                continue;
+            }
+
+            let comparison = match task.estimate {
+                Some(estimate) => format!(
+                    "{} of {}",
+                    format_duration(time_spent),
+                    format_estimate(estimate)
+                ),
+                None => format_duration(time_spent),
+            };
+
+            rows.push((
+                (position + 1).to_string(),
+                task.task.as_str(),
+                time_spent,
+                comparison,
+            ));
+        }
+
+        let mut removed_ids: Vec<&str> = time_log
+            .intervals
+            .iter()
+            .map(|interval| interval.id.as_str())
+            .filter(|id| {
+                !task_list
+                    .tasks()
+                    .iter()
+                    .any(|task| task.id.as_deref() == Some(*id))
+            })
+            .collect();
+        removed_ids.sort();
+        removed_ids.dedup();
+
+        for id in removed_ids {
+            let time_spent = time_log.time_spent(id, now);
+
+            rows.push((
+                String::from("-"),
+                "(removed task)",
+                time_spent,
+                format_duration(time_spent),
+            ));
+        }
+
+        if rows.is_empty() {
+            continue;
+        }
+
+        let day_total = rows
+            .iter()
+            .fold(Duration::zero(), |total, (_, _, time_spent, _)| {
+                total + *time_spent
+            });
+
+        timesheet.push_str(&format!(
+            "{}    {}\n",
+            task_list.date().to_iso(),
+            format_duration(day_total)
+        ));
+
+        for (index, description, _, comparison) in rows {
+            timesheet.push_str(&format!("    {index}    {description}    {comparison}\n"));
+        }
+
+        total = total + day_total;
+    }
+
+    if timesheet.is_empty() {
+        return String::from("No time has been tracked on these dates\n");
+    }
+
+    timesheet.push_str(&format!("Total    {}\n", format_duration(total)));
+
+    timesheet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Task, TaskState};
+
+    fn at(time: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(&format!("2023-06-07T{time}:00+01:00")).unwrap()
+    }
+
+    fn interval(id: &str, start: &str, stop: &str) -> Interval {
+        Interval {
+            id: String::from(id),
+            start: at(start),
+            stop: Some(at(stop)),
+        }
+    }
+
+    fn timed_task(id: &str, task: &str, state: TaskState) -> Task {
+        Task {
+            id: Some(String::from(id)),
+            ..Task::new(String::from(task), state)
+        }
+    }
+
+    #[test]
+    fn start_then_stop_timer_records_interval() {
+        let date = TuduDate::new(7, 6, 2023);
+        let mut time_log = TimeLog {
+            intervals: Vec::new(),
+            date: &date,
+        };
+
+        time_log.start_timer("b", at("09:00")).unwrap();
+        assert!(time_log.is_running("b"));
+
+        let stopped = time_log.stop_timers(Some("b"), at("09:45")).unwrap();
+
+        assert_eq!(stopped, vec![String::from("b")]);
+        assert!(!time_log.is_running("b"));
+        assert_eq!(time_log.time_spent("b", at("12:00")), Duration::minutes(45));
+    }
+
+    #[test]
+    fn start_timer_when_already_running_gives_error() {
+        let date = TuduDate::new(7, 6, 2023);
+        let mut time_log = TimeLog {
+            intervals: Vec::new(),
+            date: &date,
+        };
+
+        time_log.start_timer("a", at("09:00")).unwrap();
+
+        let result = time_log.start_timer("a", at("09:05"));
+
+        assert!(matches!(result, Err(TuduError::InvalidArguments(_))));
+    }
+
+    #[test]
+    fn stop_timers_without_id_stops_every_running_timer() {
+        let date = TuduDate::new(7, 6, 2023);
+        let mut time_log = TimeLog {
+            intervals: Vec::new(),
+            date: &date,
+        };
+
+        time_log.start_timer("a", at("09:00")).unwrap();
+        time_log.start_timer("c", at("09:30")).unwrap();
+
+        let stopped = time_log.stop_timers(None, at("10:00")).unwrap();
+
+        assert_eq!(stopped, vec![String::from("a"), String::from("c")]);
+        assert!(time_log.stop_timers(None, at("10:05")).is_err());
+    }
+
+    #[test]
+    fn time_spent_counts_running_timers_up_to_now() {
+        let date = TuduDate::new(7, 6, 2023);
+        let mut time_log = TimeLog {
+            intervals: Vec::new(),
+            date: &date,
+        };
+
+        time_log.start_timer("a", at("09:00")).unwrap();
+        time_log.stop_timers(Some("a"), at("09:30")).unwrap();
+        time_log.start_timer("a", at("11:00")).unwrap();
+
+        assert_eq!(time_log.time_spent("a", at("11:20")), Duration::minutes(50));
+    }
+
+    #[test]
+    fn remove_task_drops_only_its_time() {
+        let date = TuduDate::new(7, 6, 2023);
+        let mut time_log = TimeLog {
+            intervals: vec![
+                interval("a", "09:00", "09:10"),
+                interval("b", "10:00", "10:20"),
+                interval("c", "11:00", "11:30"),
+            ],
+            date: &date,
+        };
+
+        time_log.remove_task("b");
+
+        assert_eq!(time_log.time_spent("a", at("12:00")), Duration::minutes(10));
+        assert_eq!(time_log.time_spent("b", at("12:00")), Duration::zero());
+        assert_eq!(time_log.time_spent("c", at("12:00")), Duration::minutes(30));
+    }
+
+    #[test]
+    fn intervals_for_task_can_be_added_to_another_time_log() {
+        let date = TuduDate::new(7, 6, 2023);
+        let time_log = TimeLog {
+            intervals: vec![
+                interval("a", "09:00", "09:10"),
+                interval("b", "10:00", "10:20"),
+                interval("a", "11:00", "11:30"),
+            ],
+            date: &date,
+        };
+        let mut other_time_log = TimeLog {
+            intervals: Vec::new(),
+            date: &date,
+        };
+
+        other_time_log.add_intervals(time_log.intervals_for("a"));
+
+        assert_eq!(
+            other_time_log.time_spent("a", at("12:00")),
+            Duration::minutes(40)
+        );
+        assert_eq!(
+            other_time_log.time_spent("b", at("12:00")),
+            Duration::zero()
+        );
+    }
+
+    #[test]
+    fn format_then_parse_time_log_gives_back_the_same_intervals() {
+        let intervals = vec![
+            interval("lxq3v2", "09:00", "09:10"),
+            Interval {
+                id: String::from("lxq3v9"),
+                start: at("10:00"),
+                stop: None,
+            },
+        ];
+
+        let contents = format_time_log(&intervals);
+
+        assert_eq!(
+            contents,
+            "lxq3v2,2023-06-07T09:00:00+01:00,2023-06-07T09:10:00+01:00\nlxq3v9,2023-06-07T10:00:00+01:00,\n"
+        );
+        assert_eq!(parse_time_log(&contents).unwrap(), intervals);
+    }
+
+    #[test]
+    fn format_duration_gives_hours_and_minutes() {
+        assert_eq!(format_duration(Duration::minutes(65)), "1h 05m");
+        assert_eq!(format_duration(Duration::seconds(59)), "0h 00m");
+    }
+
+    #[test]
+    fn render_timesheet_totals_each_task_and_day() {
+        let first_date = TuduDate::new(7, 6, 2023);
+        let second_date = TuduDate::new(8, 6, 2023);
+        let days = vec![
+            (
+                TaskList::from_tasks(
+                    &first_date,
+                    vec![
+                        timed_task("a", "Write report", TaskState::Complete),
+                        Task::new(String::from("Untimed"), TaskState::NotStarted),
+                        Task {
+                            estimate: Some(Duration::minutes(30)),
+                            ..timed_task("c", "Review", TaskState::Started)
+                        },
+                        Task {
+                            estimate: Some(Duration::hours(1)),
+                            ..Task::new(String::from("Planned"), TaskState::NotStarted)
+                        },
+                    ],
+                ),
+                TimeLog {
+                    intervals: vec![
+                        interval("a", "09:00", "10:30"),
+                        interval("c", "11:00", "11:40"),
+                        interval("gone", "12:00", "12:05"),
+                        interval("a", "14:00", "14:15"),
+                    ],
+                    date: &first_date,
+                },
+            ),
+            (
+                TaskList::from_tasks(&second_date, vec![]),
+                TimeLog {
+                    intervals: vec![],
+                    date: &second_date,
+                },
+            ),
+        ];
+
+        let expected_timesheet = "2023-06-07    2h 30m
+    1    Write report    1h 45m
+    3    Review    0h 40m of ~30m
+    4    Planned    0h 00m of ~1h
+    -    (removed task)    0h 05m
+Total    2h 30m
+";
+
+        let timesheet = render_timesheet(&days, at("18:00"));
+
+        assert_eq!(timesheet, expected_timesheet);
+    }
+}
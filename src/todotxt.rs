@@ -0,0 +1,457 @@
+use crate::date::TuduDate;
+use crate::error::TuduError;
+use crate::model::{Task, TaskList, TaskState};
+use crate::priority::format_priority;
+
+const DUE_KEY: &str = "due:";
+const PRIORITY_KEY: &str = "pri:";
+const STATE_KEY: &str = "tudu:";
+const SUBTASK_KEY: &str = "sub:";
+
+/// Formats every task in the task lists as a todo.txt line. The date of the task list is used as
+/// the creation date, and states todo.txt has no notion of are kept in a `tudu:` key so that
+/// importing the output gives back the same tasks. Subtasks follow their task, marked with their
+/// number within it in a `sub:` key.
+pub fn export_tasks(task_lists: &[TaskList]) -> String {
+    let mut lines = String::new();
+
+    for task_list in task_lists {
+        let date = task_list.date();
+
+        for task in task_list.tasks() {
+            lines.push_str(&format!("{}\n", format_task(date, task)));
+
+            for (index, subtask) in task.subtasks.iter().enumerate() {
+                lines.push_str(&format!(
+                    "{} {SUBTASK_KEY}{}\n",
+                    format_task(date, subtask),
+                    index + 1
+                ));
+            }
+        }
+    }
+
+    lines
+}
+
+pub fn format_task(date: &TuduDate, task: &Task) -> String {
+    let iso_date = date.to_iso();
+
+    let mut words = match (&task.state, task.priority) {
+        (TaskState::Complete, _) => vec![String::from("x"), iso_date.clone(), iso_date],
+        (_, Some(priority)) => vec![format_priority(priority), iso_date],
+        (_, None) => vec![iso_date],
+    };
+
+    words.push(escape_description(&task.task));
+
+    if let Some(due) = &task.due {
+        words.push(format!("{DUE_KEY}{}", due.to_iso()));
+    }
+
+    // Completed tasks lose their priority in todo.txt, so it is kept in a key instead
+    match (&task.state, task.priority) {
+        (TaskState::Complete, Some(priority)) => words.push(format!("{PRIORITY_KEY}{priority}")),
+        (TaskState::Complete | TaskState::NotStarted, _) => {}
+        (state, _) => words.push(format!("{STATE_KEY}{}", state.code())),
+    }
+
+    words.join(" ")
+}
+
+/// Reads the tasks from todo.txt contents along with the date of the task list each belongs on,
+/// adding each subtask to the task before it. Every line is checked before anything is returned,
+/// so all problems are reported at once by line number.
+pub fn import_tasks(contents: &str) -> Result<Vec<(TuduDate, Task)>, TuduError> {
+    let mut tasks: Vec<(TuduDate, Task)> = Vec::new();
+    let mut errors = Vec::new();
+
+    for (line_index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (date, task, is_subtask) = match parse_task(line) {
+            Ok(parsed) => parsed,
+            Err(reason) => {
+                errors.push((line_index + 1, reason));
+                continue;
+            }
+        };
+
+        match (is_subtask, tasks.last_mut()) {
+            (false, _) => tasks.push((date, task)),
+            (true, Some((parent_date, parent))) if *parent_date == date => {
+                parent.subtasks.push(task)
+            }
+            (true, _) => errors.push((
+                line_index + 1,
+                String::from("the subtask has no task on the same date before it"),
+            )),
+        }
+    }
+
+    match errors.is_empty() {
+        true => Ok(tasks),
+        false => Err(TuduError::InvalidRows(errors)),
+    }
+}
+
+/// Parses a single todo.txt line, along with whether it is a subtask. The task is placed on its
+/// creation date, falling back to the completion date, then its due date, then today. Only the
+/// keys at the end of the line are read, so the description is kept as it was written.
+pub fn parse_task(line: &str) -> Result<(TuduDate, Task, bool), String> {
+    let mut rest = line.trim_end_matches('\r');
+
+    let is_complete = take_word(&mut rest, |word| word == "x").is_some();
+
+    let mut priority = take_word(&mut rest, is_priority).map(priority_letter);
+
+    // A completed task has its completion date before its creation date, other tasks only have a
+    // creation date, so any dates after those are part of the description
+    let date_count = if is_complete { 2 } else { 1 };
+    let mut dates = Vec::new();
+    while dates.len() < date_count {
+        match take_word(&mut rest, |word| TuduDate::from_iso(word).is_ok()) {
+            Some(word) => dates.extend(TuduDate::from_iso(word).ok()),
+            None => break,
+        }
+    }
+
+    let mut due = None;
+    let mut state = None;
+    let mut is_subtask = false;
+
+    while let Some((description, word)) = rest.rsplit_once(' ') {
+        match parse_key(word) {
+            Some(Key::Due(date)) => {
+                due.get_or_insert(date);
+            }
+            Some(Key::State(key_state)) => {
+                state.get_or_insert(key_state);
+            }
+            Some(Key::Priority(letter)) => {
+                priority.get_or_insert(letter);
+            }
+            Some(Key::Subtask) => is_subtask = true,
+            None => break,
+        }
+
+        rest = description;
+    }
+
+    let description = unescape_description(rest);
+
+    if description.trim().is_empty() {
+        return Err(String::from("the task is empty"));
+    }
+
+    let state = match (is_complete, state) {
+        (true, _) => TaskState::Complete,
+        (false, Some(state)) => state,
+        (false, None) => TaskState::NotStarted,
+    };
+
+    // A completed task's first date is its completion date, and its creation date follows
+    let dated = match (is_complete, dates.len()) {
+        (true, 2) => dates.pop(),
+        _ => dates.into_iter().next(),
+    };
+
+    let date = dated
+        .or_else(|| due.clone())
+        .unwrap_or_else(TuduDate::today);
+
+    let mut task = Task::new(description, state);
+    task.priority = priority;
+    task.due = due;
+
+    Ok((date, task, is_subtask))
+}
+
+/// Takes the first word of the rest of the line if it is wanted, along with the space after it
+fn take_word<'a>(rest: &mut &'a str, is_wanted: impl Fn(&str) -> bool) -> Option<&'a str> {
+    let (word, after) = rest.split_once(' ').unwrap_or((rest, ""));
+
+    if !is_wanted(word) {
+        return None;
+    }
+
+    *rest = after;
+
+    Some(word)
+}
+
+enum Key {
+    Due(TuduDate),
+    State(TaskState),
+    Priority(char),
+    /// The number of the subtask within its task, which follows from the order of the lines
+    Subtask,
+}
+
+/// Reads a word as a key, as long as it is one tudu writes and its value is well formed
+fn parse_key(word: &str) -> Option<Key> {
+    if let Some(date) = word.strip_prefix(DUE_KEY) {
+        return TuduDate::from_iso(date).ok().map(Key::Due);
+    }
+
+    if let Some(code) = word.strip_prefix(STATE_KEY) {
+        return TaskState::from_code(code).map(Key::State);
+    }
+
+    if let Some(number) = word.strip_prefix(SUBTASK_KEY) {
+        return matches!(number.parse::<usize>(), Ok(number) if number > 0).then_some(Key::Subtask);
+    }
+
+    let letter = word.strip_prefix(PRIORITY_KEY)?;
+    let bracketed = format!("({letter})");
+
+    is_priority(&bracketed).then(|| Key::Priority(priority_letter(&bracketed)))
+}
+
+/// Escapes the `:` of any word in the description that starts like a key, along with any `\`, so
+/// that it is not read back as a key
+fn escape_description(description: &str) -> String {
+    description
+        .replace('\\', "\\\\")
+        .split(' ')
+        .map(|word| {
+            match [DUE_KEY, PRIORITY_KEY, STATE_KEY, SUBTASK_KEY]
+                .iter()
+                .find(|key| word.starts_with(*key))
+            {
+                Some(key) => format!("{}\\:{}", key.trim_end_matches(':'), &word[key.len()..]),
+                None => String::from(word),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn unescape_description(description: &str) -> String {
+    let mut unescaped = String::with_capacity(description.len());
+    let mut characters = description.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        match (character, characters.peek()) {
+            ('\\', Some(&escaped)) if escaped == '\\' || escaped == ':' => {
+                unescaped.push(escaped);
+                characters.next();
+            }
+            (character, _) => unescaped.push(character),
+        }
+    }
+
+    unescaped
+}
+
+fn is_priority(word: &str) -> bool {
+    let bytes = word.as_bytes();
+
+    bytes.len() == 3 && bytes[0] == b'(' && bytes[1].is_ascii_uppercase() && bytes[2] == b')'
+}
+
+/// The letter of a priority such as `(A)`
+fn priority_letter(word: &str) -> char {
+    char::from(word.as_bytes()[1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_task_writes_completed_tasks_with_completion_date() {
+        let date = TuduDate::new(7, 6, 2023);
+        let task = Task::new(String::from("Water the plants"), TaskState::Complete);
+
+        let expected_line = "x 2023-06-07 2023-06-07 Water the plants";
+
+        assert_eq!(format_task(&date, &task), expected_line);
+    }
+
+    #[test]
+    fn format_task_keeps_other_states_in_a_tudu_key() {
+        let date = TuduDate::new(7, 6, 2023);
+
+        let not_started = Task::new(String::from("Buy milk"), TaskState::NotStarted);
+        let forwarded = Task::new(String::from("Buy milk"), TaskState::Forwarded);
+
+        assert_eq!(format_task(&date, &not_started), "2023-06-07 Buy milk");
+        assert_eq!(format_task(&date, &forwarded), "2023-06-07 Buy milk tudu:F");
+    }
+
+    #[test]
+    fn export_then_import_gives_back_the_same_tasks() {
+        let date = TuduDate::new(7, 6, 2023);
+        let tasks = vec![
+            Task::new(String::from("This task is started"), TaskState::Started),
+            Task::new(String::from("This one is completed"), TaskState::Complete),
+            Task::new(String::from("Didn't like this one"), TaskState::Ignored),
+            Task::new(String::from("This one's for later"), TaskState::Forwarded),
+            Task::new(String::from("Patience is a virtue"), TaskState::NotStarted),
+            Task {
+                priority: Some('A'),
+                due: Some(TuduDate::new(9, 6, 2023)),
+                ..Task::new(String::from("Urgent and due"), TaskState::Started)
+            },
+            Task {
+                priority: Some('C'),
+                due: Some(TuduDate::new(8, 6, 2023)),
+                ..Task::new(String::from("Done in time"), TaskState::Complete)
+            },
+        ];
+        let task_lists = vec![TaskList::from_tasks(&date, tasks.clone())];
+
+        let exported = export_tasks(&task_lists);
+        let imported = import_tasks(&exported).unwrap();
+
+        let expected_tasks: Vec<(TuduDate, Task)> =
+            tasks.into_iter().map(|task| (date.clone(), task)).collect();
+
+        assert_eq!(imported, expected_tasks);
+    }
+
+    #[test]
+    fn parse_task_reads_lines_from_other_tools() {
+        let (date, task, _) =
+            parse_task("(A) 2023-06-01 Call mom +family @phone due:2023-06-03").unwrap();
+
+        assert_eq!(date, TuduDate::new(1, 6, 2023));
+        assert_eq!(
+            task,
+            Task {
+                priority: Some('A'),
+                due: Some(TuduDate::new(3, 6, 2023)),
+                ..Task::new(
+                    String::from("Call mom +family @phone"),
+                    TaskState::NotStarted
+                )
+            }
+        );
+    }
+
+    #[test]
+    fn format_task_writes_priority_and_due_date() {
+        let date = TuduDate::new(7, 6, 2023);
+        let task = Task {
+            priority: Some('B'),
+            due: Some(TuduDate::new(9, 6, 2023)),
+            ..Task::new(String::from("Send the report"), TaskState::Started)
+        };
+        let completed = Task {
+            state: TaskState::Complete,
+            ..task.clone()
+        };
+
+        assert_eq!(
+            format_task(&date, &task),
+            "(B) 2023-06-07 Send the report due:2023-06-09 tudu:S"
+        );
+        assert_eq!(
+            format_task(&date, &completed),
+            "x 2023-06-07 2023-06-07 Send the report due:2023-06-09 pri:B"
+        );
+    }
+
+    #[test]
+    fn parse_task_without_creation_date_is_placed_on_due_date() {
+        let (date, task, _) = parse_task("Send the report due:2023-06-09").unwrap();
+
+        assert_eq!(date, TuduDate::new(9, 6, 2023));
+        assert_eq!(task.due, Some(TuduDate::new(9, 6, 2023)));
+    }
+
+    #[test]
+    fn parse_task_places_completed_task_on_creation_date() {
+        let (date, task, _) = parse_task("x 2023-06-09 2023-06-02 Send the report").unwrap();
+
+        assert_eq!(date, TuduDate::new(2, 6, 2023));
+        assert_eq!(task.state, TaskState::Complete);
+    }
+
+    #[test]
+    fn parse_task_keeps_keys_that_are_not_well_formed_or_not_trailing() {
+        let (_, due_soon, _) = parse_task("2023-06-07 check due:soon thing").unwrap();
+        let (_, state_in_text, _) = parse_task("2023-06-07 Fix spacing tudu:S in text").unwrap();
+        let (_, due_tomorrow, _) = parse_task("Send the report due:tomorrow").unwrap();
+
+        assert_eq!(due_soon.task, "check due:soon thing");
+        assert_eq!(due_soon.due, None);
+        assert_eq!(state_in_text.task, "Fix spacing tudu:S in text");
+        assert_eq!(state_in_text.state, TaskState::NotStarted);
+        assert_eq!(due_tomorrow.task, "Send the report due:tomorrow");
+    }
+
+    #[test]
+    fn export_then_import_keeps_descriptions_as_written() {
+        let date = TuduDate::new(7, 6, 2023);
+        let tasks = vec![
+            Task::new(String::from("Fix  spacing"), TaskState::NotStarted),
+            Task::new(String::from("2023-01-01 retro"), TaskState::Started),
+            Task::new(String::from("2023-01-01 retro"), TaskState::Complete),
+            Task::new(String::from("Ends with tudu:S"), TaskState::NotStarted),
+            Task::new(
+                String::from("Path C:\\tmp\\ and pri:A"),
+                TaskState::Forwarded,
+            ),
+            Task::new(String::from("x marks the spot"), TaskState::NotStarted),
+        ];
+        let task_lists = vec![TaskList::from_tasks(&date, tasks.clone())];
+
+        let exported = export_tasks(&task_lists);
+        let imported = import_tasks(&exported).unwrap();
+
+        let expected_tasks: Vec<(TuduDate, Task)> =
+            tasks.into_iter().map(|task| (date.clone(), task)).collect();
+
+        assert_eq!(imported, expected_tasks);
+    }
+
+    #[test]
+    fn export_then_import_keeps_subtasks() {
+        let date = TuduDate::new(7, 6, 2023);
+        let mut parent = Task::new(String::from("Ship it"), TaskState::Started);
+        parent.subtasks = vec![
+            Task::new(String::from("Write docs"), TaskState::Complete),
+            Task::new(String::from("Tag sub:1 release"), TaskState::NotStarted),
+        ];
+        let tasks = vec![
+            parent,
+            Task::new(String::from("Rest"), TaskState::NotStarted),
+        ];
+        let task_lists = vec![TaskList::from_tasks(&date, tasks.clone())];
+
+        let exported = export_tasks(&task_lists);
+        let imported = import_tasks(&exported).unwrap();
+
+        assert!(exported.contains("\nx 2023-06-07 2023-06-07 Write docs sub:1\n"));
+        assert_eq!(
+            imported,
+            tasks
+                .into_iter()
+                .map(|task| (date.clone(), task))
+                .collect::<Vec<(TuduDate, Task)>>()
+        );
+    }
+
+    #[test]
+    fn import_tasks_reports_invalid_lines_by_number() {
+        let contents = "2023-06-07 Fine\n\n(A) 2023-06-07\nx 2023-06-07\n2023-06-08 Orphan sub:1\n";
+
+        let result = import_tasks(contents);
+
+        assert_eq!(
+            result,
+            Err(TuduError::InvalidRows(vec![
+                (3, String::from("the task is empty")),
+                (4, String::from("the task is empty")),
+                (
+                    5,
+                    String::from("the subtask has no task on the same date before it")
+                ),
+            ]))
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+use std::process::Command;
+use std::{env, fs};
+
+/// A tasks directory of its own with a config, which is cleaned up when dropped
+struct TestTasks {
+    directory: PathBuf,
+}
+
+impl TestTasks {
+    fn new(name: &str, config: &str) -> TestTasks {
+        let directory = env::temp_dir().join(format!("tudu-aliases-{name}-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("config"), config).unwrap();
+
+        TestTasks { directory }
+    }
+
+    fn run(&self, args: &[&str]) -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_tudu"))
+            .args(args)
+            .env("TUDU_TASKS", &self.directory)
+            .env_remove("TUDU_GIT")
+            .env_remove("TUDU_LIST")
+            .env_remove("TUDU_CONFIG")
+            .env_remove("TUDU_DERIVE_STATE")
+            .output()
+            .unwrap();
+
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn day_file(&self) -> String {
+        fs::read_to_string(self.directory.join("2023-06-07.txt")).unwrap()
+    }
+}
+
+impl Drop for TestTasks {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+const CONFIG: &str = "# shortcuts
+alias d = complete
+alias s = set $1 S
+alias plan = add 'Write docs' $@; add Review $@; s 2 $@
+";
+
+#[test]
+fn aliases_run_the_commands_they_stand_for() {
+    let tasks = TestTasks::new("run", CONFIG);
+
+    tasks.run(&["plan", "7-6-2023"]);
+    let after_plan = tasks.day_file();
+    tasks.run(&["d", "1", "7-6-2023"]);
+
+    assert_eq!(after_plan, "N,Write docs\nS,Review\n");
+    assert_eq!(tasks.day_file(), "C,Write docs\nS,Review\n");
+}
+
+#[test]
+fn alias_lists_the_aliases() {
+    let tasks = TestTasks::new("list", CONFIG);
+    let empty = TestTasks::new("empty", "");
+
+    assert_eq!(
+        tasks.run(&["alias"]),
+        "d = complete\nplan = add 'Write docs' $@; add Review $@; s 2 $@\ns = set $1 S\n"
+    );
+    assert!(empty.run(&["alias"]).starts_with("There are no aliases"));
+}
+
+#[test]
+fn alias_cycles_are_reported() {
+    let tasks = TestTasks::new("cycle", "alias a = b\nalias b = a\n");
+
+    assert_eq!(
+        tasks.run(&["a"]),
+        "The config is not valid: the alias `a` runs itself, a → b → a\n"
+    );
+}
+
+#[test]
+fn help_is_given_when_config_is_not_valid() {
+    let tasks = TestTasks::new("invalid", "alias d\n");
+
+    assert!(tasks.run(&["help"]).contains("Usage: tudu"));
+    assert!(tasks.run(&["--help"]).contains("Usage: tudu"));
+    assert_eq!(
+        tasks.run(&["d", "1"]),
+        "The config is not valid: line 1, settings are written as `setting = value`\n"
+    );
+}
@@ -0,0 +1,177 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{env, fs};
+
+use serde_json::{json, Value};
+
+/// A `tudu daemon` running against its own tasks directory, which is stopped and cleaned up when
+/// dropped
+struct TestDaemon {
+    process: Child,
+    directory: PathBuf,
+}
+
+impl TestDaemon {
+    fn start(name: &str) -> TestDaemon {
+        let directory = env::temp_dir().join(format!("tudu-daemon-{name}-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        let daemon = TestDaemon {
+            process: tudu(&directory).arg("daemon").spawn().unwrap(),
+            directory,
+        };
+
+        let started = Instant::now();
+        while UnixStream::connect(daemon.socket()).is_err() {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "the daemon did not start"
+            );
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        daemon
+    }
+
+    fn socket(&self) -> PathBuf {
+        self.directory.join(".daemon.sock")
+    }
+
+    /// Sends each request on one connection, giving back the answers
+    fn ask(&self, requests: &[Value]) -> Vec<Value> {
+        let mut stream = UnixStream::connect(self.socket()).unwrap();
+        let mut answers = BufReader::new(stream.try_clone().unwrap()).lines();
+
+        requests
+            .iter()
+            .map(|request| {
+                writeln!(stream, "{request}").unwrap();
+                serde_json::from_str(&answers.next().unwrap().unwrap()).unwrap()
+            })
+            .collect()
+    }
+
+    fn run(&self, args: &[&str]) -> String {
+        let output = tudu(&self.directory).args(args).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+}
+
+impl Drop for TestDaemon {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+fn tudu(directory: &PathBuf) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_tudu"));
+    command
+        .env("TUDU_TASKS", directory)
+        .env_remove("TUDU_GIT")
+        .env_remove("TUDU_LIST")
+        .env_remove("TUDU_DERIVE_STATE");
+    command
+}
+
+fn task_names(answer: &Value) -> Vec<&str> {
+    answer["body"]["tasks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|task| task["task"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn daemon_answers_queries_and_mutations_on_one_connection() {
+    let daemon = TestDaemon::start("answers");
+
+    let answers = daemon.ask(&[
+        json!({ "method": "POST", "path": "/days/2023-06-07/tasks", "body": { "task": "Write docs" } }),
+        json!({ "method": "PATCH", "path": "/days/2023-06-07/tasks/1", "body": { "state": "S" } }),
+        json!({ "method": "GET", "path": "/days/2023-06-07" }),
+        json!({ "method": "DELETE", "path": "/days/2023-06-07/tasks/3" }),
+    ]);
+
+    assert_eq!(answers[0]["status"], 201);
+    assert_eq!(answers[2]["status"], 200);
+    assert_eq!(task_names(&answers[2]), vec!["Write docs"]);
+    assert_eq!(answers[2]["body"]["tasks"][0]["state"], "S");
+    assert_eq!(answers[3]["status"], 404);
+}
+
+#[test]
+fn daemon_sees_changes_made_by_the_cli_and_elsewhere() {
+    let daemon = TestDaemon::start("changes");
+    let get = || json!({ "method": "GET", "path": "/days/2023-06-07" });
+
+    daemon.run(&["add", "Write docs", "7-6-2023"]);
+    let after_add = daemon.ask(&[get()]).remove(0);
+
+    fs::write(
+        daemon.directory.join("2023-06-07.txt"),
+        "C,Write docs\nN,Edited elsewhere\n",
+    )
+    .unwrap();
+    let after_edit = daemon.ask(&[get()]).remove(0);
+    let loaded = daemon
+        .ask(&[json!({ "method": "LOAD", "path": "2023-06-07.txt" })])
+        .remove(0);
+
+    // The cli reads the day through the daemon while it is running
+    let view = daemon.run(&["view", "7-6-2023"]);
+    daemon.run(&["set", "2", "C", "7-6-2023"]);
+
+    assert_eq!(task_names(&after_add), vec!["Write docs"]);
+    assert_eq!(
+        task_names(&after_edit),
+        vec!["Write docs", "Edited elsewhere"]
+    );
+    assert_eq!(task_names(&loaded), vec!["Write docs", "Edited elsewhere"]);
+    assert_eq!(loaded["body"]["tasks"][0]["state"], "C");
+    assert!(loaded["body"]["fingerprint"].is_u64());
+    assert!(view.contains("Edited elsewhere"));
+    assert_eq!(
+        fs::read_to_string(daemon.directory.join("2023-06-07.txt")).unwrap(),
+        "C,Write docs\nC,Edited elsewhere\n"
+    );
+}
+
+#[test]
+fn daemon_will_not_start_twice() {
+    let daemon = TestDaemon::start("twice");
+
+    let output = daemon.run(&["daemon"]);
+
+    assert!(output.contains("already running"));
+}
+
+#[test]
+fn cli_reads_files_itself_when_daemon_has_stopped() {
+    let directory = env::temp_dir().join(format!("tudu-daemon-stopped-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+
+    // A socket left behind by a daemon that was stopped has nothing listening on it
+    drop(std::os::unix::net::UnixListener::bind(directory.join(".daemon.sock")).unwrap());
+    fs::write(directory.join("2023-06-07.txt"), "S,Write docs\n").unwrap();
+
+    let started = Instant::now();
+    let output = tudu(&directory)
+        .args(["view", "7-6-2023"])
+        .output()
+        .unwrap();
+    let elapsed = started.elapsed();
+
+    let _ = fs::remove_dir_all(&directory);
+
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("Write docs"));
+    assert!(elapsed < Duration::from_secs(5));
+}
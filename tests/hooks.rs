@@ -0,0 +1,206 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use std::{env, fs, thread};
+
+use serde_json::Value;
+
+/// A tasks directory of its own with a hooks folder, which is cleaned up when dropped
+struct TestTasks {
+    directory: PathBuf,
+}
+
+impl TestTasks {
+    fn new(name: &str) -> TestTasks {
+        let directory = env::temp_dir().join(format!("tudu-hooks-{name}-{}", std::process::id()));
+        fs::create_dir_all(directory.join("hooks")).unwrap();
+
+        TestTasks { directory }
+    }
+
+    fn run(&self, args: &[&str]) -> String {
+        self.run_with_stderr(args).0
+    }
+
+    fn run_with_stderr(&self, args: &[&str]) -> (String, String) {
+        let output = Command::new(env!("CARGO_BIN_EXE_tudu"))
+            .args(args)
+            .env("TUDU_TASKS", &self.directory)
+            .env_remove("TUDU_GIT")
+            .env_remove("TUDU_LIST")
+            .env_remove("TUDU_HOOKS")
+            .env_remove("TUDU_DERIVE_STATE")
+            .output()
+            .unwrap();
+
+        (
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    }
+
+    /// Installs a hook that appends what it is given to a file named after it, then exits with the
+    /// code
+    fn install_hook(&self, name: &str, exit_code: i32) {
+        let hook = self.directory.join("hooks").join(name);
+        let received = self.received_path(name);
+
+        fs::write(
+            &hook,
+            format!(
+                "#!/bin/sh\ncat >> {}\necho >> {}\nexit {exit_code}\n",
+                received.display(),
+                received.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    /// Runs tudu, killing it and failing if it has not finished within a few seconds
+    fn run_with_timeout(&self, args: &[&str]) -> String {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_tudu"))
+            .args(args)
+            .env("TUDU_TASKS", &self.directory)
+            .env_remove("TUDU_GIT")
+            .env_remove("TUDU_LIST")
+            .env_remove("TUDU_HOOKS")
+            .env_remove("TUDU_DERIVE_STATE")
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let started = Instant::now();
+
+        while child.try_wait().unwrap().is_none() {
+            if started.elapsed() > Duration::from_secs(10) {
+                child.kill().unwrap();
+                panic!("tudu {args:?} did not finish");
+            }
+
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap()
+    }
+
+    fn received_path(&self, name: &str) -> PathBuf {
+        self.directory.join(format!("{name}.received"))
+    }
+
+    /// Each payload the hook has been given
+    fn received(&self, name: &str) -> Vec<Value> {
+        read_if_exists(&self.received_path(name))
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+}
+
+impl Drop for TestTasks {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+fn read_if_exists(path: &Path) -> String {
+    fs::read_to_string(path).unwrap_or_default()
+}
+
+#[test]
+fn hooks_are_given_task_and_date_for_each_event() {
+    let tasks = TestTasks::new("events");
+    for hook in ["post-add", "on-state-change", "on-complete", "on-remove"] {
+        tasks.install_hook(hook, 0);
+    }
+
+    tasks.run(&["add", "Write docs", "7-6-2023"]);
+    tasks.run(&["set", "1", "S", "7-6-2023"]);
+    tasks.run(&["complete", "1", "7-6-2023"]);
+    tasks.run(&["remove", "1", "7-6-2023"]);
+
+    let added = tasks.received("post-add");
+    let changed = tasks.received("on-state-change");
+    let completed = tasks.received("on-complete");
+    let removed = tasks.received("on-remove");
+
+    assert_eq!(added.len(), 1);
+    assert_eq!(added[0]["date"], "2023-06-07");
+    assert_eq!(added[0]["index"], "1");
+    assert_eq!(added[0]["task"]["task"], "Write docs");
+    assert_eq!(changed.len(), 2);
+    assert_eq!(changed[0]["old_state"], "N");
+    assert_eq!(changed[0]["new_state"], "S");
+    assert_eq!(changed[1]["new_state"], "C");
+    assert_eq!(completed.len(), 1);
+    assert_eq!(completed[0]["old_state"], "S");
+    assert_eq!(completed[0]["task"]["state"], "C");
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0]["event"], "on-remove");
+}
+
+#[test]
+fn failing_pre_hook_stops_the_command() {
+    let tasks = TestTasks::new("pre");
+    tasks.install_hook("pre-add", 1);
+    tasks.install_hook("post-add", 0);
+
+    let output = tasks.run(&["add", "Write docs", "7-6-2023"]);
+
+    assert!(output.contains("A hook failed: `pre-add` exited"));
+    assert_eq!(tasks.received("pre-add").len(), 1);
+    assert!(tasks.received("post-add").is_empty());
+    assert!(!tasks.directory.join("2023-06-07.txt").exists());
+}
+
+#[test]
+fn pre_hook_can_change_tasks_with_tudu() {
+    let tasks = TestTasks::new("nested");
+    let hook = tasks.directory.join("hooks").join("pre-add");
+    fs::write(
+        &hook,
+        format!(
+            "#!/bin/sh\ncase \"$(cat)\" in *Review*) exit 0;; esac\n{} add 'Review docs' 7-6-2023 > /dev/null\n",
+            env!("CARGO_BIN_EXE_tudu")
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+
+    tasks.run_with_timeout(&["add", "Write docs", "7-6-2023"]);
+
+    let contents = read_if_exists(&tasks.directory.join("2023-06-07.txt"));
+
+    assert_eq!(contents, "N,Review docs\nN,Write docs\n");
+}
+
+#[test]
+fn failing_post_hook_is_reported_on_stderr() {
+    let tasks = TestTasks::new("post");
+    tasks.install_hook("post-add", 1);
+
+    let (stdout, stderr) = tasks.run_with_stderr(&["add", "Write docs", "7-6-2023"]);
+
+    assert!(stderr.contains("A hook failed: `post-add` exited"));
+    assert!(!stdout.contains("A hook failed"));
+    assert!(stdout.contains("Write docs"));
+}
+
+#[test]
+fn day_rollover_hook_is_given_the_last_day_once() {
+    let tasks = TestTasks::new("rollover");
+    tasks.install_hook("on-day-rollover", 0);
+
+    tasks.run(&["add", "Write docs", "7-6-2023"]);
+    fs::write(tasks.directory.join(".last-day"), "2023-06-07\n").unwrap();
+
+    tasks.run(&["view"]);
+    tasks.run(&["view"]);
+
+    let rolled_over = tasks.received("on-day-rollover");
+
+    assert_eq!(rolled_over.len(), 1);
+    assert_eq!(rolled_over[0]["previous_date"], "2023-06-07");
+    assert_eq!(rolled_over[0]["tasks"][0]["task"], "Write docs");
+}
@@ -0,0 +1,122 @@
+use std::path::PathBuf;
+use std::process::Command;
+use std::{env, fs};
+
+/// A tasks directory of its own, which is cleaned up when dropped
+struct TestTasks {
+    directory: PathBuf,
+}
+
+impl TestTasks {
+    fn new(name: &str) -> TestTasks {
+        let directory = env::temp_dir().join(format!("tudu-lists-{name}-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        TestTasks { directory }
+    }
+
+    fn run(&self, args: &[&str]) -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_tudu"))
+            .args(args)
+            .env("TUDU_TASKS", &self.directory)
+            .env_remove("TUDU_GIT")
+            .env_remove("TUDU_LIST")
+            .env_remove("TUDU_CONFIG")
+            .env_remove("TUDU_DERIVE_STATE")
+            .output()
+            .unwrap();
+
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn read(&self, path: &str) -> String {
+        fs::read_to_string(self.directory.join(path)).unwrap_or_default()
+    }
+}
+
+impl Drop for TestTasks {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+#[test]
+fn lists_keep_their_tasks_in_folders() {
+    let tasks = TestTasks::new("folders");
+
+    tasks.run(&["add", "Buy milk", "7-6-2023"]);
+    tasks.run(&["-l", "work", "add", "Write docs", "7-6-2023"]);
+    tasks.run(&["add", "Review PR", "7-6-2023", "--list", "work"]);
+
+    assert_eq!(tasks.read("2023-06-07.txt"), "N,Buy milk\n");
+    assert_eq!(
+        tasks.read("lists/work/2023-06-07.txt"),
+        "N,Write docs\nN,Review PR\n"
+    );
+    assert_eq!(tasks.run(&["lists"]), "* main\n  work\n");
+    assert_eq!(tasks.run(&["-l", "work", "lists"]), "  main\n* work\n");
+}
+
+#[test]
+fn view_all_lists_labels_each_task_with_its_list() {
+    let tasks = TestTasks::new("all");
+
+    tasks.run(&["add", "Buy milk", "7-6-2023"]);
+    tasks.run(&["-l", "work", "add", "Write docs", "7-6-2023"]);
+    tasks.run(&["-l", "empty", "add", "Nothing", "8-6-2023"]);
+
+    let output = tasks.run(&["view", "7-6-2023", "--all-lists"]);
+
+    assert_eq!(
+        output,
+        "1    ◯ - Buy milk    [main]\n\n1    ◯ - Write docs    [work]\n\n"
+    );
+}
+
+#[test]
+fn move_keeps_state_and_time_of_task() {
+    let tasks = TestTasks::new("move");
+
+    tasks.run(&["-l", "work", "add", "Anything", "7-6-2023"]);
+    tasks.run(&["add", "Buy milk", "7-6-2023"]);
+    tasks.run(&["add", "Write docs", "7-6-2023"]);
+    tasks.run(&["start", "2", "7-6-2023"]);
+
+    let output = tasks.run(&["move", "2", "--to", "work", "7-6-2023"]);
+    let missing = tasks.run(&["move", "1", "--to", "wrok", "7-6-2023"]);
+
+    assert!(output.starts_with("Moved 2 - Write docs to work"));
+    let moved = tasks.read("lists/work/2023-06-07.txt");
+    let id = moved
+        .lines()
+        .nth(1)
+        .and_then(|line| line.split_once(";id="))
+        .unwrap()
+        .1;
+    let id = &id[..id.find([',', ';']).unwrap()];
+
+    assert_eq!(tasks.read("2023-06-07.txt"), "N,Buy milk\n");
+    assert!(moved.starts_with("N,Anything\nS;id="));
+    assert!(moved.ends_with(",Write docs\n"));
+    assert_eq!(tasks.read("2023-06-07.time"), "");
+    assert!(tasks
+        .read("lists/work/2023-06-07.time")
+        .starts_with(&format!("{id},")));
+    assert!(missing.contains("there is no `wrok` list"));
+}
+
+#[test]
+fn default_list_is_set_in_config() {
+    let tasks = TestTasks::new("default");
+    fs::write(
+        tasks.directory.join("config"),
+        "list = work\nalias a = add\n",
+    )
+    .unwrap();
+
+    tasks.run(&["a", "Write docs", "7-6-2023"]);
+    tasks.run(&["-l", "main", "a", "Buy milk", "7-6-2023"]);
+
+    assert_eq!(tasks.read("lists/work/2023-06-07.txt"), "N,Write docs\n");
+    assert_eq!(tasks.read("2023-06-07.txt"), "N,Buy milk\n");
+}
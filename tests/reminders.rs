@@ -0,0 +1,141 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{env, fs};
+
+/// A tasks directory of its own, which is cleaned up when dropped
+struct TestTasks {
+    directory: PathBuf,
+}
+
+impl TestTasks {
+    fn new(name: &str) -> TestTasks {
+        let directory = env::temp_dir().join(format!("tudu-remind-{name}-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        TestTasks { directory }
+    }
+
+    /// Runs tudu as if it were the given time, such as `09:30` on 2023-06-07 or `2023-06-08 00:10`
+    fn run(&self, time: &str, args: &[&str]) -> String {
+        let now = match time.split_once(' ') {
+            Some((date, time)) => format!("{date}T{time}:00+00:00"),
+            None => format!("2023-06-07T{time}:00+00:00"),
+        };
+
+        let output = Command::new(env!("CARGO_BIN_EXE_tudu"))
+            .args(args)
+            .env("TUDU_TASKS", &self.directory)
+            .env("TUDU_NOW", now)
+            .env_remove("TUDU_GIT")
+            .env_remove("TUDU_LIST")
+            .output()
+            .unwrap();
+
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    /// Sends the reminders due at the time to a command that writes them to a file, giving back
+    /// every reminder written so far
+    fn remind(&self, time: &str) -> String {
+        let reminded = self.directory.join("reminded");
+        let command = format!(
+            "echo \"$TUDU_REMINDER_TIME $TUDU_REMINDER_INDEX $TUDU_REMINDER_TASK\" >> {}",
+            reminded.display()
+        );
+
+        self.run(
+            time,
+            &[
+                "remind",
+                "--once",
+                "--notify",
+                "command",
+                "--command",
+                &command,
+            ],
+        );
+
+        read_if_exists(&reminded)
+    }
+}
+
+impl Drop for TestTasks {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+fn read_if_exists(path: &Path) -> String {
+    fs::read_to_string(path).unwrap_or_default()
+}
+
+#[test]
+fn remind_sends_each_reminder_once_until_its_time_changes() {
+    let tasks = TestTasks::new("once");
+
+    tasks.run("08:00", &["add", "Call dentist", "--at", "09:00"]);
+    tasks.run("08:00", &["add", "No reminder"]);
+
+    assert_eq!(tasks.remind("08:59"), "");
+    assert_eq!(tasks.remind("09:00"), "09:00 1 Call dentist\n");
+    assert_eq!(tasks.remind("09:05"), "09:00 1 Call dentist\n");
+
+    tasks.run("09:05", &["at", "1", "09:10"]);
+
+    assert_eq!(tasks.remind("09:09"), "09:00 1 Call dentist\n");
+    assert_eq!(
+        tasks.remind("09:10"),
+        "09:00 1 Call dentist\n09:10 1 Call dentist\n"
+    );
+}
+
+#[test]
+fn remind_skips_snoozed_and_finished_tasks() {
+    let tasks = TestTasks::new("snooze");
+
+    tasks.run("08:00", &["add", "Call dentist", "--at", "09:00"]);
+    tasks.run("08:00", &["add", "Stretch", "--at", "09:00"]);
+
+    let snoozed = tasks.run("09:00", &["snooze", "1", "--for", "1h"]);
+    tasks.run("09:00", &["set", "2", "C"]);
+
+    assert_eq!(snoozed, "Snoozed 1 - Call dentist until 10:00\n");
+    assert_eq!(tasks.remind("09:59"), "");
+    assert_eq!(tasks.remind("10:00"), "09:00 1 Call dentist\n");
+}
+
+#[test]
+fn remind_sends_reminders_snoozed_past_midnight_the_next_day() {
+    let tasks = TestTasks::new("midnight");
+
+    tasks.run("08:00", &["add", "Call dentist", "--at", "23:30"]);
+    tasks.run("08:00", &["add", "Stretch", "--at", "23:30"]);
+
+    tasks.run("23:45", &["snooze", "1", "--for", "30m"]);
+    tasks.run("23:45", &["snooze", "2", "--for", "30m"]);
+    tasks.run("23:50", &["set", "2", "C"]);
+
+    assert_eq!(tasks.remind("2023-06-08 00:10"), "");
+    assert_eq!(tasks.remind("2023-06-08 00:15"), "23:30 1 Call dentist\n");
+    assert_eq!(tasks.remind("2023-06-08 00:20"), "23:30 1 Call dentist\n");
+
+    // The snooze is done with once the reminder is sent, which is recorded on the task's day
+    let statuses = fs::read_to_string(tasks.directory.join("2023-06-07.reminders")).unwrap();
+    assert!(statuses.contains("fired=23:30,"));
+    assert!(statuses.contains("snoozed=2023-06-08T00:15:00+00:00,"));
+    assert_eq!(
+        read_if_exists(&tasks.directory.join("2023-06-08.reminders")),
+        ""
+    );
+}
+
+#[test]
+fn snooze_without_reminder_gives_error() {
+    let tasks = TestTasks::new("no-reminder");
+
+    tasks.run("08:00", &["add", "No reminder"]);
+
+    assert!(tasks
+        .run("08:00", &["snooze", "1"])
+        .contains("has no reminder to snooze"));
+}
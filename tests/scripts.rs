@@ -0,0 +1,90 @@
+use std::path::PathBuf;
+use std::process::Command;
+use std::{env, fs};
+
+/// A tasks directory of its own with a scripts folder, which is cleaned up when dropped
+struct TestTasks {
+    directory: PathBuf,
+}
+
+impl TestTasks {
+    fn new(name: &str) -> TestTasks {
+        let directory = env::temp_dir().join(format!("tudu-scripts-{name}-{}", std::process::id()));
+        fs::create_dir_all(directory.join("scripts")).unwrap();
+
+        TestTasks { directory }
+    }
+
+    fn write_script(&self, name: &str, script: &str) {
+        fs::write(
+            self.directory.join("scripts").join(format!("{name}.rhai")),
+            script,
+        )
+        .unwrap();
+    }
+
+    fn run(&self, args: &[&str]) -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_tudu"))
+            .args(args)
+            .env("TUDU_TASKS", &self.directory)
+            .env_remove("TUDU_GIT")
+            .env_remove("TUDU_LIST")
+            .env_remove("TUDU_HOOKS")
+            .env_remove("TUDU_SCRIPTS")
+            .env_remove("TUDU_DERIVE_STATE")
+            .output()
+            .unwrap();
+
+        String::from_utf8(output.stdout).unwrap()
+    }
+}
+
+impl Drop for TestTasks {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+#[test]
+fn unknown_command_runs_script_with_api_over_tasks() {
+    let tasks = TestTasks::new("api");
+    tasks.run(&["add", "Write docs", "7-6-2023"]);
+    tasks.run(&["add", "Old idea", "7-6-2023"]);
+    tasks.write_script(
+        "triage",
+        r#"
+            let date = date(ARGS[0]);
+            let added = add(date, "Review PR ~30m");
+            add(date, "Check tests", 1);
+            set(date, 1, "S");
+            edit(date, "1.1", "Check the tests");
+            remove(date, 2);
+
+            for task in tasks(date) {
+                print(`${task.index} ${task.state} ${task.task}`);
+            }
+            print(`added ${added} on ${dates().len()} day`);
+        "#,
+    );
+
+    let output = tasks.run(&["triage", "7-6-2023"]);
+
+    assert_eq!(output, "1 S Write docs\n2 N Review PR\nadded 3 on 1 day\n");
+    assert_eq!(
+        fs::read_to_string(tasks.directory.join("2023-06-07.txt")).unwrap(),
+        "S,Write docs\n  N,Check the tests\nN;estimate=30,Review PR\n"
+    );
+}
+
+#[test]
+fn script_errors_and_unknown_commands_are_reported() {
+    let tasks = TestTasks::new("errors");
+    tasks.write_script("broken", r#"set(today(), 4, "C");"#);
+
+    let broken = tasks.run(&["broken"]);
+    let unknown = tasks.run(&["unknown"]);
+
+    assert!(broken.starts_with("The script failed: `broken`"));
+    assert!(broken.contains("The index entered does not exist for this date"));
+    assert!(unknown.contains("The command enetered is not valid"));
+}
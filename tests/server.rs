@@ -0,0 +1,263 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{env, fs};
+
+use serde_json::{json, Value};
+
+/// A `tudu serve` running against its own tasks directory, which is stopped and cleaned up when
+/// dropped
+struct TestServer {
+    process: Child,
+    port: u16,
+    directory: PathBuf,
+}
+
+impl TestServer {
+    fn start(name: &str, token: Option<&str>) -> TestServer {
+        let directory = env::temp_dir().join(format!("tudu-server-{name}-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        // The port is found by binding to any free one, then freed for the server to use
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let mut command = Command::new(env!("CARGO_BIN_EXE_tudu"));
+        command
+            .args(["serve", "--port", &port.to_string()])
+            .env("TUDU_TASKS", &directory)
+            .env_remove("TUDU_GIT")
+            .env_remove("TUDU_LIST")
+            .env_remove("TUDU_TOKEN")
+            .env_remove("TUDU_DERIVE_STATE");
+
+        if let Some(token) = token {
+            command.args(["--token", token]);
+        }
+
+        let server = TestServer {
+            process: command.spawn().unwrap(),
+            port,
+            directory,
+        };
+
+        let started = Instant::now();
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "the server did not start"
+            );
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        server
+    }
+
+    fn request(&self, method: &str, path: &str, body: Option<Value>) -> (u16, Value) {
+        self.request_with_token(method, path, body, None)
+    }
+
+    fn request_with_token(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+        token: Option<&str>,
+    ) -> (u16, Value) {
+        let body = body.map(|body| body.to_string()).unwrap_or_default();
+        let authorization = token
+            .map(|token| format!("Authorization: Bearer {token}\r\n"))
+            .unwrap_or_default();
+
+        let mut stream = TcpStream::connect(("127.0.0.1", self.port)).unwrap();
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{authorization}Content-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    fn day_file(&self, iso_date: &str) -> String {
+        fs::read_to_string(self.directory.join(format!("{iso_date}.txt"))).unwrap()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+fn task_names(day: &Value) -> Vec<&str> {
+    day["tasks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|task| task["task"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn serve_changes_tasks_through_the_endpoints() {
+    let server = TestServer::start("changes", None);
+
+    let (status, _) = server.request(
+        "POST",
+        "/days/2023-06-07/tasks",
+        Some(json!({ "task": "Write docs ~30m", "priority": "a" })),
+    );
+    assert_eq!(status, 201);
+    server.request(
+        "POST",
+        "/days/2023-06-07/tasks",
+        Some(json!({ "task": "Fix bug" })),
+    );
+    server.request(
+        "POST",
+        "/days/7-6-2023/tasks",
+        Some(json!({ "task": "Reproduce", "parent": 2 })),
+    );
+
+    let (status, day) = server.request("GET", "/days/2023-06-07", None);
+    assert_eq!(status, 200);
+    assert_eq!(day["date"], "2023-06-07");
+    assert_eq!(task_names(&day), vec!["Write docs", "Fix bug"]);
+    assert_eq!(day["tasks"][0]["estimate_minutes"], 30);
+    assert_eq!(day["tasks"][0]["priority"], "A");
+    assert_eq!(day["tasks"][1]["subtasks"][0]["index"], "2.1");
+
+    let (status, day) = server.request(
+        "PATCH",
+        "/days/2023-06-07/tasks/1",
+        Some(json!({ "state": "C", "task": "Write the docs" })),
+    );
+    assert_eq!(status, 200);
+    assert_eq!(day["tasks"][0]["state"], "C");
+    assert_eq!(day["tasks"][0]["task"], "Write the docs");
+
+    let (status, day) = server.request(
+        "POST",
+        "/days/2023-06-07/tasks/2/move",
+        Some(json!({ "to": 1 })),
+    );
+    assert_eq!(status, 200);
+    assert_eq!(task_names(&day), vec!["Fix bug", "Write the docs"]);
+
+    let (status, day) = server.request("DELETE", "/days/2023-06-07/tasks/1.1", None);
+    assert_eq!(status, 200);
+    assert_eq!(day["tasks"][0]["subtasks"], json!([]));
+
+    assert_eq!(
+        server.day_file("2023-06-07"),
+        "N,Fix bug\nC;estimate=30;priority=A,Write the docs\n"
+    );
+}
+
+#[test]
+fn serve_gives_days_in_a_range() {
+    let server = TestServer::start("range", None);
+
+    server.request(
+        "POST",
+        "/days/2023-06-05/tasks",
+        Some(json!({ "task": "Monday" })),
+    );
+    server.request(
+        "POST",
+        "/days/2023-06-07/tasks",
+        Some(json!({ "task": "Wednesday" })),
+    );
+
+    let (status, days) = server.request("GET", "/days?from=2023-06-05&to=2023-06-07", None);
+
+    assert_eq!(status, 200);
+    let days: Vec<(&str, Vec<&str>)> = days
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|day| (day["date"].as_str().unwrap(), task_names(day)))
+        .collect();
+    assert_eq!(
+        days,
+        vec![
+            ("2023-06-05", vec!["Monday"]),
+            ("2023-06-06", vec![]),
+            ("2023-06-07", vec!["Wednesday"]),
+        ]
+    );
+}
+
+#[test]
+fn serve_reports_errors_as_json() {
+    let server = TestServer::start("errors", None);
+
+    let (status, body) = server.request("DELETE", "/days/2023-06-07/tasks/4", None);
+    assert_eq!(status, 404);
+    assert!(body["error"].is_string());
+
+    let (status, _) = server.request(
+        "PATCH",
+        "/days/2023-06-07/tasks/1",
+        Some(json!({ "state": "Done" })),
+    );
+    assert_eq!(status, 400);
+
+    let (status, _) = server.request(
+        "POST",
+        "/days/2023-06-07/tasks",
+        Some(json!({ "task": "Write docs\nC,Injected" })),
+    );
+    assert_eq!(status, 400);
+
+    server.request(
+        "POST",
+        "/days/2023-06-07/tasks",
+        Some(json!({ "task": "Write docs" })),
+    );
+    let (status, body) = server.request(
+        "PATCH",
+        "/days/2023-06-07/tasks/1",
+        Some(json!({ "task": "Write docs\r\n  C;est=30,Injected" })),
+    );
+    assert_eq!(status, 400);
+    assert_eq!(
+        body["error"],
+        "Invalid argument: the task spans more than one line"
+    );
+    assert_eq!(
+        fs::read_to_string(server.directory.join("2023-06-07.txt")).unwrap(),
+        "N,Write docs\n"
+    );
+}
+
+#[test]
+fn serve_with_token_requires_it() {
+    let server = TestServer::start("token", Some("secret"));
+
+    let (status, _) = server.request("GET", "/days/2023-06-07", None);
+    assert_eq!(status, 401);
+
+    let (status, _) = server.request_with_token("GET", "/days/2023-06-07", None, Some("wrong"));
+    assert_eq!(status, 401);
+
+    let (status, day) = server.request_with_token("GET", "/days/2023-06-07", None, Some("secret"));
+    assert_eq!(status, 200);
+    assert_eq!(day["tasks"], json!([]));
+}